@@ -0,0 +1,185 @@
+use crate::state;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Write};
+use std::str::FromStr;
+use tracing::error;
+
+const AUDIT_FILE: &str = "audit.jsonl";
+
+/// Kinds of actions the bot takes against Gerrit or chat
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    /// Abandoned a change by policy
+    Abandon,
+    /// Warned a change of its upcoming abandonment
+    Warn,
+    /// Sent a community review reminder
+    Reminder,
+    /// Posted a review state notification
+    Notification,
+    /// Sent a subscriber a direct message
+    Subscription,
+    /// Added a reviewer to a change
+    Assign,
+    /// Retriggered CI on a change
+    Retrigger,
+    /// Reported a change CI keeps missing
+    Escalate,
+}
+
+impl AuditAction {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AuditAction::Abandon => "abandon",
+            AuditAction::Warn => "warn",
+            AuditAction::Reminder => "reminder",
+            AuditAction::Notification => "notification",
+            AuditAction::Subscription => "subscription",
+            AuditAction::Assign => "assign",
+            AuditAction::Retrigger => "retrigger",
+            AuditAction::Escalate => "escalate",
+        }
+    }
+}
+
+impl FromStr for AuditAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "abandon" => Ok(AuditAction::Abandon),
+            "warn" => Ok(AuditAction::Warn),
+            "reminder" => Ok(AuditAction::Reminder),
+            "notification" => Ok(AuditAction::Notification),
+            "subscription" => Ok(AuditAction::Subscription),
+            "assign" => Ok(AuditAction::Assign),
+            "retrigger" => Ok(AuditAction::Retrigger),
+            "escalate" => Ok(AuditAction::Escalate),
+            _ => Err(format!("Unknown audit action: {}", s)),
+        }
+    }
+}
+
+/// One action taken by the bot
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub time: DateTime<Utc>,
+    pub action: AuditAction,
+    /// Numbers of the changes the action concerned
+    pub changes: Vec<u64>,
+    /// Where the action went: "gerrit", or a chat backend and channel
+    pub target: String,
+    /// Policy, reminder job or notification rule which caused the action
+    pub source: String,
+    /// Text posted to Gerrit or chat
+    #[serde(default)]
+    pub message: String,
+    /// Why the action failed, if it did
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+impl AuditEntry {
+    pub fn new(
+        action: AuditAction,
+        source: &str,
+        target: &str,
+        changes: Vec<u64>,
+    ) -> AuditEntry {
+        AuditEntry {
+            time: Utc::now(),
+            action,
+            changes,
+            target: target.to_string(),
+            source: source.to_string(),
+            message: String::new(),
+            error: None,
+        }
+    }
+
+    pub fn message(mut self, message: &str) -> AuditEntry {
+        self.message = message.to_string();
+        self
+    }
+
+    pub fn result(mut self, result: &Result<(), String>) -> AuditEntry {
+        self.error = result.clone().err();
+        self
+    }
+
+    /// Append the entry to the audit log in the state directory, unless
+    /// state is only kept in memory.
+    pub fn record(self) {
+        if state::in_memory() {
+            return;
+        }
+        let dir = state::state_dir();
+        let path = dir.join(AUDIT_FILE);
+        let line = match serde_json::to_string(&self) {
+            Ok(line) => line,
+            Err(e) => {
+                error!("Failed to serialize audit entry: {}", e);
+                return;
+            }
+        };
+
+        // Entries are appended one per line so the log never needs
+        // rewriting
+        if let Err(e) = std::fs::create_dir_all(&dir).and_then(|_| {
+            std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .and_then(|mut file| writeln!(file, "{}", line))
+        }) {
+            error!("Failed to write audit log {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// Criteria for searching the audit log
+#[derive(Debug, Clone, Default)]
+pub struct AuditQuery {
+    pub change: Option<u64>,
+    pub action: Option<AuditAction>,
+    pub since: Option<DateTime<Utc>>,
+    pub limit: Option<usize>,
+}
+
+impl AuditQuery {
+    fn matches(&self, entry: &AuditEntry) -> bool {
+        self.change
+            .is_none_or(|change| entry.changes.contains(&change))
+            && self.action.is_none_or(|action| entry.action == action)
+            && self.since.is_none_or(|since| entry.time >= since)
+    }
+}
+
+/// Search the audit log, newest entries first.
+pub fn query(query: &AuditQuery) -> Vec<AuditEntry> {
+    let path = state::state_dir().join(AUDIT_FILE);
+    let Ok(file) = std::fs::File::open(&path) else {
+        return Vec::new();
+    };
+
+    let mut entries: Vec<AuditEntry> = BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|line| match serde_json::from_str(&line) {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                error!("Invalid audit entry in {}: {}", path.display(), e);
+                None
+            }
+        })
+        .filter(|entry| query.matches(entry))
+        .collect();
+
+    entries.reverse();
+    if let Some(limit) = query.limit {
+        entries.truncate(limit);
+    }
+    entries
+}
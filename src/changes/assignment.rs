@@ -0,0 +1,594 @@
+use crate::audit::{AuditAction, AuditEntry};
+use crate::changes::container::Change;
+use crate::changes::exemptions::Automation;
+use crate::changes::filter::should_include_change;
+use crate::changes::patterns::Patterns;
+use crate::changes::serve::CHANGE_DETAIL_OPTIONS;
+use crate::changes::status::ReviewState;
+use crate::config;
+use crate::context::{ServiceContext, ServiceContextData};
+use crate::gerrit::connection::GerritConnection;
+use crate::gerrit::data::ChangeInfo;
+use crate::state;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use tokio::time::sleep;
+use tracing::{error, info, warn};
+
+/// Environment variable naming the YAML file enabling reviewer assignment.
+const ASSIGNMENT_CONFIG_ENV: &str = "REVIEWER_ASSIGNMENT_CONFIG";
+
+const ASSIGNMENTS_FILE: &str = "reviewer_assignments.json";
+
+/// How often changes are checked for missing reviewers.
+const ASSIGNMENT_INTERVAL_SECS: u64 = 60 * 60;
+
+/// Most recently merged changes per project searched for reviewers
+const MERGED_CHANGES_PER_PROJECT: usize = 100;
+
+/// Accounts never assigned as reviewers
+const BOT_ACCOUNTS: [&str; 1] = ["jenkins-openbmc-ci"];
+
+fn default_delay_hours() -> i64 {
+    72
+}
+
+fn default_reviewers_per_change() -> usize {
+    2
+}
+
+fn default_max_assignments() -> usize {
+    5
+}
+
+fn default_recent_days() -> i64 {
+    90
+}
+
+/// Settings for adding reviewers to changes nobody has reviewed
+#[derive(Debug, Clone, Deserialize)]
+pub struct AssignmentConfig {
+    /// Hours a change waits in community review before reviewers are added
+    #[serde(default = "default_delay_hours")]
+    pub delay_hours: i64,
+    /// Reviewers added to each change
+    #[serde(default = "default_reviewers_per_change")]
+    pub reviewers_per_change: usize,
+    /// Changes a reviewer may have been assigned and not yet reviewed
+    #[serde(default = "default_max_assignments")]
+    pub max_assignments: usize,
+    /// Days within which a vote on the same files, in an open or merged
+    /// change, makes someone a candidate
+    #[serde(default = "default_recent_days")]
+    pub recent_days: i64,
+    /// Project regexes to assign reviewers in; all projects when empty
+    #[serde(default)]
+    pub projects: Patterns,
+    /// Usernames or emails never assigned
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+impl AssignmentConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.delay_hours < 0 || self.recent_days <= 0 {
+            return Err(
+                "delay_hours must not be negative and recent_days must be positive"
+                    .to_string()
+            );
+        }
+        if self.reviewers_per_change == 0 || self.max_assignments == 0 {
+            return Err(
+                "reviewers_per_change and max_assignments must be positive"
+                    .to_string(),
+            );
+        }
+        Ok(())
+    }
+
+    /// Load the settings from the file named by REVIEWER_ASSIGNMENT_CONFIG or
+    /// the configuration file.  Assignment is disabled, returning None, if
+    /// neither has any.
+    pub fn load() -> Result<Option<AssignmentConfig>, String> {
+        let Ok(path) = std::env::var(ASSIGNMENT_CONFIG_ENV) else {
+            return Ok(config::get().reviewer_assignment.clone());
+        };
+        let text = std::fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+        let config = serde_yaml::from_str::<AssignmentConfig>(&text)
+            .map_err(|e| format!("Failed to parse {}: {}", path, e))?;
+        config.validate()?;
+        Ok(Some(config))
+    }
+
+    fn includes_project(&self, project: &str) -> bool {
+        self.projects.is_empty() || self.projects.is_match(project)
+    }
+}
+
+/// A reviewer added to a change by the bot
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Assignment {
+    pub reviewer: String,
+    pub assigned: DateTime<Utc>,
+}
+
+/// Reviewers added by the bot, and those Gerrit would not add, by change
+/// number, persisted in the state directory.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Assignments {
+    assignments: HashMap<u64, Vec<Assignment>>,
+    #[serde(default)]
+    rejected: HashMap<u64, Vec<String>>,
+}
+
+impl Assignments {
+    pub fn load() -> Assignments {
+        state::load(ASSIGNMENTS_FILE)
+    }
+
+    pub fn save(&self) {
+        state::save(ASSIGNMENTS_FILE, self);
+    }
+
+    /// Record that a reviewer was added to a change
+    pub fn record(&mut self, id_number: u64, reviewer: &str) {
+        self.assignments
+            .entry(id_number)
+            .or_default()
+            .push(Assignment {
+                reviewer: reviewer.to_string(),
+                assigned: Utc::now(),
+            });
+        self.save();
+    }
+
+    /// Record that Gerrit would not add a reviewer to a change, so they are
+    /// not picked for it again
+    pub fn reject(&mut self, id_number: u64, reviewer: &str) {
+        self.rejected
+            .entry(id_number)
+            .or_default()
+            .push(reviewer.to_string());
+        self.save();
+    }
+
+    /// Get the reviewers Gerrit would not add to a change
+    pub fn rejected(&self, id_number: u64) -> &[String] {
+        self.rejected
+            .get(&id_number)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Forget changes which are no longer open
+    pub fn prune(&mut self, open: &HashSet<u64>) {
+        let count = self.assignments.len() + self.rejected.len();
+        self.assignments
+            .retain(|id_number, _| open.contains(id_number));
+        self.rejected
+            .retain(|id_number, _| open.contains(id_number));
+        if self.assignments.len() + self.rejected.len() != count {
+            self.save();
+        }
+    }
+
+    /// Get the reviewers added to a change
+    pub fn get(&self, id_number: u64) -> &[Assignment] {
+        self.assignments
+            .get(&id_number)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Count the changes assigned to each reviewer which still await
+    /// community review.
+    pub fn loads(&self, changes: &[Change]) -> HashMap<String, usize> {
+        let mut loads = HashMap::new();
+        for change in changes {
+            if change.review_state != ReviewState::CommunityReview {
+                continue;
+            }
+            for assignment in self.get(change.change.id_number) {
+                *loads.entry(assignment.reviewer.clone()).or_default() += 1;
+            }
+        }
+        loads
+    }
+}
+
+// Check if anyone but the owner has voted on a change.
+fn has_votes(change: &ChangeInfo) -> bool {
+    change.labels.get("Code-Review").is_some_and(|votes| {
+        votes.iter().any(|vote| {
+            vote.value != 0 && vote.username != change.owner.username
+        })
+    })
+}
+
+// Get the candidate reviewers for a change, best first: its owners, the
+// reviewers listed in OWNERS, then people who recently voted on open or
+// merged changes to the same files.  Candidates are emails where known.
+fn candidates(
+    ctx: &ServiceContextData,
+    change: &Change,
+    merged: &[ChangeInfo],
+    config: &AssignmentConfig,
+) -> Vec<String> {
+    let gerrit_change = &change.change;
+    let mut candidates: Vec<String> = ctx
+        .owners
+        .owners_for_change(gerrit_change)
+        .into_iter()
+        .chain(ctx.owners.reviewers_for_change(gerrit_change))
+        .collect();
+
+    // Rank recent voters by how many of their votes touched the same files,
+    // leaving out magic files like /COMMIT_MSG
+    let files: HashSet<&String> = gerrit_change
+        .revisions
+        .get(&gerrit_change.current_revision)
+        .map(|revision| {
+            revision
+                .files
+                .keys()
+                .filter(|path| !path.starts_with('/'))
+                .collect()
+        })
+        .unwrap_or_default();
+    let cutoff = Utc::now() - Duration::days(config.recent_days);
+    let mut voters: BTreeMap<String, usize> = BTreeMap::new();
+    let open = ctx.changes.changes.values().map(|other| &other.change);
+    for other in open.chain(merged) {
+        if other.id_number == gerrit_change.id_number
+            || other.project != gerrit_change.project
+        {
+            continue;
+        }
+        let shared = other
+            .revisions
+            .get(&other.current_revision)
+            .map(|revision| {
+                revision.files.keys().filter(|f| files.contains(f)).count()
+            })
+            .unwrap_or(0);
+        if shared == 0 {
+            continue;
+        }
+        for vote in other
+            .labels
+            .get("Code-Review")
+            .iter()
+            .flat_map(|v| v.iter())
+        {
+            if vote.value == 0
+                || vote.username == other.owner.username
+                || vote.date().is_none_or(|date| date < cutoff)
+            {
+                continue;
+            }
+            let voter = if vote.email.is_empty() {
+                vote.username.clone()
+            } else {
+                vote.email.to_lowercase()
+            };
+            *voters.entry(voter).or_default() += shared;
+        }
+    }
+    let mut voters: Vec<(String, usize)> = voters.into_iter().collect();
+    voters.sort_by_key(|(_, shared)| std::cmp::Reverse(*shared));
+    candidates.extend(voters.into_iter().map(|(voter, _)| voter));
+
+    // Leave out the author, existing reviewers, excluded accounts and those
+    // Gerrit would not add before
+    let mut skip: HashSet<String> = config
+        .exclude
+        .iter()
+        .map(|account| account.to_lowercase())
+        .chain(BOT_ACCOUNTS.iter().map(|account| account.to_string()))
+        .chain([
+            gerrit_change.owner.username.to_lowercase(),
+            gerrit_change.owner.email.to_lowercase(),
+            ctx.gerrit.get_username().to_lowercase(),
+        ])
+        .chain(
+            ctx.assignments
+                .rejected(gerrit_change.id_number)
+                .iter()
+                .map(|reviewer| reviewer.to_lowercase()),
+        )
+        .collect();
+    for votes in gerrit_change.labels.values() {
+        for vote in votes.iter() {
+            skip.insert(vote.username.to_lowercase());
+            skip.insert(vote.email.to_lowercase());
+        }
+    }
+
+    let mut seen = HashSet::new();
+    candidates
+        .into_iter()
+        .filter(|candidate| !skip.contains(&candidate.to_lowercase()))
+        .filter(|candidate| seen.insert(candidate.clone()))
+        .collect()
+}
+
+// Check if a change has waited too long in community review without
+// reviewers.
+fn needs_reviewers(
+    ctx: &ServiceContextData,
+    change: &Change,
+    config: &AssignmentConfig,
+    now: DateTime<Utc>,
+) -> bool {
+    change.review_state == ReviewState::CommunityReview
+        && now - change.review_state_updated
+            >= Duration::hours(config.delay_hours)
+        && ctx.assignments.get(change.change.id_number).is_empty()
+        && ctx.claims.get(change.change.id_number).is_none()
+        && !has_votes(&change.change)
+        && config.includes_project(&change.change.project)
+        && should_include_change(&change.change)
+        && !ctx
+            .exemptions
+            .is_exempt(&change.change, Automation::Assignment)
+}
+
+// Fetch the changes recently merged in each project, whose reviewers are
+// candidates too.  Projects Gerrit cannot be queried for are left out.
+async fn merged_changes<G: GerritConnection + Sync>(
+    gerrit: &G,
+    projects: &BTreeSet<String>,
+    config: &AssignmentConfig,
+) -> Vec<ChangeInfo> {
+    let mut merged = Vec::new();
+    for project in projects {
+        let query = format!(
+            "project:\"{}\" status:merged -age:{}d limit:{}",
+            project.replace('"', ""),
+            config.recent_days,
+            MERGED_CHANGES_PER_PROJECT
+        );
+        if let Ok(changes) =
+            gerrit.query_changes(&query, &CHANGE_DETAIL_OPTIONS).await
+        {
+            merged.extend(changes);
+        }
+    }
+    merged
+}
+
+// Pick reviewers for every change that has waited too long in community
+// review, within the reviewers' load limits.
+fn select_assignments(
+    context: &ServiceContext,
+    config: &AssignmentConfig,
+    merged: &[ChangeInfo],
+    now: DateTime<Utc>,
+) -> Vec<(Change, Vec<String>)> {
+    let ctx = context.lock().unwrap();
+    let mut changes: Vec<Change> =
+        ctx.changes.changes.values().cloned().collect();
+    let mut loads = ctx.assignments.loads(&changes);
+
+    // Changes waiting the longest get first pick of the reviewers
+    changes.sort_by_key(|change| change.review_state_updated);
+
+    let mut selected = Vec::new();
+    for change in changes {
+        if !needs_reviewers(&ctx, &change, config, now) {
+            continue;
+        }
+
+        let reviewers: Vec<String> = candidates(&ctx, &change, merged, config)
+            .into_iter()
+            .filter(|candidate| {
+                loads.get(candidate).copied().unwrap_or(0)
+                    < config.max_assignments
+            })
+            .take(config.reviewers_per_change)
+            .collect();
+        if reviewers.is_empty() {
+            continue;
+        }
+        for reviewer in &reviewers {
+            *loads.entry(reviewer.clone()).or_default() += 1;
+        }
+        selected.push((change, reviewers));
+    }
+    selected
+}
+
+// Add the selected reviewers to their changes.
+async fn assign_reviewers(context: &ServiceContext, config: &AssignmentConfig) {
+    {
+        let mut ctx = context.lock().unwrap();
+        let open: HashSet<u64> = ctx.changes.changes.keys().copied().collect();
+        // Nothing is loaded yet, or Gerrit could not be reached
+        if !open.is_empty() {
+            ctx.assignments.prune(&open);
+        }
+    }
+
+    let now = Utc::now();
+    let gerrit = context.get_gerrit();
+    let projects: BTreeSet<String> = {
+        let ctx = context.lock().unwrap();
+        ctx.changes
+            .changes
+            .values()
+            .filter(|change| needs_reviewers(&ctx, change, config, now))
+            .map(|change| change.change.project.clone())
+            .collect()
+    };
+    let merged = merged_changes(&gerrit, &projects, config).await;
+    let selected = select_assignments(context, config, &merged, now);
+
+    for (change, reviewers) in selected {
+        for reviewer in reviewers {
+            let added = gerrit.add_reviewer(&change.change.id, &reviewer).await;
+            AuditEntry::new(
+                AuditAction::Assign,
+                "reviewer-assignment",
+                "gerrit",
+                vec![change.change.id_number],
+            )
+            .message(&reviewer)
+            .result(&if added {
+                Ok(())
+            } else {
+                Err("Rejected by Gerrit".to_string())
+            })
+            .record();
+
+            if !added {
+                warn!(
+                    "Was not able to add reviewer {} to change {}",
+                    reviewer, change.change.id_number
+                );
+                context
+                    .lock()
+                    .unwrap()
+                    .assignments
+                    .reject(change.change.id_number, &reviewer);
+                continue;
+            }
+            info!(
+                "Added reviewer {} to change {}",
+                reviewer, change.change.id_number
+            );
+            context
+                .lock()
+                .unwrap()
+                .assignments
+                .record(change.change.id_number, &reviewer);
+        }
+    }
+}
+
+/// Periodic task adding reviewers to changes stuck in community review, if
+/// enabled by REVIEWER_ASSIGNMENT_CONFIG
+pub async fn serve(context: ServiceContext) {
+    let config = match AssignmentConfig::load() {
+        Ok(Some(config)) => config,
+        Ok(None) => return,
+        Err(e) => {
+            error!("Reviewer assignment disabled: {}", e);
+            return;
+        }
+    };
+    info!("Reviewer assignment enabled: {:?}", config);
+
+    loop {
+        sleep(std::time::Duration::from_secs(ASSIGNMENT_INTERVAL_SECS)).await;
+        assign_reviewers(&context, &config).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gerrit::data::{ChangeStatus, test_change};
+    use crate::gerrit::stub::StubGerrit;
+    use serde_json::{Value, json};
+
+    fn vote(username: &str, value: i64) -> Value {
+        let date = Utc::now() - Duration::days(1);
+        json!({
+            "username": username,
+            "value": value,
+            "date": date.format("%Y-%m-%d %H:%M:%S%.9f").to_string(),
+        })
+    }
+
+    // A change in community review for a week, touching src/main.cpp
+    fn waiting(id_number: u64, votes: Vec<Value>) -> Change {
+        Change {
+            change: test_change(
+                id_number,
+                json!({
+                    "labels": {"Code-Review": {"all": votes}},
+                    "current_revision": "abc",
+                    "revisions": {"abc": {"files": {"src/main.cpp": {}}}},
+                }),
+            ),
+            review_state: ReviewState::CommunityReview,
+            review_state_updated: Utc::now() - Duration::days(7),
+        }
+    }
+
+    // A context with change 1 to assign and change 2, which bob and carol
+    // recently reviewed
+    fn context(votes: Vec<Value>) -> ServiceContext {
+        let context = ServiceContext::detached();
+        {
+            let mut ctx = context.lock().unwrap();
+            for change in [
+                waiting(1, votes),
+                waiting(2, vec![vote("bob", 1), vote("carol", -1)]),
+            ] {
+                ctx.changes.changes.insert(change.change.id_number, change);
+            }
+        }
+        context
+    }
+
+    fn selected(context: &ServiceContext) -> Vec<(u64, Vec<String>)> {
+        let config: AssignmentConfig = serde_yaml::from_str("{}").unwrap();
+        select_assignments(context, &config, &[], Utc::now())
+            .into_iter()
+            .map(|(change, reviewers)| (change.change.id_number, reviewers))
+            .collect()
+    }
+
+    #[test]
+    fn recent_reviewers_of_the_same_files_are_assigned() {
+        let context = context(vec![vote("alice", 1)]);
+        assert_eq!(
+            selected(&context),
+            vec![(1, vec!["bob".to_string(), "carol".to_string()])]
+        );
+    }
+
+    #[tokio::test]
+    async fn reviewers_of_merged_changes_are_assigned() {
+        let context = ServiceContext::detached();
+        let change = waiting(1, Vec::new());
+        context.lock().unwrap().changes.changes.insert(1, change);
+        let mut merged = waiting(3, vec![vote("dave", 2)]).change;
+        merged.status = ChangeStatus::Merged;
+        let gerrit = StubGerrit::new(Ok(vec![merged]));
+
+        let config: AssignmentConfig = serde_yaml::from_str("{}").unwrap();
+        let projects = BTreeSet::from(["openbmc/bmcweb".to_string()]);
+        let merged = merged_changes(&gerrit, &projects, &config).await;
+        assert_eq!(
+            gerrit.queries(),
+            vec!["project:\"openbmc/bmcweb\" status:merged -age:90d limit:100"]
+        );
+        assert_eq!(
+            select_assignments(&context, &config, &merged, Utc::now())
+                .into_iter()
+                .map(|(change, reviewers)| (change.change.id_number, reviewers))
+                .collect::<Vec<_>>(),
+            vec![(1, vec!["dave".to_string()])]
+        );
+    }
+
+    #[test]
+    fn changes_with_votes_are_not_assigned() {
+        let context = context(vec![vote("dave", 1)]);
+        assert!(selected(&context).is_empty());
+    }
+
+    #[test]
+    fn rejected_reviewers_are_not_picked_again() {
+        let context = context(Vec::new());
+        context.lock().unwrap().assignments = serde_json::from_value(
+            json!({"assignments": {}, "rejected": {"1": ["bob"]}}),
+        )
+        .unwrap();
+        assert_eq!(selected(&context), vec![(1, vec!["carol".to_string()])]);
+    }
+}
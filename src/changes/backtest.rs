@@ -0,0 +1,213 @@
+use crate::changes::exemptions::Automation;
+use crate::changes::policy::{Action, PoliciesConfig, Warning};
+use crate::changes::snapshot::Snapshot;
+use crate::chat::reminders::{RemindersConfig, get_community_review_changes};
+use crate::chat::selection::{ReminderHistory, select_changes};
+use crate::context::ServiceContext;
+use crate::gerrit::data::{ChangeInfo, ChangeStatus};
+use chrono::{Duration, NaiveDate};
+use std::collections::{HashMap, HashSet};
+
+/// What the automation would have done on one day
+#[derive(Debug, Clone, Default)]
+pub struct DayReport {
+    pub date: NaiveDate,
+    /// Changes warned of abandonment, with the policy
+    pub warned: Vec<(u64, String)>,
+    /// Changes abandoned, with the policy
+    pub abandoned: Vec<(u64, String)>,
+    /// Changes advertised by each reminder run
+    pub advertised: Vec<(String, Vec<u64>)>,
+}
+
+impl DayReport {
+    fn is_empty(&self) -> bool {
+        self.warned.is_empty()
+            && self.abandoned.is_empty()
+            && self.advertised.is_empty()
+    }
+}
+
+/// Replay snapshots through the abandonment policies and reminder
+/// selection, day by day, from the first snapshot until `extra_days` after
+/// the last.  Each day uses the latest snapshot taken by its end, with the
+/// changes abandoned so far removed.  Nothing is sent to Gerrit or chat and
+/// no state is saved.  Reminder selection is partly random, so the
+/// advertised changes are one possible outcome.
+pub fn run(
+    mut snapshots: Vec<Snapshot>,
+    policies: &PoliciesConfig,
+    reminders: &RemindersConfig,
+    extra_days: i64,
+) -> Vec<DayReport> {
+    snapshots.sort_by_key(|snapshot| snapshot.time);
+    let (Some(first), Some(last)) = (snapshots.first(), snapshots.last())
+    else {
+        return Vec::new();
+    };
+
+    let context = ServiceContext::detached();
+    let exemptions = context.lock().unwrap().exemptions.clone();
+    let mut warnings: HashMap<u64, Warning> = HashMap::new();
+    let mut abandoned: HashSet<u64> = HashSet::new();
+    let mut history = ReminderHistory::default();
+    let mut reports = Vec::new();
+
+    let mut date = first.time.date_naive();
+    let end = last.time.date_naive() + Duration::days(extra_days);
+    while date <= end {
+        let day_start = date.and_hms_opt(0, 0, 0).unwrap().and_utc();
+        let day_end = day_start + Duration::days(1);
+        let noon = day_start + Duration::hours(12);
+        let snapshot = snapshots
+            .iter()
+            .rev()
+            .find(|snapshot| snapshot.time < day_end)
+            .unwrap_or(first);
+        let mut report = DayReport {
+            date,
+            ..Default::default()
+        };
+
+        let open: Vec<&ChangeInfo> = snapshot
+            .changes
+            .iter()
+            .filter(|change| {
+                change.status == ChangeStatus::New
+                    && !change.work_in_progress
+                    && !abandoned.contains(&change.id_number)
+            })
+            .collect();
+
+        for change in &open {
+            let id = change.id_number;
+            if exemptions.is_exempt(change, Automation::Abandon) {
+                warnings.remove(&id);
+                continue;
+            }
+            if warnings.get(&id).is_some_and(|w| w.outdated(change)) {
+                warnings.remove(&id);
+            }
+
+            match policies.decide(change, warnings.get(&id), noon) {
+                Action::None => {}
+                Action::Warn(verdict) => {
+                    // Unlike a real warning, the simulated one does not
+                    // update the change.
+                    warnings.insert(
+                        id,
+                        Warning {
+                            policy: verdict.policy.clone(),
+                            warned: noon,
+                            previous_updated: change.updated,
+                            updated: change.updated,
+                        },
+                    );
+                    report.warned.push((id, verdict.policy));
+                }
+                Action::Abandon(verdict) => {
+                    warnings.remove(&id);
+                    abandoned.insert(id);
+                    report.abandoned.push((id, verdict.policy));
+                }
+            }
+        }
+
+        // Keep the container between days so review states carry over
+        {
+            let mut ctx = context.lock().unwrap();
+            let gone: Vec<ChangeInfo> = ctx
+                .changes
+                .changes
+                .values()
+                .filter(|change| {
+                    !open.iter().any(|c| c.id_number == change.change.id_number)
+                        || abandoned.contains(&change.change.id_number)
+                })
+                .map(|change| change.change.clone())
+                .collect();
+            for change in &gone {
+                ctx.changes.remove(change);
+            }
+            for change in &open {
+                if !abandoned.contains(&change.id_number) {
+                    ctx.changes.set(change);
+                }
+            }
+        }
+
+        for job in &reminders.jobs {
+            let mut next = job.next_run(day_start - Duration::seconds(1));
+            while let Some(time) = next.filter(|time| *time < day_end) {
+                for (channel, changes) in
+                    get_community_review_changes(&context, job, time)
+                {
+                    let selected = select_changes(
+                        changes,
+                        job,
+                        &history,
+                        &HashSet::new(),
+                        time,
+                    );
+                    if selected.is_empty() {
+                        continue;
+                    }
+                    history.mark(&selected, time);
+                    report.advertised.push((
+                        format!(
+                            "{} {} to {}",
+                            time.format("%H:%M"),
+                            job.name,
+                            channel
+                        ),
+                        selected
+                            .iter()
+                            .map(|change| change.change.id_number)
+                            .collect(),
+                    ));
+                }
+                next = job.next_run(time);
+            }
+        }
+
+        reports.push(report);
+        date = date.succ_opt().unwrap();
+    }
+    reports
+}
+
+/// Format backtest results as text, one section per day with any action
+pub fn report_text(reports: &[DayReport]) -> String {
+    let join = |ids: &[u64]| {
+        ids.iter()
+            .map(u64::to_string)
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    let mut text = String::new();
+    for report in reports.iter().filter(|report| !report.is_empty()) {
+        text += &format!("{}\n", report.date);
+        for (id, policy) in &report.warned {
+            text += &format!("  warn     {} ({})\n", id, policy);
+        }
+        for (id, policy) in &report.abandoned {
+            text += &format!("  abandon  {} ({})\n", id, policy);
+        }
+        for (run, ids) in &report.advertised {
+            text += &format!("  remind   {}: {}\n", run, join(ids));
+        }
+    }
+
+    let total = |count: fn(&DayReport) -> usize| -> usize {
+        reports.iter().map(count).sum()
+    };
+    text += &format!(
+        "{} days: {} warned, {} abandoned, {} reminders\n",
+        reports.len(),
+        total(|report| report.warned.len()),
+        total(|report| report.abandoned.len()),
+        total(|report| report.advertised.len()),
+    );
+    text
+}
@@ -12,12 +12,25 @@ pub struct Change {
     pub review_state_updated: DateTime<Utc>,
 }
 
+/// A change whose review state differs from the last time it was seen
+#[derive(Debug, Clone)]
+pub struct Transition {
+    pub change: Change,
+    pub previous: Status::ReviewState,
+}
+
 #[derive(Debug, Clone)]
 pub struct Container {
     pub changes: HashMap<u64, Change>,
     pub changes_by_id: HashMap<String, u64>,
 }
 
+impl Default for Container {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Container {
     pub fn new() -> Container {
         Container {
@@ -26,7 +39,9 @@ impl Container {
         }
     }
 
-    pub fn set(&mut self, change: &GerritChange) {
+    /// Add or update a change, returning the review state transition if a
+    /// known change moved to a new state.
+    pub fn set(&mut self, change: &GerritChange) -> Option<Transition> {
         debug!("Change: {:?}", change);
         if change.status != GerritChangeStatus::New {
             if self.changes.contains_key(&change.id_number) {
@@ -45,28 +60,35 @@ impl Container {
             let review_state = Status::review_state(change);
             debug!("Change Status = {:?}", review_state);
 
-            let review_state_updated =
-                if let Some(i) = self.changes.get(&change.id_number) {
-                    if i.review_state == review_state {
-                        i.review_state_updated
-                    } else {
-                        change.updated
-                    }
-                } else {
-                    change.updated
-                };
+            let previous = self
+                .changes
+                .get(&change.id_number)
+                .map(|i| (i.review_state.clone(), i.review_state_updated));
+
+            let review_state_updated = match previous {
+                Some((ref state, updated)) if *state == review_state => updated,
+                _ => change.updated,
+            };
 
-            self.changes.insert(
-                change.id_number,
-                Change {
-                    change: change.clone(),
-                    review_state: review_state,
-                    review_state_updated: review_state_updated,
-                },
-            );
+            let updated = Change {
+                change: change.clone(),
+                review_state,
+                review_state_updated,
+            };
+            self.changes.insert(change.id_number, updated.clone());
             self.changes_by_id
                 .insert(change.change_id.clone(), change.id_number);
+
+            if let Some((previous, _)) = previous
+                && previous != updated.review_state
+            {
+                return Some(Transition {
+                    change: updated,
+                    previous,
+                });
+            }
         }
+        None
     }
 
     pub fn get(&self, id: u64) -> Option<Change> {
@@ -0,0 +1,215 @@
+use crate::changes::patterns::Patterns;
+use crate::config;
+use crate::gerrit::data::ChangeInfo;
+use serde::Deserialize;
+
+/// Environment variable naming the YAML file with the exemptions.
+const EXEMPTIONS_CONFIG_ENV: &str = "EXEMPTIONS_CONFIG";
+
+/// Automated handling a change can be exempted from
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Automation {
+    /// Abandonment policies, including their warnings
+    Abandon,
+    /// Community review reminders
+    Reminders,
+    /// Web, chat and Discord reports
+    Reports,
+    /// Automatic reviewer assignment
+    Assignment,
+    /// CI retriggers
+    Retrigger,
+}
+
+impl Automation {
+    pub const ALL: [Automation; 5] = [
+        Automation::Abandon,
+        Automation::Reminders,
+        Automation::Reports,
+        Automation::Assignment,
+        Automation::Retrigger,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Automation::Abandon => "abandonment",
+            Automation::Reminders => "reminders",
+            Automation::Reports => "reports",
+            Automation::Assignment => "reviewer assignment",
+            Automation::Retrigger => "CI retriggers",
+        }
+    }
+}
+
+/// Exempt changes carrying a hashtag, or with a matching topic, from some
+/// automation
+#[derive(Debug, Clone, Deserialize)]
+pub struct Exemption {
+    pub name: String,
+    /// Hashtags, compared case-insensitively
+    #[serde(default)]
+    pub hashtags: Vec<String>,
+    /// Topic regexes
+    #[serde(default)]
+    pub topics: Patterns,
+    /// Automation the change is exempt from
+    pub exempt_from: Vec<Automation>,
+}
+
+impl Exemption {
+    fn new(name: &str, exempt_from: &[Automation]) -> Exemption {
+        Exemption {
+            name: name.to_string(),
+            hashtags: vec![name.to_string()],
+            topics: Patterns::default(),
+            exempt_from: exempt_from.to_vec(),
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.hashtags.is_empty() && self.topics.is_empty() {
+            return Err(format!(
+                "Exemption {} needs hashtags or topics",
+                self.name
+            ));
+        }
+        Ok(())
+    }
+
+    /// Check if the exemption applies to a change
+    pub fn matches(&self, change: &ChangeInfo) -> bool {
+        let hashtag = self.hashtags.iter().any(|hashtag| {
+            change
+                .hashtags
+                .iter()
+                .any(|tag| tag.eq_ignore_ascii_case(hashtag))
+        });
+        hashtag
+            || (!change.topic.is_empty() && self.topics.is_match(&change.topic))
+    }
+}
+
+/// The configured exemptions
+#[derive(Debug, Clone, Deserialize)]
+pub struct Exemptions {
+    pub exemptions: Vec<Exemption>,
+}
+
+impl Default for Exemptions {
+    /// The exemptions used without a configuration file
+    fn default() -> Self {
+        Exemptions {
+            exemptions: vec![
+                Exemption::new("bot-ignore", &Automation::ALL),
+                Exemption::new("do-not-abandon", &[Automation::Abandon]),
+                Exemption::new("do-not-advertise", &[Automation::Reminders]),
+            ],
+        }
+    }
+}
+
+impl Exemptions {
+    /// Load the exemptions from the file named by EXEMPTIONS_CONFIG, the
+    /// configuration file, or the default exemptions if neither has any.
+    pub fn load() -> Result<Exemptions, String> {
+        let Ok(path) = std::env::var(EXEMPTIONS_CONFIG_ENV) else {
+            return Ok(match &config::get().exemptions {
+                Some(exemptions) => Exemptions {
+                    exemptions: exemptions.clone(),
+                },
+                None => Exemptions::default(),
+            });
+        };
+        let text = std::fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+        let config = serde_yaml::from_str::<Exemptions>(&text)
+            .map_err(|e| format!("Failed to parse {}: {}", path, e))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        for exemption in &self.exemptions {
+            exemption.validate()?;
+        }
+        Ok(())
+    }
+
+    /// Get the exemptions applying to a change
+    pub fn matching(&self, change: &ChangeInfo) -> Vec<&Exemption> {
+        self.exemptions
+            .iter()
+            .filter(|exemption| exemption.matches(change))
+            .collect()
+    }
+
+    /// Check if a change is exempt from some automation
+    pub fn is_exempt(
+        &self,
+        change: &ChangeInfo,
+        automation: Automation,
+    ) -> bool {
+        self.matching(change)
+            .iter()
+            .any(|exemption| exemption.exempt_from.contains(&automation))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gerrit::data::test_change;
+    use serde_json::json;
+
+    fn exemptions(yaml: &str) -> Exemptions {
+        let exemptions: Exemptions = serde_yaml::from_str(yaml).unwrap();
+        exemptions.validate().unwrap();
+        exemptions
+    }
+
+    #[test]
+    fn hashtags_match_case_insensitively() {
+        let exemptions = Exemptions::default();
+        let change = test_change(1, json!({"hashtags": ["Do-Not-Abandon"]}));
+        assert!(exemptions.is_exempt(&change, Automation::Abandon));
+        assert!(!exemptions.is_exempt(&change, Automation::Reminders));
+        assert!(
+            !exemptions
+                .is_exempt(&test_change(2, json!({})), Automation::Abandon)
+        );
+    }
+
+    #[test]
+    fn topics_match_whole_topics() {
+        let exemptions = exemptions(
+            "
+exemptions:
+- name: releases
+  topics: [release-.*]
+  exempt_from: [retrigger, assignment]
+",
+        );
+        let release = test_change(1, json!({"topic": "release-2.18"}));
+        assert!(exemptions.is_exempt(&release, Automation::Retrigger));
+        assert!(exemptions.is_exempt(&release, Automation::Assignment));
+        assert!(!exemptions.is_exempt(&release, Automation::Abandon));
+
+        let other = test_change(2, json!({"topic": "pre-release-2.18"}));
+        assert!(!exemptions.is_exempt(&other, Automation::Retrigger));
+        assert!(exemptions.matching(&test_change(3, json!({}))).is_empty());
+    }
+
+    #[test]
+    fn exemptions_need_hashtags_or_topics() {
+        let exemptions: Exemptions = serde_yaml::from_str(
+            "
+exemptions:
+- name: empty
+  exempt_from: [abandon]
+",
+        )
+        .unwrap();
+        assert!(exemptions.validate().is_err());
+    }
+}
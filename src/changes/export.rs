@@ -0,0 +1,64 @@
+use crate::changes::report::{ReportFormat, ReportTable};
+use crate::changes::status::NextStepOwner;
+use crate::context::ServiceContext;
+
+const EXPORT_HEADER: [&str; 12] = [
+    "Change",
+    "Project",
+    "Branch",
+    "Topic",
+    "Subject",
+    "Owner",
+    "Review State",
+    "Next Step",
+    "State Since",
+    "Created",
+    "Updated",
+    "URL",
+];
+
+/// Tabulate the open changes, one row per change in number order
+pub fn export_table(context: &ServiceContext) -> ReportTable {
+    let ctx = context.lock().unwrap();
+    let mut changes: Vec<_> = ctx.changes.changes.values().collect();
+    changes.sort_by_key(|change| change.change.id_number);
+
+    let rows = changes
+        .into_iter()
+        .map(|change| {
+            let gerrit = &change.change;
+            vec![
+                gerrit.id_number.to_string(),
+                gerrit.project.clone(),
+                gerrit.branch.clone(),
+                gerrit.topic.clone(),
+                gerrit.subject.clone(),
+                gerrit.owner.username.clone(),
+                change.review_state.name().to_string(),
+                format!(
+                    "{:?}",
+                    NextStepOwner::from(change.review_state.clone())
+                ),
+                change.review_state_updated.to_rfc3339(),
+                gerrit.created.to_rfc3339(),
+                gerrit.updated.to_rfc3339(),
+                format!(
+                    "https://gerrit.openbmc.org/c/{}/+/{}",
+                    gerrit.project, gerrit.id_number
+                ),
+            ]
+        })
+        .collect();
+
+    ReportTable {
+        label: "Change".to_string(),
+        header: EXPORT_HEADER.iter().map(|s| s.to_string()).collect(),
+        rows,
+        numeric: vec![0],
+    }
+}
+
+/// Export the open changes in the given format
+pub fn export(context: &ServiceContext, format: ReportFormat) -> String {
+    format.renderer().render(&export_table(context))
+}
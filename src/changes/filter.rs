@@ -1,10 +1,19 @@
+use crate::config;
 use crate::gerrit::data::ChangeInfo;
 use fancy_regex::Regex;
 use serde::Deserialize;
 use std::collections::HashMap;
+use std::sync::OnceLock;
+use tracing::error;
+
+/// Rejected patterns used unless the configuration file names others.
+const BUILTIN_PATTERNS: &str =
+    include_str!("../../config/rejected_patterns.yaml");
+
+static PATTERNS: OnceLock<CommunityPatternsConfig> = OnceLock::new();
 
 #[derive(Debug, Deserialize)]
-struct CommunityPatternsConfig {
+pub struct CommunityPatternsConfig {
     rejected_repos: Vec<String>,
     rejected_project_regex: Vec<String>,
     rejected_files: Vec<String>,
@@ -12,12 +21,47 @@ struct CommunityPatternsConfig {
 }
 
 impl CommunityPatternsConfig {
-    fn from_str(yaml_str: &str) -> Self {
-        serde_yaml::from_str(yaml_str)
-            .expect("Failed to parse rejected patterns config")
+    fn from_str(yaml_str: &str) -> Result<Self, String> {
+        let config: CommunityPatternsConfig =
+            serde_yaml::from_str(yaml_str).map_err(|e| e.to_string())?;
+
+        for pattern in config
+            .rejected_project_regex
+            .iter()
+            .chain(config.rejected_file_regex.values().flatten())
+        {
+            Regex::new(pattern).map_err(|e| {
+                format!("Invalid rejected pattern '{}': {}", pattern, e)
+            })?;
+        }
+        Ok(config)
     }
 }
 
+/// Load the rejected patterns from a YAML file, or the built-in ones
+pub fn load_patterns(
+    path: Option<&str>,
+) -> Result<CommunityPatternsConfig, String> {
+    let Some(path) = path else {
+        return CommunityPatternsConfig::from_str(BUILTIN_PATTERNS);
+    };
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    CommunityPatternsConfig::from_str(&text)
+        .map_err(|e| format!("Failed to parse {}: {}", path, e))
+}
+
+// Get the rejected patterns, loading them on first use.
+fn patterns() -> &'static CommunityPatternsConfig {
+    PATTERNS.get_or_init(|| {
+        let path = config::get().filters.rejected_patterns.as_deref();
+        load_patterns(path).unwrap_or_else(|e| {
+            error!("Using the built-in rejected patterns: {}", e);
+            load_patterns(None).unwrap()
+        })
+    })
+}
+
 fn community_repo(
     change: &ChangeInfo,
     config: &CommunityPatternsConfig,
@@ -74,9 +118,9 @@ fn community_file(
 
     // Check if all files match rejected patterns
     // Return false only if ALL files are rejected
-    for (file_path, _file_info) in &revision.files {
+    for file_path in revision.files.keys() {
         // If this file is not rejected, include the change
-        if !config.rejected_files.contains(&file_path.to_string())
+        if !config.rejected_files.contains(file_path)
             && !all_regex_patterns
                 .iter()
                 .any(|regex| regex.is_match(file_path).unwrap_or(false))
@@ -94,11 +138,9 @@ fn autobump_topic(change: &ChangeInfo) -> bool {
 }
 
 pub fn should_include_change(change: &ChangeInfo) -> bool {
-    let config = CommunityPatternsConfig::from_str(include_str!(
-        "../../config/rejected_patterns.yaml"
-    ));
+    let config = patterns();
 
-    community_repo(change, &config)
-        && community_file(change, &config)
+    community_repo(change, config)
+        && community_file(change, config)
         && !autobump_topic(change)
 }
@@ -0,0 +1,330 @@
+use crate::changes::patterns::{
+    Patterns, deserialize_partial_regex, deserialize_regex,
+};
+use crate::changes::report::{ChangesByOwnerAndTime, TimeInterval};
+use crate::changes::status::{NextStepOwner, ReviewState};
+use crate::context::ServiceContext;
+use crate::gerrit::connection::GerritConnection;
+use crate::gerrit::data::ChangeInfo;
+use chrono::Utc;
+use serde::Deserialize;
+use std::collections::{BTreeSet, HashMap};
+use tracing::{debug, warn};
+
+/// A file-specific rule from an OWNERS file
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct OwnersMatcher {
+    pub exact: Option<String>,
+    pub suffix: Option<String>,
+    /// Regex matching the whole path
+    #[serde(default, deserialize_with = "deserialize_regex")]
+    pub regex: Patterns,
+    /// Regex matching anywhere in the path
+    #[serde(default, deserialize_with = "deserialize_partial_regex")]
+    pub partial_regex: Patterns,
+    #[serde(default)]
+    pub owners: Vec<String>,
+    #[serde(default)]
+    pub reviewers: Vec<String>,
+}
+
+impl OwnersMatcher {
+    fn matches(&self, path: &str) -> bool {
+        self.exact.as_ref().is_some_and(|exact| exact == path)
+            || self
+                .suffix
+                .as_ref()
+                .is_some_and(|suffix| path.ends_with(suffix.as_str()))
+            || self.regex.is_match(path)
+            || self.partial_regex.is_match(path)
+    }
+}
+
+/// Contact details from the OpenBMC-specific section of an OWNERS file
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct OwnersContact {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub email: String,
+    #[serde(default)]
+    pub discord: String,
+}
+
+/// A parsed OWNERS file, as understood by the Gerrit owners plugin
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct OwnersFile {
+    #[serde(default)]
+    pub owners: Vec<String>,
+    #[serde(default)]
+    pub reviewers: Vec<String>,
+    #[serde(default)]
+    pub matchers: Vec<OwnersMatcher>,
+    #[serde(default)]
+    pub openbmc: Vec<OwnersContact>,
+}
+
+/// Normalize an OWNERS entry ("Name <user@example.com>" or
+/// "user@example.com") to a lowercase email address.
+fn normalize_owner(owner: &str) -> String {
+    let owner = match (owner.find('<'), owner.rfind('>')) {
+        (Some(start), Some(end)) if start < end => &owner[start + 1..end],
+        _ => owner,
+    };
+    owner.trim().to_lowercase()
+}
+
+impl OwnersFile {
+    pub fn parse(content: &str) -> Option<OwnersFile> {
+        // An empty OWNERS file is valid YAML but parses as null.
+        if content.trim().is_empty() {
+            return Some(OwnersFile::default());
+        }
+        serde_yaml::from_str(content)
+            .map_err(|e| warn!("Failed to parse OWNERS file: {}", e))
+            .ok()
+    }
+
+    /// Owners responsible for a single file
+    pub fn owners_for_file(&self, path: &str) -> BTreeSet<String> {
+        self.owners
+            .iter()
+            .chain(
+                self.matchers
+                    .iter()
+                    .filter(|matcher| matcher.matches(path))
+                    .flat_map(|matcher| matcher.owners.iter()),
+            )
+            .map(|owner| normalize_owner(owner))
+            .collect()
+    }
+
+    /// Reviewers listed for a single file
+    pub fn reviewers_for_file(&self, path: &str) -> BTreeSet<String> {
+        self.reviewers
+            .iter()
+            .chain(
+                self.matchers
+                    .iter()
+                    .filter(|matcher| matcher.matches(path))
+                    .flat_map(|matcher| matcher.reviewers.iter()),
+            )
+            .map(|reviewer| normalize_owner(reviewer))
+            .collect()
+    }
+
+    /// Owners responsible for any of the files in a change
+    pub fn owners_for_change(&self, change: &ChangeInfo) -> BTreeSet<String> {
+        let Some(revision) = change.revisions.get(&change.current_revision)
+        else {
+            return self.owners.iter().map(|o| normalize_owner(o)).collect();
+        };
+
+        revision
+            .files
+            .keys()
+            .filter(|path| !path.starts_with('/'))
+            .flat_map(|path| self.owners_for_file(path))
+            .collect()
+    }
+}
+
+/// OWNERS files for each project and branch with open changes
+#[derive(Debug, Clone, Default)]
+pub struct Owners(HashMap<(String, String), OwnersFile>);
+
+impl Owners {
+    /// Get the OWNERS file that applies to a change
+    pub fn get(&self, change: &ChangeInfo) -> Option<&OwnersFile> {
+        self.0.get(&(change.project.clone(), change.branch.clone()))
+    }
+
+    /// Get the owners responsible for a change
+    pub fn owners_for_change(&self, change: &ChangeInfo) -> BTreeSet<String> {
+        self.get(change)
+            .map(|owners| owners.owners_for_change(change))
+            .unwrap_or_default()
+    }
+
+    /// Get the reviewers listed for the files of a change
+    pub fn reviewers_for_change(
+        &self,
+        change: &ChangeInfo,
+    ) -> BTreeSet<String> {
+        let Some(owners) = self.get(change) else {
+            return BTreeSet::new();
+        };
+        let Some(revision) = change.revisions.get(&change.current_revision)
+        else {
+            return owners
+                .reviewers
+                .iter()
+                .map(|r| normalize_owner(r))
+                .collect();
+        };
+
+        revision
+            .files
+            .keys()
+            .filter(|path| !path.starts_with('/'))
+            .flat_map(|path| owners.reviewers_for_file(path))
+            .collect()
+    }
+}
+
+/// Fetch the OWNERS file for every project and branch with open changes.
+pub async fn refresh(context: &ServiceContext) {
+    let mut branches: Vec<(String, String)> = context
+        .lock()
+        .unwrap()
+        .changes
+        .changes
+        .values()
+        .map(|c| (c.change.project.clone(), c.change.branch.clone()))
+        .collect();
+    branches.sort();
+    branches.dedup();
+
+    let gerrit = context.get_gerrit();
+    let mut owners = Owners::default();
+    for (project, branch) in branches {
+        let Some(content) =
+            gerrit.file_content(&project, &branch, "OWNERS").await
+        else {
+            debug!("No OWNERS file for {} ({})", project, branch);
+            continue;
+        };
+        if let Some(file) = OwnersFile::parse(&content) {
+            owners.0.insert((project, branch), file);
+        }
+    }
+
+    context.lock().unwrap().owners = owners;
+}
+
+/// Find the email addresses used by a Gerrit user, so OWNERS entries (which
+/// are emails) can be matched to a username.  An email is returned as-is.
+pub fn user_emails(context: &ServiceContext, user: &str) -> BTreeSet<String> {
+    if user.contains('@') {
+        return BTreeSet::from([user.to_lowercase()]);
+    }
+
+    let mut emails = BTreeSet::new();
+    for change in context.lock().unwrap().changes.changes.values() {
+        if change.change.owner.username == user
+            && !change.change.owner.email.is_empty()
+        {
+            emails.insert(change.change.owner.email.to_lowercase());
+        }
+        for votes in change.change.labels.values() {
+            for vote in votes.iter() {
+                if vote.username == user && !vote.email.is_empty() {
+                    emails.insert(vote.email.to_lowercase());
+                }
+            }
+        }
+    }
+    emails
+}
+
+/// Changes awaiting maintainer review where the user is one of the owners of
+/// the changed files.
+pub fn changes_by_maintainer(
+    context: &ServiceContext,
+    user: &str,
+) -> ChangesByOwnerAndTime {
+    let emails = user_emails(context, user);
+    let mut changes = ChangesByOwnerAndTime::default();
+    let now = Utc::now();
+
+    let ctx = context.lock().unwrap();
+    for change in ctx.changes.changes.values() {
+        if change.review_state != ReviewState::MaintainerReview {
+            continue;
+        }
+        if ctx
+            .owners
+            .owners_for_change(&change.change)
+            .is_disjoint(&emails)
+        {
+            continue;
+        }
+
+        changes.increment(
+            TimeInterval::from_timestamp(change.review_state_updated, now),
+            NextStepOwner::Maintainer,
+            change.change.id_number,
+        );
+    }
+
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OWNERS: &str = "
+owners:
+- Alice <ALICE@example.com>
+reviewers:
+- carol@example.com
+matchers:
+- exact: meson.build
+  owners:
+  - bob@example.com
+- suffix: .hpp
+  owners:
+  - dave@example.com
+- regex: src/.*\\.cpp
+  reviewers:
+  - erin@example.com
+- partial_regex: test
+  owners:
+  - frank@example.com
+";
+
+    fn set(emails: &[&str]) -> BTreeSet<String> {
+        emails.iter().map(|email| email.to_string()).collect()
+    }
+
+    #[test]
+    fn matchers_add_to_the_default_owners() {
+        let owners = OwnersFile::parse(OWNERS).unwrap();
+        assert_eq!(
+            owners.owners_for_file("meson.build"),
+            set(&["alice@example.com", "bob@example.com"])
+        );
+        assert_eq!(
+            owners.owners_for_file("include/sensor.hpp"),
+            set(&["alice@example.com", "dave@example.com"])
+        );
+        assert_eq!(
+            owners.owners_for_file("src/unit_test.cpp"),
+            set(&["alice@example.com", "frank@example.com"])
+        );
+        assert_eq!(
+            owners.owners_for_file("README.md"),
+            set(&["alice@example.com"])
+        );
+    }
+
+    #[test]
+    fn regex_matchers_are_anchored() {
+        let owners = OwnersFile::parse(OWNERS).unwrap();
+        assert_eq!(
+            owners.reviewers_for_file("src/main.cpp"),
+            set(&["carol@example.com", "erin@example.com"])
+        );
+        assert_eq!(
+            owners.reviewers_for_file("subprojects/src/main.cpp.orig"),
+            set(&["carol@example.com"])
+        );
+    }
+
+    #[test]
+    fn empty_file_has_no_owners() {
+        let owners = OwnersFile::parse("\n").unwrap();
+        assert!(owners.owners_for_file("meson.build").is_empty());
+    }
+}
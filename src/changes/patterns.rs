@@ -0,0 +1,138 @@
+use fancy_regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize, de::Error};
+
+/// A list of regexes, each matching a whole name such as a project or topic.
+/// The regexes are compiled when the configuration is read, so an invalid
+/// one fails parsing and matching does not compile them again.  They
+/// serialize back to their source strings.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(try_from = "Vec<String>", into = "Vec<String>")]
+pub struct Patterns {
+    sources: Vec<String>,
+    regexes: Vec<Regex>,
+}
+
+impl TryFrom<Vec<String>> for Patterns {
+    type Error = String;
+
+    fn try_from(patterns: Vec<String>) -> Result<Self, Self::Error> {
+        Patterns::compile(patterns, true)
+    }
+}
+
+impl From<Patterns> for Vec<String> {
+    fn from(patterns: Patterns) -> Self {
+        patterns.sources
+    }
+}
+
+impl PartialEq for Patterns {
+    fn eq(&self, other: &Self) -> bool {
+        self.sources == other.sources
+    }
+}
+
+impl Patterns {
+    fn compile(sources: Vec<String>, anchored: bool) -> Result<Self, String> {
+        let regexes = sources
+            .iter()
+            .map(|pattern| {
+                let regex = if anchored {
+                    format!("^(?:{})$", pattern)
+                } else {
+                    pattern.clone()
+                };
+                Regex::new(&regex).map_err(|e| {
+                    format!("Invalid pattern '{}': {}", pattern, e)
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Patterns { sources, regexes })
+    }
+
+    /// Regexes which may match anywhere in a value, rather than the whole of
+    /// it
+    pub fn partial(patterns: Vec<String>) -> Result<Patterns, String> {
+        Patterns::compile(patterns, false)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.regexes.is_empty()
+    }
+
+    /// Check if any of the patterns matches the value
+    pub fn is_match(&self, value: &str) -> bool {
+        self.regexes
+            .iter()
+            .any(|regex| regex.is_match(value).unwrap_or(false))
+    }
+}
+
+/// Deserialize an optional single regex matching whole values, e.g. the
+/// `regex` of an OWNERS matcher
+pub fn deserialize_regex<'de, D>(deserializer: D) -> Result<Patterns, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map_or(Ok(Patterns::default()), |pattern| {
+            Patterns::try_from(vec![pattern])
+        })
+        .map_err(D::Error::custom)
+}
+
+/// Deserialize an optional single regex matching anywhere in values, e.g.
+/// the `partial_regex` of an OWNERS matcher
+pub fn deserialize_partial_regex<'de, D>(
+    deserializer: D,
+) -> Result<Patterns, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map_or(Ok(Patterns::default()), |pattern| {
+            Patterns::partial(vec![pattern])
+        })
+        .map_err(D::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patterns_match_whole_values() {
+        let patterns: Patterns =
+            serde_yaml::from_str("[openbmc/phosphor-.*, openbmc/bmcweb]")
+                .unwrap();
+        assert!(patterns.is_match("openbmc/phosphor-networkd"));
+        assert!(patterns.is_match("openbmc/bmcweb"));
+        assert!(!patterns.is_match("openbmc/bmcweb-tests"));
+        assert!(!patterns.is_match("other/openbmc/bmcweb"));
+    }
+
+    #[test]
+    fn partial_patterns_match_anywhere() {
+        let patterns = Patterns::partial(vec!["bmcweb".to_string()]).unwrap();
+        assert!(patterns.is_match("openbmc/bmcweb-tests"));
+        assert!(!patterns.is_match("openbmc/docs"));
+    }
+
+    #[test]
+    fn patterns_serialize_to_their_sources() {
+        let patterns: Patterns =
+            serde_yaml::from_str("[openbmc/phosphor-.*]").unwrap();
+        assert_eq!(
+            serde_json::to_value(&patterns).unwrap(),
+            serde_json::json!(["openbmc/phosphor-.*"])
+        );
+    }
+
+    #[test]
+    fn invalid_patterns_fail_parsing() {
+        let error = serde_yaml::from_str::<Patterns>("['openbmc/(']")
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("Invalid pattern 'openbmc/('"), "{}", error);
+    }
+}
@@ -0,0 +1,762 @@
+use crate::changes::patterns::Patterns;
+use crate::changes::status::{self, ReviewState};
+use crate::config;
+use crate::gerrit::data::{ApprovalInfo, ChangeInfo, ChangeStatus};
+use crate::state;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Environment variable naming the YAML file with the abandonment policies.
+const ABANDON_POLICIES_CONFIG_ENV: &str = "ABANDON_POLICIES_CONFIG";
+
+const ABANDONMENTS_FILE: &str = "abandonments.json";
+const ABANDON_WARNINGS_FILE: &str = "abandon_warnings.json";
+
+/// Minutes a change may be updated after its warning was posted and still
+/// count as untouched, in case the update time after the warning could not
+/// be fetched.
+const WARNING_TOLERANCE_MINUTES: i64 = 5;
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_message() -> String {
+    concat!(
+        "Automatically abandoned by the {policy} policy.\n",
+        "Please rebase and reopen if this should still be merged.",
+    )
+    .to_string()
+}
+
+fn default_warning_message() -> String {
+    concat!(
+        "This change will be automatically abandoned by the {policy} policy ",
+        "on {date} unless it is updated.\n",
+        "Upload a new patch set, reply or vote to keep it open.",
+    )
+    .to_string()
+}
+
+/// A vote a change must carry for a policy to apply
+#[derive(Debug, Clone, Deserialize)]
+pub struct LabelCondition {
+    /// Label name, e.g. Verified or Code-Review
+    pub label: String,
+    /// Only count votes by this user
+    #[serde(default)]
+    pub user: Option<String>,
+    /// Lowest matching vote value
+    #[serde(default)]
+    pub min: Option<i64>,
+    /// Highest matching vote value
+    #[serde(default)]
+    pub max: Option<i64>,
+}
+
+impl LabelCondition {
+    fn votes(&self, change: &ChangeInfo) -> Vec<ApprovalInfo> {
+        let Some(votes) = change.labels.get(&self.label) else {
+            return Vec::new();
+        };
+        votes
+            .iter()
+            .filter(|vote| {
+                self.user.as_ref().is_none_or(|user| *user == vote.username)
+                    && self.min.is_none_or(|min| vote.value >= min)
+                    && self.max.is_none_or(|max| vote.value <= max)
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+/// Conditions under which open changes are abandoned, and the message left
+/// on them.  A change must meet every condition given.
+#[derive(Debug, Clone, Deserialize)]
+pub struct AbandonPolicy {
+    pub name: String,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Days since the change was last updated
+    #[serde(default)]
+    pub inactive_days: Option<i64>,
+    /// Days since the change was created
+    #[serde(default)]
+    pub min_age_days: Option<i64>,
+    /// Review states, e.g. FailingCI or MergeConflict; any state when empty
+    #[serde(default)]
+    pub states: Vec<String>,
+    /// Votes the change must carry
+    #[serde(default)]
+    pub labels: Vec<LabelCondition>,
+    /// Project regexes the policy applies to; all projects when empty
+    #[serde(default)]
+    pub projects: Patterns,
+    /// Project regexes the policy is disabled for
+    #[serde(default)]
+    pub exclude_projects: Patterns,
+    /// Topic regexes, one of which the change topic must match
+    #[serde(default)]
+    pub topics: Patterns,
+    /// Hashtags, one of which the change must carry
+    #[serde(default)]
+    pub hashtags: Vec<String>,
+    /// Gerrit message.  {policy}, {project}, {number}, {subject}, {owner},
+    /// {days}, {votes} and {date} are replaced by details of the change.
+    #[serde(default = "default_message")]
+    pub message: String,
+    /// Days before abandoning to warn the owner with a review comment.
+    /// Changes are abandoned without warning when not set.
+    #[serde(default)]
+    pub warning_days: Option<i64>,
+    /// Review comment warning of the abandonment, with the same
+    /// placeholders as message
+    #[serde(default = "default_warning_message")]
+    pub warning_message: String,
+}
+
+impl AbandonPolicy {
+    fn validate(&self) -> Result<(), String> {
+        let context = |e: String| format!("Policy {}: {}", self.name, e);
+
+        if self.inactive_days.is_none() && self.min_age_days.is_none() {
+            return Err(context(
+                "needs inactive_days or min_age_days".to_string(),
+            ));
+        }
+        for state in &self.states {
+            if !ReviewState::NAMES.contains(&state.as_str()) {
+                return Err(context(format!(
+                    "unknown review state {}, expected one of {}",
+                    state,
+                    ReviewState::NAMES.join(", ")
+                )));
+            }
+        }
+        if self.warning_days.is_some_and(|days| days <= 0) {
+            return Err(context("warning_days must be positive".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Check if the policy applies to a change, returning the votes which
+    /// met the label conditions.
+    pub fn matches(
+        &self,
+        change: &ChangeInfo,
+        now: DateTime<Utc>,
+    ) -> Option<Vec<ApprovalInfo>> {
+        if !self.enabled || change.status != ChangeStatus::New {
+            return None;
+        }
+        if let Some(days) = self.inactive_days
+            && change.updated >= now - Duration::days(days)
+        {
+            return None;
+        }
+        if let Some(days) = self.min_age_days
+            && change.created >= now - Duration::days(days)
+        {
+            return None;
+        }
+
+        if !self.projects.is_empty() && !self.projects.is_match(&change.project)
+        {
+            return None;
+        }
+        if self.exclude_projects.is_match(&change.project) {
+            return None;
+        }
+        if !self.topics.is_empty() && !self.topics.is_match(&change.topic) {
+            return None;
+        }
+        if !self.hashtags.is_empty()
+            && !self
+                .hashtags
+                .iter()
+                .any(|tag| change.hashtags.contains(tag))
+        {
+            return None;
+        }
+        if !self.states.is_empty() {
+            let state = status::review_state(change);
+            if !self.states.iter().any(|name| name == state.name()) {
+                return None;
+            }
+        }
+
+        let mut matched = Vec::new();
+        for condition in &self.labels {
+            let votes = condition.votes(change);
+            if votes.is_empty() {
+                return None;
+            }
+            matched.extend(votes);
+        }
+        Some(matched)
+    }
+
+    /// Fill in a message template for a change, with {date} being the day
+    /// of abandonment
+    pub fn render(
+        &self,
+        template: &str,
+        change: &ChangeInfo,
+        votes: &[ApprovalInfo],
+        now: DateTime<Utc>,
+        date: DateTime<Utc>,
+    ) -> String {
+        let votes = votes
+            .iter()
+            .map(|vote| format!("{}={}", vote.username, vote.value))
+            .collect::<Vec<_>>()
+            .join(", ");
+        template
+            .replace("{policy}", &self.name)
+            .replace("{project}", &change.project)
+            .replace("{number}", &change.id_number.to_string())
+            .replace("{subject}", &change.subject)
+            .replace("{owner}", &change.owner.username)
+            .replace("{days}", &(now - change.updated).num_days().to_string())
+            .replace("{votes}", &votes)
+            .replace("{date}", &date.format("%Y-%m-%d").to_string())
+    }
+}
+
+/// A policy which applies to a change, with the message to post
+#[derive(Debug, Clone)]
+pub struct Verdict {
+    pub policy: String,
+    pub message: String,
+}
+
+/// What to do with a change according to the policies
+#[derive(Debug, Clone)]
+pub enum Action {
+    None,
+    Warn(Verdict),
+    Abandon(Verdict),
+}
+
+/// The configured abandonment policies
+#[derive(Debug, Clone, Deserialize)]
+pub struct PoliciesConfig {
+    pub policies: Vec<AbandonPolicy>,
+}
+
+impl Default for PoliciesConfig {
+    /// The policies used without a configuration file: abandon changes
+    /// inactive for two years, or for one year with failing CI.
+    fn default() -> Self {
+        PoliciesConfig {
+            policies: vec![
+                AbandonPolicy {
+                    name: "inactive".to_string(),
+                    enabled: true,
+                    inactive_days: Some(2 * 365),
+                    min_age_days: None,
+                    states: Vec::new(),
+                    labels: Vec::new(),
+                    projects: Patterns::default(),
+                    exclude_projects: Patterns::default(),
+                    topics: Patterns::default(),
+                    hashtags: Vec::new(),
+                    message: concat!(
+                        "Automatically abandoned due to inactivity of over two years.\n",
+                        "Please rebase and reopen if this should still be merged.",
+                    )
+                    .to_string(),
+                    warning_days: Some(14),
+                    warning_message: default_warning_message(),
+                },
+                AbandonPolicy {
+                    name: "inactive-bad-ci".to_string(),
+                    enabled: true,
+                    inactive_days: Some(365),
+                    min_age_days: None,
+                    states: Vec::new(),
+                    labels: vec![LabelCondition {
+                        label: "Verified".to_string(),
+                        user: Some("jenkins-openbmc-ci".to_string()),
+                        min: None,
+                        max: Some(0),
+                    }],
+                    projects: Patterns::default(),
+                    exclude_projects: Patterns::default(),
+                    topics: Patterns::default(),
+                    hashtags: Vec::new(),
+                    message: concat!(
+                        "Automatically abandoned due to missing or failing CI and inactivity of over one year.\n",
+                        "Please rebase, resolve CI and reopen if this should still be merged.\n",
+                        "\n",
+                        "CI status: {votes}",
+                    )
+                    .to_string(),
+                    warning_days: Some(14),
+                    warning_message: default_warning_message(),
+                },
+            ],
+        }
+    }
+}
+
+impl PoliciesConfig {
+    /// Load the policies from the file named by ABANDON_POLICIES_CONFIG, the
+    /// configuration file, or the default policies if neither has any.
+    pub fn load() -> Result<PoliciesConfig, String> {
+        if let Ok(path) = std::env::var(ABANDON_POLICIES_CONFIG_ENV) {
+            return Self::from_file(&path);
+        }
+        Ok(match &config::get().policies {
+            Some(policies) => PoliciesConfig {
+                policies: policies.clone(),
+            },
+            None => PoliciesConfig::default(),
+        })
+    }
+
+    /// Load the policies from a YAML file
+    pub fn from_file(path: &str) -> Result<PoliciesConfig, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+        let config = serde_yaml::from_str::<PoliciesConfig>(&text)
+            .map_err(|e| format!("Failed to parse {}: {}", path, e))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        for policy in &self.policies {
+            policy.validate()?;
+        }
+        Ok(())
+    }
+
+    /// Find the first policy, in configuration order, which applies to a
+    /// change.
+    pub fn evaluate(
+        &self,
+        change: &ChangeInfo,
+        now: DateTime<Utc>,
+    ) -> Option<Verdict> {
+        self.policies.iter().find_map(|policy| {
+            policy.matches(change, now).map(|votes| Verdict {
+                policy: policy.name.clone(),
+                message: policy.render(
+                    &policy.message,
+                    change,
+                    &votes,
+                    now,
+                    now,
+                ),
+            })
+        })
+    }
+
+    /// Decide whether to warn about or abandon a change, given the warning
+    /// already posted on it.  Policies with warning_days only abandon once
+    /// the warning is that many days old and the change is untouched since.
+    pub fn decide(
+        &self,
+        change: &ChangeInfo,
+        warning: Option<&Warning>,
+        now: DateTime<Utc>,
+    ) -> Action {
+        let warning = warning.filter(|warning| !warning.outdated(change));
+
+        // The warning comment itself updated the change, so measure
+        // inactivity from before it.
+        let mut change = change.clone();
+        if let Some(warning) = warning {
+            change.updated = warning.previous_updated;
+        }
+
+        let warn = |policy: &AbandonPolicy, votes: &[ApprovalInfo], days| {
+            Action::Warn(Verdict {
+                policy: policy.name.clone(),
+                message: policy.render(
+                    &policy.warning_message,
+                    &change,
+                    votes,
+                    now,
+                    now + Duration::days(days),
+                ),
+            })
+        };
+
+        for policy in &self.policies {
+            let Some(votes) = policy.matches(&change, now) else {
+                continue;
+            };
+            let verdict = Verdict {
+                policy: policy.name.clone(),
+                message: policy.render(
+                    &policy.message,
+                    &change,
+                    &votes,
+                    now,
+                    now,
+                ),
+            };
+            return match (policy.warning_days, warning) {
+                (None, _) => Action::Abandon(verdict),
+                (Some(days), Some(warning))
+                    if now >= warning.warned + Duration::days(days) =>
+                {
+                    Action::Abandon(verdict)
+                }
+                (Some(_), Some(_)) => Action::None,
+                (Some(days), None) => warn(policy, &votes, days),
+            };
+        }
+
+        if warning.is_some() {
+            return Action::None;
+        }
+        for policy in &self.policies {
+            let Some(days) = policy.warning_days else {
+                continue;
+            };
+            if let Some(votes) =
+                policy.matches(&change, now + Duration::days(days))
+            {
+                return warn(policy, &votes, days);
+            }
+        }
+        Action::None
+    }
+}
+
+/// A change abandoned by a policy
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Abandonment {
+    pub policy: String,
+    pub project: String,
+    pub subject: String,
+    pub abandoned: DateTime<Utc>,
+}
+
+impl Abandonment {
+    /// Describe which policy abandoned the change, and when
+    pub fn description(&self) -> String {
+        format!(
+            "Abandoned by the {} policy on {}",
+            self.policy,
+            self.abandoned.format("%Y-%m-%d")
+        )
+    }
+}
+
+/// The policy which abandoned each change, persisted in the state directory.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Abandonments {
+    abandonments: HashMap<u64, Abandonment>,
+}
+
+impl Abandonments {
+    pub fn load() -> Abandonments {
+        state::load(ABANDONMENTS_FILE)
+    }
+
+    pub fn save(&self) {
+        state::save(ABANDONMENTS_FILE, self);
+    }
+
+    /// Record that a policy abandoned a change
+    pub fn record(&mut self, change: &ChangeInfo, policy: &str) {
+        self.abandonments.insert(
+            change.id_number,
+            Abandonment {
+                policy: policy.to_string(),
+                project: change.project.clone(),
+                subject: change.subject.clone(),
+                abandoned: Utc::now(),
+            },
+        );
+        self.save();
+    }
+
+    /// Get the policy which abandoned a change
+    pub fn get(&self, id_number: u64) -> Option<&Abandonment> {
+        self.abandonments.get(&id_number)
+    }
+}
+
+/// A warning posted on a change about its upcoming abandonment
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Warning {
+    pub policy: String,
+    pub warned: DateTime<Utc>,
+    /// When the change was updated before the warning
+    pub previous_updated: DateTime<Utc>,
+    /// When the change was updated by the warning
+    pub updated: DateTime<Utc>,
+}
+
+impl Warning {
+    /// Check if the change was updated since the warning was posted
+    pub fn outdated(&self, change: &ChangeInfo) -> bool {
+        change.updated
+            > self.updated + Duration::minutes(WARNING_TOLERANCE_MINUTES)
+    }
+}
+
+/// Abandonment warnings by change number, persisted in the state directory.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AbandonWarnings {
+    warnings: HashMap<u64, Warning>,
+}
+
+impl AbandonWarnings {
+    pub fn load() -> AbandonWarnings {
+        state::load(ABANDON_WARNINGS_FILE)
+    }
+
+    pub fn save(&self) {
+        state::save(ABANDON_WARNINGS_FILE, self);
+    }
+
+    /// Record a warning posted on a change
+    pub fn record(&mut self, id_number: u64, warning: Warning) {
+        self.warnings.insert(id_number, warning);
+        self.save();
+    }
+
+    /// Forget the warning posted on a change
+    pub fn remove(&mut self, id_number: u64) {
+        if self.warnings.remove(&id_number).is_some() {
+            self.save();
+        }
+    }
+
+    /// Get the warning posted on a change
+    pub fn get(&self, id_number: u64) -> Option<&Warning> {
+        self.warnings.get(&id_number)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gerrit::data::test_change;
+    use serde_json::json;
+
+    fn now() -> DateTime<Utc> {
+        "2025-06-01T12:00:00Z".parse().unwrap()
+    }
+
+    // A change last updated `days` ago, with extra Gerrit fields
+    fn change(days: i64, mut fields: serde_json::Value) -> ChangeInfo {
+        let updated = (now() - Duration::days(days))
+            .format("%Y-%m-%d %H:%M:%S%.9f")
+            .to_string();
+        fields["created"] = json!(updated);
+        fields["updated"] = json!(updated);
+        test_change(1, fields)
+    }
+
+    fn policies(yaml: &str) -> PoliciesConfig {
+        let config: PoliciesConfig = serde_yaml::from_str(yaml).unwrap();
+        config.validate().unwrap();
+        config
+    }
+
+    fn warning(warned_days_ago: i64, change: &ChangeInfo) -> Warning {
+        let warned = now() - Duration::days(warned_days_ago);
+        Warning {
+            policy: "inactive".to_string(),
+            warned,
+            previous_updated: change.updated,
+            updated: warned,
+        }
+    }
+
+    fn verified(value: i64) -> serde_json::Value {
+        json!({"labels": {"Verified": {"all": [
+            {"username": "jenkins-openbmc-ci", "value": value},
+        ]}}})
+    }
+
+    #[test]
+    fn first_matching_policy_abandons() {
+        let config = policies(
+            "
+policies:
+  - name: bad-ci
+    inactive_days: 365
+    labels:
+      - label: Verified
+        user: jenkins-openbmc-ci
+        max: 0
+    message: 'CI status: {votes}'
+  - name: inactive
+    inactive_days: 730
+",
+        );
+
+        match config.decide(&change(400, verified(-1)), None, now()) {
+            Action::Abandon(verdict) => {
+                assert_eq!(verdict.policy, "bad-ci");
+                assert_eq!(verdict.message, "CI status: jenkins-openbmc-ci=-1");
+            }
+            action => panic!("unexpected {:?}", action),
+        }
+        assert!(matches!(
+            config.decide(&change(400, verified(1)), None, now()),
+            Action::None
+        ));
+        assert!(matches!(
+            config.decide(&change(800, verified(1)), None, now()),
+            Action::Abandon(verdict) if verdict.policy == "inactive"
+        ));
+    }
+
+    #[test]
+    fn project_topic_and_hashtag_conditions() {
+        let config = policies(
+            "
+policies:
+  - name: parked
+    inactive_days: 30
+    projects: [openbmc/.*]
+    exclude_projects: [openbmc/docs]
+    topics: [parked-.*]
+    hashtags: [prototype]
+",
+        );
+        let fields = |project: &str, topic: &str| {
+            json!({
+                "project": project,
+                "topic": topic,
+                "hashtags": ["prototype"],
+            })
+        };
+
+        let decide = |fields| config.decide(&change(60, fields), None, now());
+        assert!(matches!(
+            decide(fields("openbmc/bmcweb", "parked-sensors")),
+            Action::Abandon(_)
+        ));
+        assert!(matches!(
+            decide(fields("openbmc/docs", "parked-sensors")),
+            Action::None
+        ));
+        assert!(matches!(
+            decide(fields("openbmc/bmcweb", "sensors")),
+            Action::None
+        ));
+        assert!(matches!(
+            decide(json!({"topic": "parked-sensors"})),
+            Action::None
+        ));
+    }
+
+    #[test]
+    fn disabled_policies_and_closed_changes_are_skipped() {
+        let config = policies(
+            "
+policies:
+  - name: inactive
+    enabled: false
+    inactive_days: 30
+  - name: old
+    min_age_days: 30
+",
+        );
+        assert!(matches!(
+            config.decide(&change(60, json!({})), None, now()),
+            Action::Abandon(verdict) if verdict.policy == "old"
+        ));
+        assert!(matches!(
+            config.decide(
+                &change(60, json!({"status": "MERGED"})),
+                None,
+                now()
+            ),
+            Action::None
+        ));
+    }
+
+    #[test]
+    fn warns_ahead_of_abandoning() {
+        let config = policies(
+            "
+policies:
+  - name: inactive
+    inactive_days: 730
+    warning_days: 14
+    warning_message: 'Abandoning on {date}'
+",
+        );
+
+        // Too early to warn
+        assert!(matches!(
+            config.decide(&change(700, json!({})), None, now()),
+            Action::None
+        ));
+        // Within the warning period of the policy
+        match config.decide(&change(720, json!({})), None, now()) {
+            Action::Warn(verdict) => {
+                assert_eq!(verdict.message, "Abandoning on 2025-06-15")
+            }
+            action => panic!("unexpected {:?}", action),
+        }
+        // Already past the policy, but never warned
+        assert!(matches!(
+            config.decide(&change(800, json!({})), None, now()),
+            Action::Warn(_)
+        ));
+    }
+
+    #[test]
+    fn abandons_after_the_warning_period() {
+        let config = policies(
+            "
+policies:
+  - name: inactive
+    inactive_days: 730
+    warning_days: 14
+",
+        );
+
+        // The warning updated the change; inactivity counts from before it
+        let mut warned = change(740, json!({}));
+        let recent = warning(10, &warned);
+        warned.updated = recent.updated;
+        assert!(matches!(
+            config.decide(&warned, Some(&recent), now()),
+            Action::None
+        ));
+
+        let mut warned = change(750, json!({}));
+        let old = warning(14, &warned);
+        warned.updated = old.updated;
+        assert!(matches!(
+            config.decide(&warned, Some(&old), now()),
+            Action::Abandon(_)
+        ));
+    }
+
+    #[test]
+    fn updates_after_the_warning_cancel_it() {
+        let config = policies(
+            "
+policies:
+  - name: inactive
+    inactive_days: 730
+    warning_days: 14
+",
+        );
+
+        let original = change(750, json!({}));
+        let old = warning(20, &original);
+        let touched = change(1, json!({}));
+        assert!(old.outdated(&touched));
+        assert!(matches!(
+            config.decide(&touched, Some(&old), now()),
+            Action::None
+        ));
+    }
+}
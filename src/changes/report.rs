@@ -1,11 +1,15 @@
+use crate::changes::exemptions::Automation;
 use crate::changes::status::NextStepOwner;
 use crate::context::ServiceContext;
+use crate::gerrit::data::ChangeInfo;
 use chrono::{DateTime, Utc};
 use enum_map::{Enum, EnumMap};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fmt;
+use std::str::FromStr;
 
-#[derive(Debug, Clone, Copy, PartialEq, Enum)]
+#[derive(Debug, Clone, Copy, PartialEq, Enum, Serialize, Deserialize)]
 pub enum TimeInterval {
     Under24Hours,
     Under72Hours,
@@ -15,8 +19,11 @@ pub enum TimeInterval {
 }
 
 impl TimeInterval {
-    pub fn from_timestamp(timestamp: DateTime<Utc>) -> Self {
-        let now = Utc::now();
+    /// Get the interval that has passed between `timestamp` and `now`
+    pub fn from_timestamp(
+        timestamp: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> Self {
         let duration = now.signed_duration_since(timestamp);
 
         if duration.num_hours() < 24 {
@@ -102,56 +109,124 @@ impl ChangesByOwnerAndTime {
     }
 }
 
-/// A map structure that tracks changes by owner, organized by repository
+/// Attribute used to group changes in a summary report
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GroupBy {
+    Repo,
+    Branch,
+    Topic,
+}
+
+impl GroupBy {
+    /// Get the value of the grouping attribute for a change, if it has one
+    pub fn key(&self, change: &ChangeInfo) -> Option<String> {
+        match self {
+            GroupBy::Repo => Some(change.project.clone()),
+            GroupBy::Branch => Some(change.branch.clone()),
+            GroupBy::Topic => {
+                Some(change.topic.clone()).filter(|topic| !topic.is_empty())
+            }
+        }
+    }
+}
+
+impl FromStr for GroupBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "repo" | "repository" | "project" => Ok(GroupBy::Repo),
+            "branch" => Ok(GroupBy::Branch),
+            "topic" => Ok(GroupBy::Topic),
+            _ => Err(format!("Unknown grouping: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for GroupBy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GroupBy::Repo => write!(f, "Repository"),
+            GroupBy::Branch => write!(f, "Branch"),
+            GroupBy::Topic => write!(f, "Topic"),
+        }
+    }
+}
+
+/// A map structure that tracks changes by owner, organized by a grouping
+/// attribute such as the repository
 #[derive(Debug, Default)]
-pub struct ChangesByOwnerAndRepo(HashMap<String, ChangesByOwner>);
+pub struct ChangesByOwnerAndGroup(HashMap<String, ChangesByOwner>);
 
-impl ChangesByOwnerAndRepo {
-    /// Increment the count for a specific repository and owner
+impl ChangesByOwnerAndGroup {
+    /// Increment the count for a specific group and owner
     pub fn increment(
         &mut self,
-        repo: String,
+        group: String,
         owner: NextStepOwner,
         id_number: u64,
     ) {
-        self.0
-            .entry(repo)
-            .or_insert_with(ChangesByOwner::default)
-            .increment(owner, id_number);
+        self.0.entry(group).or_default().increment(owner, id_number);
     }
 
-    /// Get the changes by owner for a specific repository
-    pub fn get_repo_changes(&self, repo: &str) -> Option<&ChangesByOwner> {
-        self.0.get(repo)
+    /// Get the changes by owner for a specific group
+    pub fn get_group_changes(&self, group: &str) -> Option<&ChangesByOwner> {
+        self.0.get(group)
     }
 
-    /// Get all repositories
-    pub fn get_repos(&self) -> Vec<&String> {
+    /// Get all groups
+    pub fn get_groups(&self) -> Vec<&String> {
         self.0.keys().collect()
     }
 }
 
+/// Criteria for selecting which changes are included in a report
+#[derive(Debug, Clone, Default)]
+pub struct ChangeFilter {
+    pub project: Option<String>,
+    pub owner: Option<String>,
+    pub branch: Option<String>,
+    pub topic: Option<String>,
+    /// Also include changes exempt from reports
+    pub include_exempt: bool,
+}
+
+impl ChangeFilter {
+    /// Check if a change passes every criteria that is set
+    pub fn matches(&self, change: &ChangeInfo) -> bool {
+        let check = |expected: &Option<String>, actual: &String| {
+            expected.as_ref().is_none_or(|expected| expected.eq(actual))
+        };
+
+        check(&self.project, &change.project)
+            && check(&self.owner, &change.owner.username)
+            && check(&self.branch, &change.branch)
+            && check(&self.topic, &change.topic)
+    }
+}
+
+/// Count the changes matching the filter by next step owner and how long
+/// they have been in their review state as of `now`
 pub fn changes_by_owner_time(
     context: &ServiceContext,
-    project: Option<String>,
-    owner: Option<String>,
+    filter: &ChangeFilter,
+    now: DateTime<Utc>,
 ) -> ChangesByOwnerAndTime {
     let mut changes = ChangesByOwnerAndTime::default();
 
-    for (_, change) in &context.lock().unwrap().changes.changes {
-        if let Some(ref project_name) = project
-            && !change.change.project.eq(project_name)
-        {
-            continue;
-        }
-        if let Some(ref owner_name) = owner
-            && !change.change.owner.username.eq(owner_name)
+    let ctx = context.lock().unwrap();
+    for change in ctx.changes.changes.values() {
+        if !filter.matches(&change.change)
+            || (!filter.include_exempt
+                && ctx
+                    .exemptions
+                    .is_exempt(&change.change, Automation::Reports))
         {
             continue;
         }
 
         let time_unit =
-            TimeInterval::from_timestamp(change.review_state_updated);
+            TimeInterval::from_timestamp(change.review_state_updated, now);
         let owner = NextStepOwner::from(change.review_state.clone());
 
         changes.increment(time_unit, owner, change.change.id_number);
@@ -160,53 +235,254 @@ pub fn changes_by_owner_time(
     changes
 }
 
-pub fn changes_by_owner_repo(
+pub fn changes_by_owner_group(
     context: &ServiceContext,
-    owner: Option<String>,
-) -> ChangesByOwnerAndRepo {
-    let mut changes = ChangesByOwnerAndRepo::default();
-
-    for (_, change) in &context.lock().unwrap().changes.changes {
-        if let Some(ref owner_name) = owner
-            && !change.change.owner.username.eq(owner_name)
+    filter: &ChangeFilter,
+    group_by: GroupBy,
+) -> ChangesByOwnerAndGroup {
+    let mut changes = ChangesByOwnerAndGroup::default();
+
+    let ctx = context.lock().unwrap();
+    for change in ctx.changes.changes.values() {
+        if !filter.matches(&change.change)
+            || (!filter.include_exempt
+                && ctx
+                    .exemptions
+                    .is_exempt(&change.change, Automation::Reports))
         {
             continue;
         }
+        let Some(group) = group_by.key(&change.change) else {
+            continue;
+        };
 
-        let repo = change.change.project.clone();
         let owner = NextStepOwner::from(change.review_state.clone());
 
-        changes.increment(repo, owner, change.change.id_number);
+        changes.increment(group, owner, change.change.id_number);
     }
 
     changes
 }
 
+/// Longest window, in days, a report may look back over
+pub const MAX_DAYS: i64 = 3650;
+
+/// Check a user-supplied number of days to look back over
+pub fn check_days(days: i64) -> Result<i64, String> {
+    if (1..=MAX_DAYS).contains(&days) {
+        Ok(days)
+    } else {
+        Err(format!("days must be between 1 and {}", MAX_DAYS))
+    }
+}
+
+/// Output formats supported by the report renderers
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ReportFormat {
+    #[default]
+    Text,
+    Markdown,
+    Csv,
+    Json,
+}
+
+impl ReportFormat {
+    /// Get the renderer backend for this format
+    pub fn renderer(&self) -> Box<dyn ReportRenderer> {
+        match self {
+            ReportFormat::Text => Box::new(TextRenderer),
+            ReportFormat::Markdown => Box::new(MarkdownRenderer),
+            ReportFormat::Csv => Box::new(CsvRenderer),
+            ReportFormat::Json => Box::new(JsonRenderer),
+        }
+    }
+
+    /// Get the HTTP content type for this format
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ReportFormat::Text => "text/plain; charset=utf-8",
+            ReportFormat::Markdown => "text/markdown; charset=utf-8",
+            ReportFormat::Csv => "text/csv; charset=utf-8",
+            ReportFormat::Json => "application/json",
+        }
+    }
+}
+
+impl FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "text" | "txt" => Ok(ReportFormat::Text),
+            "markdown" | "md" => Ok(ReportFormat::Markdown),
+            "csv" => Ok(ReportFormat::Csv),
+            "json" => Ok(ReportFormat::Json),
+            _ => Err(format!("Unknown report format: {}", s)),
+        }
+    }
+}
+
+/// A format-independent report: a header and rows of cells
+#[derive(Debug, Clone)]
+pub struct ReportTable {
+    /// Name of the first column, used by formats that need a non-empty key
+    pub label: String,
+    pub header: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+    /// Columns holding numbers, written as numbers by formats that have them
+    pub numeric: Vec<usize>,
+}
+
+impl ReportTable {
+    /// Header with the (normally blank) first cell replaced by the label
+    fn labeled_header(&self) -> Vec<String> {
+        let mut header = self.header.clone();
+        if let Some(first) = header.first_mut()
+            && first.is_empty()
+        {
+            *first = self.label.clone();
+        }
+        header
+    }
+}
+
+/// A backend that turns a report table into text
+pub trait ReportRenderer {
+    fn render(&self, table: &ReportTable) -> String;
+}
+
+/// UTF-8 box drawing table
+pub struct TextRenderer;
+
+impl ReportRenderer for TextRenderer {
+    fn render(&self, table: &ReportTable) -> String {
+        let mut result = comfy_table::Table::new();
+        result
+            .load_preset(comfy_table::presets::UTF8_FULL)
+            .apply_modifier(comfy_table::modifiers::UTF8_ROUND_CORNERS)
+            .set_header(table.header.clone());
+
+        for row in &table.rows {
+            result.add_row(row.clone());
+        }
+
+        result.to_string()
+    }
+}
+
+/// GitHub-flavored Markdown table
+pub struct MarkdownRenderer;
+
+impl ReportRenderer for MarkdownRenderer {
+    fn render(&self, table: &ReportTable) -> String {
+        let escape = |cell: &String| cell.replace('|', "\\|");
+        let line = |cells: Vec<String>| format!("| {} |\n", cells.join(" | "));
+
+        let mut result =
+            line(table.labeled_header().iter().map(escape).collect());
+        result += &line(
+            (0..table.header.len())
+                .map(|i| if i == 0 { "---" } else { "--:" }.to_string())
+                .collect(),
+        );
+        for row in &table.rows {
+            result += &line(row.iter().map(escape).collect());
+        }
+        result
+    }
+}
+
+/// Comma-separated values, quoted as described in RFC 4180
+pub struct CsvRenderer;
+
+impl ReportRenderer for CsvRenderer {
+    fn render(&self, table: &ReportTable) -> String {
+        let escape = |cell: &String| {
+            if cell.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", cell.replace('"', "\"\""))
+            } else {
+                cell.clone()
+            }
+        };
+        let line = |cells: &[String]| {
+            cells.iter().map(escape).collect::<Vec<_>>().join(",") + "\r\n"
+        };
+
+        let mut result = line(&table.labeled_header());
+        for row in &table.rows {
+            result += &line(row);
+        }
+        result
+    }
+}
+
+/// JSON array with one object per row, keyed by column name
+pub struct JsonRenderer;
+
+impl ReportRenderer for JsonRenderer {
+    fn render(&self, table: &ReportTable) -> String {
+        let header = table.labeled_header();
+        let rows: Vec<serde_json::Value> = table
+            .rows
+            .iter()
+            .map(|row| {
+                serde_json::Value::Object(
+                    header
+                        .iter()
+                        .zip(row)
+                        .enumerate()
+                        .map(|(column, (key, cell))| {
+                            let number = table
+                                .numeric
+                                .contains(&column)
+                                .then(|| cell.parse::<u64>().ok())
+                                .flatten();
+                            let value = match number {
+                                Some(number) => serde_json::Value::from(number),
+                                None => serde_json::Value::from(cell.clone()),
+                            };
+                            (key.clone(), value)
+                        })
+                        .collect(),
+                )
+            })
+            .collect();
+
+        serde_json::to_string_pretty(&rows).unwrap()
+    }
+}
+
 pub fn report_by_time(
     context: &ServiceContext,
-    project: Option<String>,
-    owner: Option<String>,
+    filter: &ChangeFilter,
+    format: ReportFormat,
 ) -> String {
-    report_by_owner_time(&changes_by_owner_time(context, project, owner))
+    report_by_owner_time(
+        &changes_by_owner_time(context, filter, Utc::now()),
+        format,
+    )
 }
 
-pub fn report_by_repo<F>(
+pub fn report_by_group<F>(
     context: &ServiceContext,
-    owner: Option<String>,
-    repo_transform: Option<F>,
+    filter: &ChangeFilter,
+    group_by: GroupBy,
+    format: ReportFormat,
+    group_transform: Option<F>,
 ) -> String
 where
     F: Fn(&str) -> String,
 {
-    report_by_owner_repo(&changes_by_owner_repo(context, owner), repo_transform)
+    report_by_owner_group(
+        &changes_by_owner_group(context, filter, group_by),
+        group_by,
+        format,
+        group_transform,
+    )
 }
 
-pub fn report_by_owner_time(changes: &ChangesByOwnerAndTime) -> String {
-    let mut table = comfy_table::Table::new();
-    table
-        .load_preset(comfy_table::presets::UTF8_FULL)
-        .apply_modifier(comfy_table::modifiers::UTF8_ROUND_CORNERS)
-        .set_header(vec!["", "Community", "Maintainers", "Author"]);
+pub fn table_by_owner_time(changes: &ChangesByOwnerAndTime) -> ReportTable {
+    let mut rows = Vec::new();
 
     // Iterate over all time intervals and add rows dynamically
     for time_interval in [
@@ -216,7 +492,7 @@ pub fn report_by_owner_time(changes: &ChangesByOwnerAndTime) -> String {
         TimeInterval::Under8Weeks,
         TimeInterval::Over8Weeks,
     ] {
-        table.add_row(vec![
+        rows.push(vec![
             time_interval.to_string(),
             changes
                 .get_count(time_interval, NextStepOwner::Community)
@@ -230,58 +506,91 @@ pub fn report_by_owner_time(changes: &ChangesByOwnerAndTime) -> String {
         ]);
     }
 
-    table.to_string()
+    ReportTable {
+        label: "Age".to_string(),
+        header: ["", "Community", "Maintainers", "Author"]
+            .map(String::from)
+            .to_vec(),
+        rows,
+        numeric: vec![1, 2, 3],
+    }
 }
 
-pub fn report_by_owner_repo<F>(
-    changes: &ChangesByOwnerAndRepo,
-    repo_transform: Option<F>,
-) -> String
-where
-    F: Fn(&str) -> String,
-{
-    let mut table = comfy_table::Table::new();
-    table
-        .load_preset(comfy_table::presets::UTF8_FULL)
-        .apply_modifier(comfy_table::modifiers::UTF8_ROUND_CORNERS)
-        .set_header(vec!["", "Community", "Maintainers", "Author"]);
-
-    // Get all repos and sort them for consistent output
-    let mut repos: Vec<&String> = changes.get_repos();
-    repos.sort();
-
-    // Add a row for each repository (using plain repo names)
-    for repo in &repos {
-        if let Some(repo_changes) = changes.get_repo_changes(repo) {
-            table.add_row(vec![
-                (*repo).clone(),
-                repo_changes.get_count(NextStepOwner::Community).to_string(),
-                repo_changes
+pub fn table_by_owner_group(
+    changes: &ChangesByOwnerAndGroup,
+    group_by: GroupBy,
+) -> ReportTable {
+    let mut rows = Vec::new();
+
+    // Get all groups and sort them for consistent output
+    let mut groups: Vec<&String> = changes.get_groups();
+    groups.sort();
+
+    // Add a row for each group (using plain group names)
+    for group in &groups {
+        if let Some(group_changes) = changes.get_group_changes(group) {
+            rows.push(vec![
+                (*group).clone(),
+                group_changes
+                    .get_count(NextStepOwner::Community)
+                    .to_string(),
+                group_changes
                     .get_count(NextStepOwner::Maintainer)
                     .to_string(),
-                repo_changes.get_count(NextStepOwner::Author).to_string(),
+                group_changes.get_count(NextStepOwner::Author).to_string(),
             ]);
         }
     }
 
+    ReportTable {
+        label: group_by.to_string(),
+        header: ["", "Community", "Maintainers", "Author"]
+            .map(String::from)
+            .to_vec(),
+        rows,
+        numeric: vec![1, 2, 3],
+    }
+}
+
+pub fn report_by_owner_time(
+    changes: &ChangesByOwnerAndTime,
+    format: ReportFormat,
+) -> String {
+    format.renderer().render(&table_by_owner_time(changes))
+}
+
+pub fn report_by_owner_group<F>(
+    changes: &ChangesByOwnerAndGroup,
+    group_by: GroupBy,
+    format: ReportFormat,
+    group_transform: Option<F>,
+) -> String
+where
+    F: Fn(&str) -> String,
+{
+    let table = table_by_owner_group(changes, group_by);
+
     // Generate the table as string
-    let table_string = table.to_string();
+    let table_string = format.renderer().render(&table);
 
-    // If we have a transform function, apply it post-generation
-    if let Some(transform) = repo_transform {
+    // If we have a transform function, apply it post-generation.  This is
+    // only meaningful for the text table, which is embedded in HTML.
+    if format == ReportFormat::Text
+        && let Some(transform) = group_transform
+    {
         let mut result = table_string;
-        // Replace each repo name with its transformed version
+        // Replace each group name with its transformed version
         // We need to be careful about partial matches, so we'll replace
-        // with delimiters (whitespace) around the repo names
-        for repo in &repos {
-            let plain_repo = (*repo).to_string();
-            let transformed_repo = transform(repo);
+        // with delimiters (whitespace) around the group names
+        for row in &table.rows {
+            let plain_group = row[0].clone();
+            let transformed_group = transform(&plain_group);
 
             // Replace with proper delimiters to avoid partial matches
             // comfy-table typically adds spaces around cell content
             result = result.replace(
-                &format!(" {} ", plain_repo),
-                &format!(" {} ", transformed_repo),
+                &format!(" {} ", plain_group),
+                &format!(" {} ", transformed_group),
             );
         }
         return result;
@@ -289,3 +598,81 @@ where
 
     table_string
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table() -> ReportTable {
+        ReportTable {
+            label: "Project".to_string(),
+            header: vec!["".to_string(), "Community".to_string()],
+            rows: vec![
+                vec!["openbmc/bmcweb".to_string(), "3".to_string()],
+                vec!["a|b, \"c\"".to_string(), "0".to_string()],
+                vec!["1234".to_string(), "1".to_string()],
+            ],
+            numeric: vec![1],
+        }
+    }
+
+    #[test]
+    fn markdown_escapes_pipes() {
+        assert_eq!(
+            MarkdownRenderer.render(&table()),
+            "| Project | Community |\n\
+             | --- | --: |\n\
+             | openbmc/bmcweb | 3 |\n\
+             | a\\|b, \"c\" | 0 |\n\
+             | 1234 | 1 |\n"
+        );
+    }
+
+    #[test]
+    fn csv_quotes_special_cells() {
+        assert_eq!(
+            CsvRenderer.render(&table()),
+            "Project,Community\r\n\
+             openbmc/bmcweb,3\r\n\
+             \"a|b, \"\"c\"\"\",0\r\n\
+             1234,1\r\n"
+        );
+    }
+
+    #[test]
+    fn json_writes_only_numeric_columns_as_numbers() {
+        let rows: serde_json::Value =
+            serde_json::from_str(&JsonRenderer.render(&table())).unwrap();
+        assert_eq!(
+            rows,
+            serde_json::json!([
+                {"Project": "openbmc/bmcweb", "Community": 3},
+                {"Project": "a|b, \"c\"", "Community": 0},
+                {"Project": "1234", "Community": 1},
+            ])
+        );
+    }
+
+    #[test]
+    fn intervals_are_measured_from_now() {
+        let updated = Utc::now();
+        let interval = |days| {
+            TimeInterval::from_timestamp(
+                updated,
+                updated + chrono::Duration::days(days),
+            )
+        };
+        assert_eq!(interval(0), TimeInterval::Under24Hours);
+        assert_eq!(interval(2), TimeInterval::Under72Hours);
+        assert_eq!(interval(13), TimeInterval::Under2Weeks);
+        assert_eq!(interval(55), TimeInterval::Under8Weeks);
+        assert_eq!(interval(56), TimeInterval::Over8Weeks);
+    }
+
+    #[test]
+    fn days_are_bounded() {
+        assert_eq!(check_days(30), Ok(30));
+        assert!(check_days(0).is_err());
+        assert!(check_days(i64::MAX).is_err());
+    }
+}
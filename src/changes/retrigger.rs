@@ -0,0 +1,622 @@
+use crate::audit::{AuditAction, AuditEntry};
+use crate::changes::container::Change;
+use crate::changes::exemptions::Automation;
+use crate::changes::patterns::Patterns;
+use crate::changes::status::ReviewState;
+use crate::chat::backend::{Backends, ChatMessage};
+use crate::chat::reminders::deserialize_optional_channel;
+use crate::config;
+use crate::context::ServiceContext;
+use crate::gerrit::connection::GerritConnection;
+use crate::gerrit::data::ChangeInfo;
+use crate::state;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tokio::time::sleep;
+use tracing::{error, info, warn};
+
+/// Environment variable naming the YAML file enabling CI retriggers.
+const RETRIGGER_CONFIG_ENV: &str = "CI_RETRIGGER_CONFIG";
+
+const RETRIGGERS_FILE: &str = "ci_retriggers.json";
+
+/// How often changes are checked for missing CI.
+const RETRIGGER_INTERVAL_SECS: u64 = 15 * 60;
+
+/// Days after its last retrigger that a change's history is forgotten.
+const RETRIGGER_HISTORY_DAYS: i64 = 90;
+
+/// Minutes a change may be updated after its retrigger and still count as
+/// untouched, in case the update time after the retrigger could not be
+/// fetched.
+const RETRIGGER_TOLERANCE_MINUTES: i64 = 5;
+
+fn default_wait_hours() -> i64 {
+    4
+}
+
+fn default_message() -> String {
+    "recheck".to_string()
+}
+
+fn default_max_failures() -> usize {
+    2
+}
+
+fn default_max_age_days() -> i64 {
+    30
+}
+
+fn default_backend() -> String {
+    "discord".to_string()
+}
+
+/// Where to report changes CI keeps missing
+#[derive(Debug, Clone, Deserialize)]
+pub struct Escalation {
+    /// Chat backend to post to: discord, matrix or webhook
+    #[serde(default = "default_backend")]
+    pub backend: String,
+    #[serde(deserialize_with = "deserialize_optional_channel")]
+    pub channel_id: Option<String>,
+}
+
+/// Settings for retriggering CI on changes it has not voted on
+#[derive(Debug, Clone, Deserialize)]
+pub struct RetriggerConfig {
+    /// Hours without activity or a CI vote before retriggering
+    #[serde(default = "default_wait_hours")]
+    pub wait_hours: i64,
+    /// Review comment posted to retrigger CI; none when empty
+    #[serde(default = "default_message")]
+    pub message: String,
+    /// Votes set to retrigger CI, e.g. {Ok-To-Test: 1}
+    #[serde(default)]
+    pub labels: HashMap<String, i64>,
+    /// Retriggers in a row without a CI vote before escalating; a change is
+    /// escalated sooner if the retrigger of its current patchset fails
+    #[serde(default = "default_max_failures")]
+    pub max_failures: usize,
+    /// Days without activity after which a change is left alone, so that
+    /// old changes missing CI are not all retriggered at once
+    #[serde(default = "default_max_age_days")]
+    pub max_age_days: i64,
+    /// Project regexes to retrigger CI in; all projects when empty
+    #[serde(default)]
+    pub projects: Patterns,
+    /// Chat channel told about changes CI keeps missing
+    #[serde(default)]
+    pub escalation: Option<Escalation>,
+}
+
+impl RetriggerConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.wait_hours <= 0 || self.max_age_days <= 0 {
+            return Err(
+                "wait_hours and max_age_days must be positive".to_string()
+            );
+        }
+        if self.max_failures == 0 {
+            return Err("max_failures must be positive".to_string());
+        }
+        if self.message.is_empty() && self.labels.is_empty() {
+            return Err("CI retrigger needs a message or labels".to_string());
+        }
+        if let Some(escalation) = &self.escalation
+            && escalation.channel_id.is_none()
+        {
+            return Err(
+                "CI retrigger escalation needs a channel_id".to_string()
+            );
+        }
+        Ok(())
+    }
+
+    /// Load the settings from the file named by CI_RETRIGGER_CONFIG or the
+    /// configuration file.  Retriggering is disabled, returning None, if
+    /// neither has any.
+    pub fn load() -> Result<Option<RetriggerConfig>, String> {
+        let Ok(path) = std::env::var(RETRIGGER_CONFIG_ENV) else {
+            return Ok(config::get().ci_retrigger.clone());
+        };
+        let text = std::fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+        let config = serde_yaml::from_str::<RetriggerConfig>(&text)
+            .map_err(|e| format!("Failed to parse {}: {}", path, e))?;
+        config.validate()?;
+        Ok(Some(config))
+    }
+
+    fn includes_project(&self, project: &str) -> bool {
+        self.projects.is_empty() || self.projects.is_match(project)
+    }
+}
+
+/// A retrigger of CI on one patchset
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Attempt {
+    pub revision: String,
+    pub time: DateTime<Utc>,
+    /// Whether CI voted afterwards, or None while still waiting
+    pub succeeded: Option<bool>,
+    /// When the change was last updated by anything but a retrigger
+    #[serde(default)]
+    pub previous_updated: Option<DateTime<Utc>>,
+    /// When the change was updated by the retrigger, unless it failed
+    #[serde(default)]
+    pub updated: Option<DateTime<Utc>>,
+}
+
+/// The retriggers of a change
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RetriggerHistory {
+    pub attempts: Vec<Attempt>,
+    pub escalated: Option<DateTime<Utc>>,
+}
+
+impl RetriggerHistory {
+    /// Count the retriggers in a row which CI did not vote after
+    pub fn failures(&self) -> usize {
+        self.attempts
+            .iter()
+            .rev()
+            .take_while(|attempt| attempt.succeeded == Some(false))
+            .count()
+    }
+}
+
+/// CI retriggers by change number, persisted in the state directory.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Retriggers {
+    changes: HashMap<u64, RetriggerHistory>,
+}
+
+impl Retriggers {
+    pub fn load() -> Retriggers {
+        state::load(RETRIGGERS_FILE)
+    }
+
+    pub fn save(&self) {
+        state::save(RETRIGGERS_FILE, self);
+    }
+
+    /// Get the retriggers of a change
+    pub fn get(&self, id_number: u64) -> Option<&RetriggerHistory> {
+        self.changes.get(&id_number)
+    }
+
+    /// When a change was last updated by anything but its retriggers.
+    /// Retrigger comments update the change, so they would otherwise reset
+    /// its inactivity.
+    pub fn last_activity(&self, change: &ChangeInfo) -> DateTime<Utc> {
+        let tolerance = Duration::minutes(RETRIGGER_TOLERANCE_MINUTES);
+        self.get(change.id_number)
+            .and_then(|history| {
+                history
+                    .attempts
+                    .iter()
+                    .rev()
+                    .find(|attempt| attempt.updated.is_some())
+            })
+            .filter(|attempt| {
+                attempt.updated.is_some_and(|updated| {
+                    change.updated <= updated + tolerance
+                })
+            })
+            .and_then(|attempt| attempt.previous_updated)
+            .unwrap_or(change.updated)
+    }
+
+    /// Record a retrigger of a change's current patchset, given when the
+    /// retrigger updated the change
+    pub fn record(
+        &mut self,
+        change: &Change,
+        updated: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) {
+        let previous_updated = self.last_activity(&change.change);
+        self.changes
+            .entry(change.change.id_number)
+            .or_default()
+            .attempts
+            .push(Attempt {
+                revision: change.change.current_revision.clone(),
+                time: now,
+                succeeded: None,
+                previous_updated: Some(previous_updated),
+                updated: Some(updated),
+            });
+        self.save();
+    }
+
+    /// Record that Gerrit rejected the retrigger of a change's current
+    /// patchset, which counts as a retrigger CI did not vote after
+    pub fn record_failure(&mut self, change: &Change, now: DateTime<Utc>) {
+        self.changes
+            .entry(change.change.id_number)
+            .or_default()
+            .attempts
+            .push(Attempt {
+                revision: change.change.current_revision.clone(),
+                time: now,
+                succeeded: Some(false),
+                previous_updated: None,
+                updated: None,
+            });
+        self.save();
+    }
+
+    /// Record that a change was escalated
+    pub fn escalate(&mut self, id_number: u64, now: DateTime<Utc>) {
+        self.changes.entry(id_number).or_default().escalated = Some(now);
+        self.save();
+    }
+
+    /// Settle the outcome of pending retriggers now that a change was seen:
+    /// CI voted if the change is no longer missing it, and failed if it is
+    /// still missing after the wait.  Returns whether anything changed.
+    fn update(
+        &mut self,
+        change: &Change,
+        wait: Duration,
+        now: DateTime<Utc>,
+    ) -> bool {
+        let Some(history) = self.changes.get_mut(&change.change.id_number)
+        else {
+            return false;
+        };
+
+        let missing = change.review_state == ReviewState::MissingCI;
+        let mut updated = false;
+        for attempt in &mut history.attempts {
+            if attempt.succeeded.is_some() {
+                continue;
+            }
+            if !missing {
+                attempt.succeeded = Some(true);
+                updated = true;
+            } else if now - attempt.time >= wait {
+                attempt.succeeded = Some(false);
+                updated = true;
+            }
+        }
+
+        // Once CI votes again the change is no longer escalated
+        if !missing && history.escalated.is_some() {
+            history.escalated = None;
+            updated = true;
+        }
+        updated
+    }
+
+    // Forget changes not retriggered for a long time.
+    fn prune(&mut self, now: DateTime<Utc>) {
+        let cutoff = now - Duration::days(RETRIGGER_HISTORY_DAYS);
+        self.changes.retain(|_, history| {
+            history
+                .attempts
+                .last()
+                .is_some_and(|attempt| attempt.time >= cutoff)
+        });
+    }
+}
+
+/// What to do about a change missing CI
+enum Step {
+    Retrigger(Change),
+    Escalate(Change, usize),
+}
+
+// Settle earlier retriggers and decide which changes to retrigger or
+// escalate.
+fn select_steps(
+    context: &ServiceContext,
+    config: &RetriggerConfig,
+    now: DateTime<Utc>,
+) -> Vec<Step> {
+    let wait = Duration::hours(config.wait_hours);
+    let mut ctx = context.lock().unwrap();
+    let changes: Vec<Change> = ctx.changes.changes.values().cloned().collect();
+
+    let mut updated = false;
+    for change in &changes {
+        updated |= ctx.retriggers.update(change, wait, now);
+    }
+    if updated {
+        ctx.retriggers.prune(now);
+        ctx.retriggers.save();
+    }
+
+    let max_age = Duration::days(config.max_age_days);
+    let mut steps = Vec::new();
+    for change in changes {
+        if change.review_state != ReviewState::MissingCI
+            || now - change.change.updated < wait
+            || now - ctx.retriggers.last_activity(&change.change) > max_age
+            || !config.includes_project(&change.change.project)
+            || ctx
+                .exemptions
+                .is_exempt(&change.change, Automation::Retrigger)
+        {
+            continue;
+        }
+
+        let history = ctx
+            .retriggers
+            .get(change.change.id_number)
+            .cloned()
+            .unwrap_or_default();
+        if history.escalated.is_some() {
+            continue;
+        }
+
+        // Each patchset is retriggered once, so a change whose current
+        // patchset was retriggered in vain is stuck until escalated
+        let failures = history.failures();
+        let current = history
+            .attempts
+            .iter()
+            .find(|attempt| attempt.revision == change.change.current_revision);
+        match current {
+            None if failures < config.max_failures => {
+                steps.push(Step::Retrigger(change));
+            }
+            None => steps.push(Step::Escalate(change, failures)),
+            Some(attempt) if attempt.succeeded == Some(false) => {
+                steps.push(Step::Escalate(change, failures));
+            }
+            Some(_) => {}
+        }
+    }
+    steps
+}
+
+// Post the retrigger comment or votes on a change.
+async fn retrigger(
+    context: &ServiceContext,
+    config: &RetriggerConfig,
+    change: &Change,
+    now: DateTime<Utc>,
+) {
+    let gerrit = context.get_gerrit();
+    let result = gerrit
+        .post_review(
+            &change.change.id,
+            config.message.clone(),
+            config.labels.clone(),
+        )
+        .await;
+    AuditEntry::new(
+        AuditAction::Retrigger,
+        "ci-retrigger",
+        "gerrit",
+        vec![change.change.id_number],
+    )
+    .message(&config.message)
+    .result(&result)
+    .record();
+
+    if result.is_err() {
+        warn!(
+            "Was not able to retrigger CI on {}",
+            change.change.id_number
+        );
+        context
+            .lock()
+            .unwrap()
+            .retriggers
+            .record_failure(change, now);
+        return;
+    }
+    info!("Retriggered CI on change {}", change.change.id_number);
+
+    // The retrigger updates the change; remember by how much so that only
+    // later updates count as activity.
+    let updated = gerrit
+        .query_changes(&format!("change:{}", change.change.id_number), &[])
+        .await
+        .ok()
+        .and_then(|changes| changes.first().map(|updated| updated.updated))
+        .unwrap_or(now);
+    context
+        .lock()
+        .unwrap()
+        .retriggers
+        .record(change, updated, now);
+}
+
+// Stop retriggering a change CI keeps missing and tell the escalation
+// channel about it.
+async fn escalate(
+    context: &ServiceContext,
+    config: &RetriggerConfig,
+    backends: &Backends,
+    change: &Change,
+    failures: usize,
+    now: DateTime<Utc>,
+) {
+    warn!(
+        "CI did not vote on change {} after {} retriggers",
+        change.change.id_number, failures
+    );
+    context
+        .lock()
+        .unwrap()
+        .retriggers
+        .escalate(change.change.id_number, now);
+
+    let Some(escalation) = &config.escalation else {
+        return;
+    };
+    let Some(channel) = &escalation.channel_id else {
+        return;
+    };
+    let Some(backend) = backends.get(&escalation.backend) else {
+        error!(
+            "CI retrigger escalation uses backend {}, which is not enabled",
+            escalation.backend
+        );
+        return;
+    };
+
+    let mut message = ChatMessage::new("CI Not Running").change(change);
+    message.description = Some(format!(
+        "CI has not voted after {} retriggers in a row.",
+        failures
+    ));
+    let result = backend.send_message(channel, &message).await;
+    AuditEntry::new(
+        AuditAction::Escalate,
+        "ci-retrigger",
+        &format!("{}:{}", escalation.backend, channel),
+        vec![change.change.id_number],
+    )
+    .message(&message.to_markdown())
+    .result(&result)
+    .record();
+    if let Err(e) = result {
+        error!("CI retrigger escalation: {}", e);
+    }
+}
+
+/// Periodic task retriggering CI on changes it has missed, if enabled by
+/// CI_RETRIGGER_CONFIG
+pub async fn serve(context: ServiceContext, backends: Backends) {
+    let config = match RetriggerConfig::load() {
+        Ok(Some(config)) => config,
+        Ok(None) => return,
+        Err(e) => {
+            error!("CI retrigger disabled: {}", e);
+            return;
+        }
+    };
+    info!("CI retrigger enabled: {:?}", config);
+
+    loop {
+        sleep(std::time::Duration::from_secs(RETRIGGER_INTERVAL_SECS)).await;
+
+        let now = Utc::now();
+        for step in select_steps(&context, &config, now) {
+            match step {
+                Step::Retrigger(change) => {
+                    retrigger(&context, &config, &change, now).await;
+                }
+                Step::Escalate(change, failures) => {
+                    escalate(
+                        &context, &config, &backends, &change, failures, now,
+                    )
+                    .await;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gerrit::data::test_change;
+    use serde_json::json;
+
+    // A change missing CI, last updated a day ago
+    fn missing_ci() -> Change {
+        let mut change = test_change(1, json!({"current_revision": "def"}));
+        change.updated = Utc::now() - Duration::days(1);
+        Change {
+            change,
+            review_state: ReviewState::MissingCI,
+            review_state_updated: Utc::now() - Duration::days(7),
+        }
+    }
+
+    // Select the steps for a change missing CI whose earlier patchsets
+    // were retriggered in vain
+    fn steps(revisions: &[&str]) -> Vec<Step> {
+        let context = ServiceContext::detached();
+        {
+            let mut ctx = context.lock().unwrap();
+            ctx.changes.changes.insert(1, missing_ci());
+            let attempts = revisions
+                .iter()
+                .map(|revision| Attempt {
+                    revision: revision.to_string(),
+                    time: Utc::now() - Duration::days(1),
+                    succeeded: Some(false),
+                    previous_updated: None,
+                    updated: None,
+                })
+                .collect();
+            ctx.retriggers.changes.insert(
+                1,
+                RetriggerHistory {
+                    attempts,
+                    escalated: None,
+                },
+            );
+        }
+        let config: RetriggerConfig = serde_yaml::from_str("{}").unwrap();
+        select_steps(&context, &config, Utc::now())
+    }
+
+    #[test]
+    fn new_patchsets_are_retriggered() {
+        assert!(matches!(steps(&[])[..], [Step::Retrigger(_)]));
+        assert!(matches!(steps(&["abc"])[..], [Step::Retrigger(_)]));
+    }
+
+    #[test]
+    fn failing_retriggers_are_escalated() {
+        assert!(matches!(steps(&["abc", "bcd"])[..], [Step::Escalate(_, 2)]));
+    }
+
+    #[test]
+    fn a_stuck_patchset_is_escalated() {
+        assert!(matches!(steps(&["def"])[..], [Step::Escalate(_, 1)]));
+    }
+
+    #[test]
+    fn retriggers_are_not_activity() {
+        let mut change = missing_ci().change;
+        let active = change.updated;
+        let retriggered = active + Duration::hours(6);
+        let mut retriggers = Retriggers::default();
+        retriggers.changes.insert(
+            1,
+            RetriggerHistory {
+                attempts: vec![Attempt {
+                    revision: "def".to_string(),
+                    time: retriggered,
+                    succeeded: None,
+                    previous_updated: Some(active),
+                    updated: Some(retriggered),
+                }],
+                escalated: None,
+            },
+        );
+
+        change.updated = retriggered + Duration::minutes(1);
+        assert_eq!(retriggers.last_activity(&change), active);
+        change.updated = retriggered + Duration::hours(1);
+        assert_eq!(retriggers.last_activity(&change), change.updated);
+    }
+
+    #[test]
+    fn old_changes_are_left_alone() {
+        let context = ServiceContext::detached();
+        let change = missing_ci();
+        let now = change.change.updated;
+        context.lock().unwrap().changes.changes.insert(1, change);
+        let config: RetriggerConfig =
+            serde_yaml::from_str("max_age_days: 30").unwrap();
+        assert!(matches!(
+            select_steps(&context, &config, now + Duration::days(29))[..],
+            [Step::Retrigger(_)]
+        ));
+        assert!(
+            select_steps(&context, &config, now + Duration::days(31))
+                .is_empty()
+        );
+    }
+}
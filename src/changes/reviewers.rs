@@ -0,0 +1,130 @@
+use crate::changes::report::{ReportFormat, ReportTable};
+use crate::changes::status::ReviewState;
+use crate::context::ServiceContext;
+use chrono::{Duration, Utc};
+use std::collections::HashMap;
+
+/// Default window, in days, for counting recent votes
+pub const DEFAULT_REVIEWER_DAYS: i64 = 30;
+
+/// Review activity of a single reviewer across the open changes
+#[derive(Debug, Default, Clone)]
+pub struct ReviewerStats {
+    /// Open changes the reviewer has a non-zero Code-Review vote on
+    pub voted: Vec<u64>,
+    /// Open changes waiting on the author to address the reviewer's -1
+    pub blocking: Vec<u64>,
+    /// Votes cast within the requested number of days
+    pub recent_votes: u64,
+}
+
+/// A map structure that tracks review activity by reviewer username
+#[derive(Debug, Default)]
+pub struct ChangesByReviewer(HashMap<String, ReviewerStats>);
+
+impl ChangesByReviewer {
+    /// Get the stats for a specific reviewer
+    pub fn get_reviewer(&self, reviewer: &str) -> Option<&ReviewerStats> {
+        self.0.get(reviewer)
+    }
+
+    /// Get all reviewers, most active first
+    pub fn get_reviewers(&self) -> Vec<&String> {
+        let mut reviewers: Vec<&String> = self.0.keys().collect();
+        reviewers.sort_by(|a, b| {
+            let (a_stats, b_stats) = (&self.0[*a], &self.0[*b]);
+            b_stats
+                .recent_votes
+                .cmp(&a_stats.recent_votes)
+                .then(b_stats.voted.len().cmp(&a_stats.voted.len()))
+                .then(a.cmp(b))
+        });
+        reviewers
+    }
+}
+
+pub fn changes_by_reviewer(
+    context: &ServiceContext,
+    days: i64,
+) -> ChangesByReviewer {
+    let mut reviewers = ChangesByReviewer::default();
+    let cutoff = Utc::now() - Duration::days(days);
+
+    for change in context.lock().unwrap().changes.changes.values() {
+        let id_number = change.change.id_number;
+
+        if let Some(votes) = change.change.labels.get("Code-Review") {
+            for vote in votes.iter() {
+                if vote.username == change.change.owner.username
+                    || vote.value == 0
+                {
+                    continue;
+                }
+
+                let stats =
+                    reviewers.0.entry(vote.username.clone()).or_default();
+                stats.voted.push(id_number);
+                if vote.date().is_some_and(|date| date >= cutoff) {
+                    stats.recent_votes += 1;
+                }
+            }
+        }
+
+        if let ReviewState::PendingFeedback(ref user) = change.review_state {
+            reviewers
+                .0
+                .entry(user.clone())
+                .or_default()
+                .blocking
+                .push(id_number);
+        }
+    }
+
+    reviewers
+}
+
+pub fn table_by_reviewer(
+    reviewers: &ChangesByReviewer,
+    days: i64,
+    limit: Option<usize>,
+) -> ReportTable {
+    let rows = reviewers
+        .get_reviewers()
+        .into_iter()
+        .take(limit.unwrap_or(usize::MAX))
+        .map(|reviewer| {
+            let stats = &reviewers.0[reviewer];
+            vec![
+                reviewer.clone(),
+                stats.voted.len().to_string(),
+                stats.blocking.len().to_string(),
+                stats.recent_votes.to_string(),
+            ]
+        })
+        .collect();
+
+    ReportTable {
+        label: "Reviewer".to_string(),
+        header: vec![
+            "".to_string(),
+            "Voted".to_string(),
+            "Blocking".to_string(),
+            format!("Votes ({} days)", days),
+        ],
+        rows,
+        numeric: vec![1, 2, 3],
+    }
+}
+
+pub fn report_by_reviewer(
+    context: &ServiceContext,
+    days: i64,
+    limit: Option<usize>,
+    format: ReportFormat,
+) -> String {
+    format.renderer().render(&table_by_reviewer(
+        &changes_by_reviewer(context, days),
+        days,
+        limit,
+    ))
+}
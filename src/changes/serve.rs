@@ -1,128 +1,258 @@
+use crate::audit::{AuditAction, AuditEntry};
+use crate::changes::exemptions::Automation;
+use crate::changes::policy::{Action, PoliciesConfig, Verdict, Warning};
+use crate::changes::{owners, trends};
 use crate::context::ServiceContext;
 use crate::gerrit::connection::GerritConnection;
-use crate::gerrit::data::{ApprovalInfo, ChangeInfo};
-use chrono::{TimeZone, Utc};
+use crate::gerrit::data::ChangeInfo;
+use chrono::{DateTime, TimeZone, Utc};
+use std::collections::HashMap;
 use tokio::time::{Duration, sleep};
-use tracing::{debug, info, warn};
+use tracing::{debug, error, info, warn};
 
-async fn abandon_older_than_two_years(
+// Post a review comment warning that a change is about to be abandoned.
+async fn warn_change(
     context: &ServiceContext,
     change: &ChangeInfo,
-) -> bool {
-    let now = Utc::now();
-    let two_years_ago = now - chrono::Duration::days(2 * 365);
-
-    // Check if the change is older than two years
-    if change.updated >= two_years_ago {
-        return false;
-    }
-
+    verdict: Verdict,
+    now: DateTime<Utc>,
+) {
     info!(
-        "Abandoning change {} (last updated: {})",
-        change.id, change.updated
+        "Warning change {} of abandonment by policy {}",
+        change.id, verdict.policy
     );
 
-    // Call the abandon_change method
-    if let Some(_) = context
-        .get_gerrit()
-        .abandon_change(
-            &change.id,
-            concat!(
-                "Automatically abandoned due to inactivity of over two years.\n",
-                "Please rebase and reopen if this should still be merged.",
-            ).to_string(),
-        )
-        .await
-    {
-        info!("Successfully abandoned change {}", change.id);
-        return true;
+    let gerrit = context.get_gerrit();
+    let result = gerrit
+        .post_review(&change.id, verdict.message.clone(), HashMap::new())
+        .await;
+    AuditEntry::new(
+        AuditAction::Warn,
+        &verdict.policy,
+        "gerrit",
+        vec![change.id_number],
+    )
+    .message(&verdict.message)
+    .result(&result)
+    .record();
+    if result.is_err() {
+        warn!("Was not able to warn change {}.", change.id);
+        return;
     }
 
-    warn!("Was not able to abandon change {}.", change.id);
-    false
+    // The comment updates the change; remember by how much so that only
+    // later updates count as activity.
+    let updated = gerrit
+        .query_changes(&format!("change:{}", change.id_number), &[])
+        .await
+        .ok()
+        .and_then(|changes| changes.first().map(|updated| updated.updated))
+        .unwrap_or(now);
+
+    context.lock().unwrap().abandon_warnings.record(
+        change.id_number,
+        Warning {
+            policy: verdict.policy,
+            warned: now,
+            previous_updated: change.updated,
+            updated,
+        },
+    );
 }
 
-async fn abandon_older_than_one_year_and_bad_ci(
+// Warn about or abandon a change according to the policies, returning
+// whether it was abandoned.
+async fn apply_policies(
     context: &ServiceContext,
+    policies: &PoliciesConfig,
     change: &ChangeInfo,
 ) -> bool {
     let now = Utc::now();
-    let one_year_ago = now - chrono::Duration::days(1 * 365);
-
-    // Check if the change is older than one year
-    if change.updated >= one_year_ago {
-        return false;
-    }
-
-    let mut found_verified: Option<ApprovalInfo> = None;
-    for score in change.labels["Verified"].iter() {
-        if score.username != "jenkins-openbmc-ci" || score.value > 0 {
-            continue;
+    let mut change = change.clone();
+    let warning = {
+        let mut ctx = context.lock().unwrap();
+        // The bot's own CI retriggers do not count as activity
+        change.updated = ctx.retriggers.last_activity(&change);
+        let change = &change;
+        if ctx.exemptions.is_exempt(change, Automation::Abandon) {
+            debug!("Change {} is exempt from abandonment", change.id);
+            ctx.abandon_warnings.remove(change.id_number);
+            return false;
         }
-        found_verified = Some(score.clone());
-    }
+        match ctx.abandon_warnings.get(change.id_number).cloned() {
+            Some(warning) if warning.outdated(change) => {
+                debug!("Change {} updated since its warning", change.id);
+                ctx.abandon_warnings.remove(change.id_number);
+                None
+            }
+            warning => warning,
+        }
+    };
 
-    if !found_verified.is_some() {
-        return false;
-    }
-    let ci_failure = found_verified.unwrap();
+    let change = &change;
+    let verdict = match policies.decide(change, warning.as_ref(), now) {
+        Action::None => return false,
+        Action::Warn(verdict) => {
+            warn_change(context, change, verdict, now).await;
+            return false;
+        }
+        Action::Abandon(verdict) => verdict,
+    };
 
     info!(
-        "Abandoning change {} (not verified: {}, {})",
-        change.id, ci_failure.username, ci_failure.value,
+        "Abandoning change {} by policy {} (last updated: {})",
+        change.id, verdict.policy, change.updated
     );
 
-    // Call the abandon_change method
-    if let Some(_) = context
+    let abandoned = context
         .get_gerrit()
-        .abandon_change(
-            &change.id,
-            concat!(
-                "Automatically abandoned due to missing or failing CI and inactivity of over one year.\n",
-                "Please rebase, resolve CI and reopen if this should still be merged.\n",
-                "\n",
-            )
-            .to_string()
-            + &format!(
-                "CI status: {}={}",
-                ci_failure.username,
-                ci_failure.value
-            )
-        )
+        .abandon_change(&change.id, verdict.message.clone())
         .await
-    {
+        .is_some();
+    AuditEntry::new(
+        AuditAction::Abandon,
+        &verdict.policy,
+        "gerrit",
+        vec![change.id_number],
+    )
+    .message(&verdict.message)
+    .result(&if abandoned {
+        Ok(())
+    } else {
+        Err("Conflict".to_string())
+    })
+    .record();
+
+    if abandoned {
         info!("Successfully abandoned change {}", change.id);
+        let mut ctx = context.lock().unwrap();
+        ctx.abandonments.record(change, &verdict.policy);
+        ctx.abandon_warnings.remove(change.id_number);
         return true;
     }
-    warn!("Was not able to abandon change {}", change.id);
+
+    warn!("Was not able to abandon change {}.", change.id);
     false
 }
 
+/// Query options giving the detail needed to work out a review state.
+pub const CHANGE_DETAIL_OPTIONS: [&str; 5] = [
+    "LABELS",
+    "DETAILED_LABELS",
+    "DETAILED_ACCOUNTS",
+    "CURRENT_REVISION",
+    "CURRENT_FILES",
+];
+
+/// Fetch all open changes into the container once, without applying the
+/// abandonment policies
+pub async fn fetch_open_changes(context: &ServiceContext) {
+    let changes = context.get_gerrit().all_open_changes().await;
+    let mut ctx = context.lock().unwrap();
+    for change in &changes {
+        ctx.changes.set(change);
+    }
+}
+
+/// Fetch a single change, by number or Change-Id, in any status.  Gerrit
+/// rejects malformed change ids, which fails like an unknown change.
+pub async fn fetch_change<G: GerritConnection + Sync>(
+    gerrit: &G,
+    change_id: &str,
+) -> Result<ChangeInfo, String> {
+    gerrit
+        .query_changes(&format!("change:{}", change_id), &CHANGE_DETAIL_OPTIONS)
+        .await
+        .map_err(|e| format!("Could not find change {}: {}", change_id, e))?
+        .into_iter()
+        .next()
+        .ok_or_else(|| format!("Could not find change: {}", change_id))
+}
+
 pub async fn serve(context: ServiceContext) {
+    let policies = PoliciesConfig::load().unwrap_or_else(|e| {
+        error!("Automatic abandonment disabled: {}", e);
+        PoliciesConfig {
+            policies: Vec::new(),
+        }
+    });
+
     let mut last_full_sync = Utc.timestamp_opt(0, 0).unwrap();
 
     loop {
-        let changes =
-            if Utc::now().signed_duration_since(last_full_sync).num_days() >= 1
-            {
-                last_full_sync = Utc::now();
-                debug!("Performing daily full sync of open changes");
-                context.get_gerrit().all_open_changes().await
-            } else {
-                context.get_gerrit().recent_changes().await
-            };
+        let full_sync =
+            Utc::now().signed_duration_since(last_full_sync).num_days() >= 1;
+        let changes = if full_sync {
+            last_full_sync = Utc::now();
+            debug!("Performing daily full sync of open changes");
+            context.get_gerrit().all_open_changes().await
+        } else {
+            context.get_gerrit().recent_changes().await
+        };
 
         for change in &changes {
-            context.lock().unwrap().changes.set(change);
-
-            if abandon_older_than_two_years(&context, change).await
-                || abandon_older_than_one_year_and_bad_ci(&context, change)
-                    .await
             {
+                let mut ctx = context.lock().unwrap();
+                if let Some(transition) = ctx.changes.set(change) {
+                    debug!(
+                        "Change {} moved from {:?} to {:?}",
+                        change.id_number,
+                        transition.previous,
+                        transition.change.review_state
+                    );
+                    // Sending only fails when nobody is listening.
+                    let _ = ctx.transitions.send(transition);
+                }
+            }
+
+            if apply_policies(&context, &policies, change).await {
                 context.lock().unwrap().changes.remove(change);
             }
         }
 
+        // Record a backlog snapshot for the trend charts and refresh the
+        // OWNERS files once the full set of open changes is known.
+        if full_sync {
+            trends::record_snapshot(&context);
+            owners::refresh(&context).await;
+        }
+
         sleep(Duration::from_secs(60)).await;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gerrit::data::test_change;
+    use crate::gerrit::stub::StubGerrit;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn fetch_change_finds_the_change() {
+        let gerrit = StubGerrit::new(Ok(vec![test_change(12, json!({}))]));
+        let change = fetch_change(&gerrit, "12").await.unwrap();
+        assert_eq!(change.id_number, 12);
+        assert_eq!(gerrit.queries(), vec!["change:12"]);
+    }
+
+    #[tokio::test]
+    async fn fetch_change_fails_for_unknown_and_rejected_ids() {
+        let gerrit = StubGerrit::new(Ok(Vec::new()));
+        assert_eq!(
+            fetch_change(&gerrit, "12").await.unwrap_err(),
+            "Could not find change: 12"
+        );
+
+        let gerrit = StubGerrit::new(Err(
+            "Query change:bad failed: 400 Bad Request: invalid change"
+                .to_string(),
+        ));
+        let error = fetch_change(&gerrit, "bad").await.unwrap_err();
+        assert!(
+            error.starts_with("Could not find change bad: "),
+            "{}",
+            error
+        );
+    }
+}
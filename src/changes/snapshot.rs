@@ -0,0 +1,155 @@
+use crate::context::ServiceContext;
+use crate::gerrit::data::{ChangeInfo, ChangeInfoRaw};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+/// Open changes as recorded at one time
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub time: DateTime<Utc>,
+    pub changes: Vec<ChangeInfo>,
+}
+
+/// A snapshot as saved by `capture`: the time it was taken and the changes
+/// as Gerrit returned them
+#[derive(Deserialize)]
+struct Capture {
+    time: DateTime<Utc>,
+    changes: Vec<ChangeInfoRaw>,
+}
+
+// Strip the magic prefix of a Gerrit response, if there is one.
+fn strip_magic(text: &str) -> &str {
+    text.strip_prefix(")]}'").unwrap_or(text).trim_start()
+}
+
+// Parse a Gerrit `/changes/` response, with or without its magic prefix.
+fn parse(text: &str) -> Result<Vec<ChangeInfoRaw>, String> {
+    serde_json::from_str::<Vec<ChangeInfoRaw>>(strip_magic(text))
+        .map_err(|e| e.to_string())
+}
+
+impl Snapshot {
+    /// Load a snapshot saved by `capture`, or a saved Gerrit `/changes/`
+    /// response.  A bare response is dated by its most recently updated
+    /// change, or by the file's modification time if there are no open
+    /// changes.
+    pub fn from_file(path: &str) -> Result<Snapshot, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+        let json = strip_magic(&text);
+        let (time, changes) = if json.starts_with('[') {
+            (None, parse(json))
+        } else {
+            match serde_json::from_str::<Capture>(json) {
+                Ok(capture) => (Some(capture.time), Ok(capture.changes)),
+                Err(e) => (None, Err(e.to_string())),
+            }
+        };
+        let changes: Vec<ChangeInfo> = changes
+            .map_err(|e| format!("Failed to parse {}: {}", path, e))?
+            .into_iter()
+            .map(Into::into)
+            .collect();
+
+        let latest = changes.iter().map(|change| change.updated).max();
+        let time = match time.or(latest) {
+            Some(time) => time,
+            None => std::fs::metadata(path)
+                .and_then(|metadata| metadata.modified())
+                .map(DateTime::<Utc>::from)
+                .map_err(|e| format!("Failed to date {}: {}", path, e))?,
+        };
+        Ok(Snapshot { time, changes })
+    }
+
+    /// Replace the changes of a context with the snapshot's
+    pub fn load_into(&self, context: &ServiceContext) {
+        let mut ctx = context.lock().unwrap();
+        ctx.changes = Default::default();
+        for change in &self.changes {
+            ctx.changes.set(change);
+        }
+    }
+}
+
+/// Fetch the open changes from Gerrit and save them with the current time as
+/// a snapshot, returning the number of changes.
+pub async fn capture(
+    context: &ServiceContext,
+    path: &str,
+) -> Result<usize, String> {
+    let time = Utc::now();
+    let json = context.get_gerrit().open_changes_json().await;
+    let count = parse(&json)
+        .map_err(|e| format!("Invalid response from Gerrit: {}", e))?
+        .len();
+    // Keep the changes exactly as Gerrit returned them
+    let changes: serde_json::Value =
+        serde_json::from_str(strip_magic(&json))
+            .map_err(|e| format!("Invalid response from Gerrit: {}", e))?;
+    let snapshot = serde_json::json!({"time": time, "changes": changes});
+    std::fs::write(path, snapshot.to_string())
+        .map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gerrit::data::test_change_json;
+    use serde_json::json;
+
+    // Write a capture to a temporary file and load it
+    fn load(name: &str, text: &str) -> Snapshot {
+        let path = std::env::temp_dir().join(format!(
+            "snapshot-{}-{}.json",
+            name,
+            std::process::id()
+        ));
+        std::fs::write(&path, text).unwrap();
+        let snapshot = Snapshot::from_file(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        snapshot
+    }
+
+    #[test]
+    fn captures_are_dated_by_their_time() {
+        let text = json!({
+            "time": "2025-04-01T12:00:00Z",
+            "changes": [test_change_json(1, json!({}))],
+        });
+        let snapshot = load("capture", &text.to_string());
+        assert_eq!(snapshot.changes.len(), 1);
+        assert_eq!(snapshot.time.to_rfc3339(), "2025-04-01T12:00:00+00:00");
+    }
+
+    #[test]
+    fn responses_are_dated_by_the_latest_update() {
+        let text = format!(
+            ")]}}'\n{}",
+            json!([
+                test_change_json(
+                    1,
+                    json!({"updated": "2025-03-01 10:00:00.000000000"})
+                ),
+                test_change_json(
+                    2,
+                    json!({"updated": "2025-02-01 10:00:00.000000000"})
+                ),
+            ])
+        );
+
+        let snapshot = load("changes", &text);
+        assert_eq!(snapshot.changes.len(), 2);
+        assert_eq!(snapshot.time.to_rfc3339(), "2025-03-01T10:00:00+00:00");
+    }
+
+    #[test]
+    fn empty_responses_are_dated_by_the_file() {
+        let before = Utc::now() - chrono::Duration::minutes(1);
+        let snapshot = load("empty", ")]}'\n[]");
+        assert!(snapshot.changes.is_empty());
+        assert!(snapshot.time >= before, "{}", snapshot.time);
+    }
+}
@@ -1,5 +1,6 @@
 use crate::gerrit::data as GerritData;
 use enum_map::Enum;
+use serde::{Deserialize, Serialize};
 
 #[derive(Clone, PartialEq)]
 pub enum ReviewState {
@@ -44,7 +45,81 @@ impl std::fmt::Debug for ReviewState {
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Enum, Debug)]
+impl ReviewState {
+    /// Get the variant name, without any details, as used in configuration
+    pub fn name(&self) -> &'static str {
+        match self {
+            ReviewState::Unknown => "Unknown",
+            ReviewState::MissingCI => "MissingCI",
+            ReviewState::FailingCI => "FailingCI",
+            ReviewState::MergeConflict => "MergeConflict",
+            ReviewState::PendingFeedback(_) => "PendingFeedback",
+            ReviewState::PendingCommentResolution(_) => {
+                "PendingCommentResolution"
+            }
+            ReviewState::CommunityReview => "CommunityReview",
+            ReviewState::MaintainerReview => "MaintainerReview",
+            ReviewState::ReadyToSubmit => "ReadyToSubmit",
+        }
+    }
+
+    /// Explain what the change is waiting for
+    pub fn explanation(&self) -> String {
+        match self {
+            ReviewState::Unknown => {
+                "The change does not match any known review state.".to_string()
+            }
+            ReviewState::MissingCI => {
+                "CI has not voted on the current patchset.".to_string()
+            }
+            ReviewState::FailingCI => {
+                "CI voted against the current patchset; the author needs to \
+                 fix the failure."
+                    .to_string()
+            }
+            ReviewState::MergeConflict => {
+                "The change does not merge cleanly and needs a rebase."
+                    .to_string()
+            }
+            ReviewState::PendingFeedback(user) => format!(
+                "{} voted against the change; the author needs to address \
+                 the feedback.",
+                user
+            ),
+            ReviewState::PendingCommentResolution(count) => format!(
+                "{} unresolved comment(s) need the author's attention.",
+                count
+            ),
+            ReviewState::CommunityReview => {
+                "Nobody besides the author has reviewed the change yet."
+                    .to_string()
+            }
+            ReviewState::MaintainerReview => {
+                "The change needs approval from a maintainer listed in OWNERS."
+                    .to_string()
+            }
+            ReviewState::ReadyToSubmit => {
+                "All submit requirements are met; a maintainer can submit it."
+                    .to_string()
+            }
+        }
+    }
+
+    /// All variant names accepted by `name`
+    pub const NAMES: [&'static str; 9] = [
+        "Unknown",
+        "MissingCI",
+        "FailingCI",
+        "MergeConflict",
+        "PendingFeedback",
+        "PendingCommentResolution",
+        "CommunityReview",
+        "MaintainerReview",
+        "ReadyToSubmit",
+    ];
+}
+
+#[derive(Clone, Copy, PartialEq, Enum, Debug, Serialize, Deserialize)]
 pub enum NextStepOwner {
     Author,
     Community,
@@ -0,0 +1,127 @@
+use crate::changes::report::TimeInterval;
+use crate::changes::status::NextStepOwner;
+use crate::context::ServiceContext;
+use crate::state;
+use chrono::{DateTime, Utc};
+use enum_map::EnumMap;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+const TRENDS_FILE: &str = "trends.json";
+
+/// Counts of changes by time interval and next step owner at one point in
+/// time.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TrendCounts(EnumMap<TimeInterval, EnumMap<NextStepOwner, u64>>);
+
+impl TrendCounts {
+    pub fn increment(
+        &mut self,
+        time_interval: TimeInterval,
+        owner: NextStepOwner,
+    ) {
+        self.0[time_interval][owner] += 1;
+    }
+
+    pub fn add(&mut self, other: &TrendCounts) {
+        for (time_interval, owners) in &other.0 {
+            for (owner, count) in owners {
+                self.0[time_interval][owner] += count;
+            }
+        }
+    }
+
+    /// Get the count for a specific next step owner across all intervals
+    pub fn get_owner_count(&self, owner: NextStepOwner) -> u64 {
+        self.0.values().map(|owners| owners[owner]).sum()
+    }
+
+    /// Get the count for a specific time interval across all owners
+    pub fn get_interval_count(&self, time_interval: TimeInterval) -> u64 {
+        self.0[time_interval].values().sum()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrendSnapshot {
+    pub timestamp: DateTime<Utc>,
+    pub overall: TrendCounts,
+    pub projects: HashMap<String, TrendCounts>,
+}
+
+impl TrendSnapshot {
+    /// Get the counts for a set of projects, or overall if none are given
+    pub fn counts(&self, projects: &[&str]) -> TrendCounts {
+        if projects.is_empty() {
+            return self.overall.clone();
+        }
+
+        let mut counts = TrendCounts::default();
+        for project in projects {
+            if let Some(project_counts) = self.projects.get(*project) {
+                counts.add(project_counts);
+            }
+        }
+        counts
+    }
+}
+
+/// Time-series of backlog snapshots, persisted in the state directory.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Trends {
+    pub snapshots: Vec<TrendSnapshot>,
+}
+
+impl Trends {
+    pub fn load() -> Trends {
+        state::load(TRENDS_FILE)
+    }
+
+    pub fn save(&self) {
+        state::save(TRENDS_FILE, self);
+    }
+
+    /// Record a snapshot, keeping at most one per calendar day.
+    pub fn record(&mut self, snapshot: TrendSnapshot) {
+        if let Some(last) = self.snapshots.last()
+            && last.timestamp.date_naive() == snapshot.timestamp.date_naive()
+        {
+            self.snapshots.pop();
+        }
+        self.snapshots.push(snapshot);
+    }
+}
+
+/// Take a snapshot of the current backlog counts.
+pub fn snapshot(context: &ServiceContext) -> TrendSnapshot {
+    let mut overall = TrendCounts::default();
+    let mut projects = HashMap::<String, TrendCounts>::new();
+    let now = Utc::now();
+
+    for change in context.lock().unwrap().changes.changes.values() {
+        let time_unit =
+            TimeInterval::from_timestamp(change.review_state_updated, now);
+        let owner = NextStepOwner::from(change.review_state.clone());
+
+        overall.increment(time_unit, owner);
+        projects
+            .entry(change.change.project.clone())
+            .or_default()
+            .increment(time_unit, owner);
+    }
+
+    TrendSnapshot {
+        timestamp: now,
+        overall,
+        projects,
+    }
+}
+
+/// Snapshot the current backlog and persist it.
+pub fn record_snapshot(context: &ServiceContext) {
+    let snapshot = snapshot(context);
+
+    let mut ctx = context.lock().unwrap();
+    ctx.trends.record(snapshot);
+    ctx.trends.save();
+}
@@ -0,0 +1,167 @@
+use crate::changes::container::Change;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// One titled entry of a chat message, with a Markdown value
+#[derive(Debug, Clone, Serialize)]
+pub struct ChatField {
+    pub name: String,
+    pub value: String,
+}
+
+/// A message that each backend renders in its own way: an embed on Discord,
+/// Markdown text on Matrix, JSON for webhooks.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ChatMessage {
+    /// Text shown above the rest of the message.  Discord only pings users
+    /// mentioned here, not in embeds.
+    pub content: Option<String>,
+    pub title: String,
+    pub description: Option<String>,
+    pub url: Option<String>,
+    pub fields: Vec<ChatField>,
+    pub footer: Option<String>,
+    /// Changes a reader may claim for review, on backends supporting it
+    pub claimable: Vec<u64>,
+}
+
+impl ChatMessage {
+    pub fn new(title: impl Into<String>) -> ChatMessage {
+        ChatMessage {
+            title: title.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Add a field describing a change
+    pub fn change(mut self, change: &Change) -> ChatMessage {
+        let (name, value) = change_field(change);
+        self.fields.push(ChatField { name, value });
+        self
+    }
+
+    /// Render the message as Markdown
+    pub fn to_markdown(&self) -> String {
+        let mut lines = Vec::new();
+        if let Some(content) = &self.content {
+            lines.push(content.clone());
+        }
+        match &self.url {
+            Some(url) => lines.push(format!("**[{}]({})**", self.title, url)),
+            None if !self.title.is_empty() => {
+                lines.push(format!("**{}**", self.title))
+            }
+            None => {}
+        }
+        if let Some(description) = &self.description {
+            lines.push(description.clone());
+        }
+        let mut text = lines.join("\n");
+        for field in &self.fields {
+            text += &format!("\n- {}: {}", field.name, field.value);
+        }
+        if let Some(footer) = &self.footer {
+            text += &format!("\n_{}_", footer);
+        }
+        text
+    }
+}
+
+/// A text command received from a chat backend
+#[derive(Debug, Clone)]
+pub struct ChatCommand {
+    pub channel: String,
+    pub sender: String,
+    pub text: String,
+}
+
+/// A chat platform the bot posts reminders and notifications to, and may
+/// receive commands from.
+#[async_trait::async_trait]
+pub trait ChatBackend: Send + Sync {
+    /// Name used to refer to the backend in configuration
+    fn name(&self) -> &str;
+
+    /// Post a message to a channel or room
+    async fn send_message(
+        &self,
+        channel: &str,
+        message: &ChatMessage,
+    ) -> Result<(), String>;
+
+    /// Send a direct message to a user
+    async fn send_direct(
+        &self,
+        user: &str,
+        message: &ChatMessage,
+    ) -> Result<(), String>;
+
+    /// Wait for the next commands sent to the bot.  Backends which receive
+    /// commands some other way, like Discord's slash commands, return None.
+    async fn receive_commands(&self) -> Option<Vec<ChatCommand>> {
+        None
+    }
+
+    /// Reply to a command
+    async fn reply(
+        &self,
+        command: &ChatCommand,
+        text: &str,
+    ) -> Result<(), String> {
+        let message = ChatMessage {
+            description: Some(text.to_string()),
+            ..Default::default()
+        };
+        self.send_message(&command.channel, &message).await
+    }
+}
+
+/// The configured chat backends by name
+pub type Backends = HashMap<String, Arc<dyn ChatBackend>>;
+
+/// Format a change as a message field name and value.
+pub fn change_field(change: &Change) -> (String, String) {
+    let change_url = format!(
+        "https://gerrit.openbmc.org/c/{}/+/{}",
+        change.change.project, change.change.id_number
+    );
+
+    // Calculate waiting time
+    let now = chrono::Utc::now();
+    let duration = now.signed_duration_since(change.review_state_updated);
+    let waiting_time = format_duration(duration);
+
+    (
+        format!("{} - waiting {}", change.change.project, waiting_time),
+        format!(
+            "[{}]({}) (+{}/-{})",
+            change.change.subject,
+            change_url,
+            change.change.insertions,
+            change.change.deletions,
+        ),
+    )
+}
+
+/// Format duration as simple time string like "1 hour" or "3 days"
+pub fn format_duration(duration: chrono::Duration) -> String {
+    let hours = duration.num_hours();
+    let days = duration.num_days();
+
+    if days > 0 {
+        if days == 1 {
+            "1 day".to_string()
+        } else {
+            format!("{} days", days)
+        }
+    } else if hours > 0 {
+        if hours == 1 {
+            "1 hour".to_string()
+        } else {
+            format!("{} hours", hours)
+        }
+    } else {
+        "less than 1 hour".to_string()
+    }
+}
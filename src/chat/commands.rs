@@ -0,0 +1,221 @@
+use crate::changes::container::Change;
+use crate::changes::report::{ChangeFilter, ReportFormat, report_by_time};
+use crate::changes::reviewers::{DEFAULT_REVIEWER_DAYS, report_by_reviewer};
+use crate::changes::status::ReviewState;
+use crate::context::ServiceContext;
+
+/// Prefix of text commands on backends without native commands.
+pub const COMMAND_PREFIX: &str = "!obmc";
+
+/// Number of reviewers listed by the reviewers commands
+pub const REVIEWERS_TO_SELECT: usize = 15;
+
+const HELP: &str = "Commands:
+!obmc report [project] - report of outstanding changes
+!obmc status <change> - review status of a change
+!obmc reviewers - most active reviewers
+!obmc topic <topic> - review status of a topic";
+
+/// Handle a text command, returning the reply or None if the text is not a
+/// command.
+pub fn handle(context: &ServiceContext, text: &str) -> Option<String> {
+    let mut words = text.strip_prefix(COMMAND_PREFIX)?.split_whitespace();
+    let command = words.next().unwrap_or("help");
+    let argument = words.next();
+
+    Some(match (command, argument) {
+        ("report", project) => {
+            let filter = ChangeFilter {
+                project: project.map(str::to_string),
+                ..Default::default()
+            };
+            format!(
+                "{}:\n{}",
+                project
+                    .map(|p| format!("Project {}", p))
+                    .unwrap_or("Overall Status".to_string()),
+                report_by_time(context, &filter, ReportFormat::Markdown)
+            )
+        }
+        ("status", Some(change_id)) => review_status(context, change_id),
+        ("reviewers", _) => format!(
+            "Reviewers:\n{}",
+            report_by_reviewer(
+                context,
+                DEFAULT_REVIEWER_DAYS,
+                Some(REVIEWERS_TO_SELECT),
+                ReportFormat::Markdown,
+            )
+        ),
+        ("topic", Some(topic)) => topic_status(context, topic),
+        _ => HELP.to_string(),
+    })
+}
+
+/// Find an open change by number or Change-Id
+pub fn find_change(
+    context: &ServiceContext,
+    change_id: &str,
+) -> Option<Change> {
+    let changes = &context.lock().unwrap().changes;
+    match change_id.parse::<u64>() {
+        Ok(id) => changes.get(id),
+        _ => changes.get_by_change_id(&change_id.to_string()),
+    }
+}
+
+/// Describe the review state of a change
+pub fn review_status(context: &ServiceContext, change_id: &str) -> String {
+    match find_change(context, change_id) {
+        Some(change) => {
+            format!("Change {} is {:?}.", change_id, change.review_state)
+        }
+        None => {
+            // Abandoned changes are no longer tracked, but the policy which
+            // abandoned them is remembered
+            let ctx = context.lock().unwrap();
+            match change_id
+                .parse::<u64>()
+                .ok()
+                .and_then(|id| ctx.abandonments.get(id))
+            {
+                Some(abandonment) => format!(
+                    "Change {}: {}.",
+                    change_id,
+                    abandonment.description()
+                ),
+                None => format!("Could not find change: {}", change_id),
+            }
+        }
+    }
+}
+
+/// Get the open changes in a topic, ordered by project and number
+pub fn topic_changes(context: &ServiceContext, topic: &str) -> Vec<Change> {
+    let mut changes: Vec<Change> = context
+        .lock()
+        .unwrap()
+        .changes
+        .changes
+        .values()
+        .filter(|change| change.change.topic == topic)
+        .cloned()
+        .collect();
+    changes.sort_by(|a, b| {
+        (&a.change.project, a.change.id_number)
+            .cmp(&(&b.change.project, b.change.id_number))
+    });
+    changes
+}
+
+/// Check if every change in a topic is ready to submit
+pub fn topic_ready(changes: &[Change]) -> bool {
+    changes
+        .iter()
+        .all(|change| change.review_state == ReviewState::ReadyToSubmit)
+}
+
+/// Summarize whether a topic is ready to submit
+pub fn topic_summary(topic: &str, changes: &[Change]) -> String {
+    if changes.is_empty() {
+        format!("Could not find open changes in topic: {}", topic)
+    } else if topic_ready(changes) {
+        format!("Topic {} is Ready to Submit.", topic)
+    } else {
+        format!("Topic {} is not ready ({} changes).", topic, changes.len())
+    }
+}
+
+fn topic_status(context: &ServiceContext, topic: &str) -> String {
+    let changes = topic_changes(context, topic);
+    let mut text = topic_summary(topic, &changes);
+    for change in changes {
+        text += &format!(
+            "\n- {} {}: {:?}",
+            change.change.project, change.change.id_number, change.review_state
+        );
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gerrit::data::test_change;
+    use chrono::Utc;
+    use serde_json::json;
+
+    fn context(states: &[(u64, ReviewState)]) -> ServiceContext {
+        let context = ServiceContext::detached();
+        for (id_number, state) in states {
+            let change = Change {
+                change: test_change(*id_number, json!({"topic": "sensors"})),
+                review_state: state.clone(),
+                review_state_updated: Utc::now(),
+            };
+            context
+                .lock()
+                .unwrap()
+                .changes
+                .changes
+                .insert(*id_number, change);
+        }
+        context
+    }
+
+    #[test]
+    fn status_finds_changes_by_number() {
+        let context = context(&[(12, ReviewState::CommunityReview)]);
+        assert_eq!(
+            handle(&context, "!obmc status 12").unwrap(),
+            "Change 12 is Awaiting Community Review."
+        );
+        assert_eq!(
+            handle(&context, "!obmc status 13").unwrap(),
+            "Could not find change: 13"
+        );
+    }
+
+    #[test]
+    fn status_names_the_abandoning_policy() {
+        let context = context(&[]);
+        context.lock().unwrap().abandonments = serde_json::from_value(json!({
+            "abandonments": {"12": {
+                "policy": "stale",
+                "project": "openbmc/bmcweb",
+                "subject": "Add a feature",
+                "abandoned": "2026-01-02T03:04:05Z",
+            }}
+        }))
+        .unwrap();
+        assert_eq!(
+            handle(&context, "!obmc status 12").unwrap(),
+            "Change 12: Abandoned by the stale policy on 2026-01-02."
+        );
+    }
+
+    #[test]
+    fn topic_lists_changes_in_order() {
+        let context = context(&[
+            (12, ReviewState::CommunityReview),
+            (11, ReviewState::ReadyToSubmit),
+        ]);
+        assert_eq!(
+            handle(&context, "!obmc topic sensors").unwrap(),
+            "Topic sensors is not ready (2 changes).\n\
+             - openbmc/bmcweb 11: Ready to Submit\n\
+             - openbmc/bmcweb 12: Awaiting Community Review"
+        );
+        assert_eq!(
+            handle(&context, "!obmc topic fans").unwrap(),
+            "Could not find open changes in topic: fans"
+        );
+    }
+
+    #[test]
+    fn other_text_is_not_a_command() {
+        let context = context(&[]);
+        assert!(handle(&context, "hello").is_none());
+        assert!(handle(&context, "!obmc").unwrap().starts_with("Commands:"));
+    }
+}
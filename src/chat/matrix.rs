@@ -0,0 +1,354 @@
+use crate::chat::backend::{ChatBackend, ChatCommand, ChatMessage};
+use crate::chat::commands::COMMAND_PREFIX;
+use crate::config;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use tokio::sync::Mutex;
+use tokio::time::{self, Duration};
+use tracing::warn;
+
+/// How long a sync request waits on the homeserver for new events.
+const SYNC_TIMEOUT_MS: u64 = 30_000;
+
+/// Posts messages and receives commands through the Matrix client-server API
+pub struct MatrixBackend {
+    base_url: String,
+    access_token: String,
+    user_id: String,
+    client: reqwest::Client,
+    /// Sync token of the last batch of events seen
+    since: Mutex<Option<String>>,
+    /// Direct message rooms created for each user
+    direct_rooms: Mutex<HashMap<String, String>>,
+}
+
+impl MatrixBackend {
+    pub fn new(
+        base_url: &str,
+        access_token: &str,
+        user_id: &str,
+    ) -> MatrixBackend {
+        MatrixBackend {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            access_token: access_token.to_string(),
+            user_id: user_id.to_string(),
+            client: reqwest::Client::new(),
+            since: Mutex::new(None),
+            direct_rooms: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Create a backend using the configured homeserver, access token and
+    /// user
+    pub fn from_config() -> Option<MatrixBackend> {
+        let matrix = &config::get().matrix;
+        Some(Self::new(
+            matrix.homeserver_url.as_deref()?,
+            matrix.access_token.as_deref()?,
+            matrix.user_id.as_deref()?,
+        ))
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/_matrix/client/v3{}", self.base_url, path)
+    }
+
+    async fn request(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> Result<Value, String> {
+        let response = request
+            .bearer_auth(&self.access_token)
+            .send()
+            .await
+            .map_err(|e| format!("Matrix request failed: {}", e))?;
+
+        let status = response.status();
+        let body = response
+            .json::<Value>()
+            .await
+            .map_err(|e| format!("Invalid Matrix response: {}", e))?;
+        if !status.is_success() {
+            return Err(format!("Matrix request failed: {} {}", status, body));
+        }
+        Ok(body)
+    }
+
+    async fn send_text(&self, room: &str, text: &str) -> Result<(), String> {
+        let txn_id = format!(
+            "{}-{:08x}",
+            chrono::Utc::now().timestamp_millis(),
+            rand::random::<u32>()
+        );
+        let url = self.url(&format!(
+            "/rooms/{}/send/m.room.message/{}",
+            urlencoding::encode(room),
+            txn_id
+        ));
+
+        self.request(
+            self.client
+                .put(url)
+                .json(&json!({ "msgtype": "m.notice", "body": text })),
+        )
+        .await
+        .map(|_| ())
+    }
+
+    /// Get, or create, the direct message room for a user
+    async fn direct_room(&self, user: &str) -> Result<String, String> {
+        let mut rooms = self.direct_rooms.lock().await;
+        if let Some(room) = rooms.get(user) {
+            return Ok(room.clone());
+        }
+
+        let response = self
+            .request(self.client.post(self.url("/createRoom")).json(&json!({
+                "is_direct": true,
+                "invite": [user],
+                "preset": "trusted_private_chat",
+            })))
+            .await?;
+        let room = response["room_id"]
+            .as_str()
+            .ok_or("Matrix createRoom returned no room_id")?
+            .to_string();
+        rooms.insert(user.to_string(), room.clone());
+        Ok(room)
+    }
+
+    /// Parse the commands, and join the rooms invited to, in a sync response
+    async fn handle_sync(&self, sync: &Value) -> Vec<ChatCommand> {
+        if let Some(invites) = sync["rooms"]["invite"].as_object() {
+            for room in invites.keys() {
+                let url =
+                    self.url(&format!("/join/{}", urlencoding::encode(room)));
+                if let Err(e) =
+                    self.request(self.client.post(url).json(&json!({}))).await
+                {
+                    warn!("Failed to join Matrix room {}: {}", room, e);
+                }
+            }
+        }
+
+        let mut commands = Vec::new();
+        let Some(rooms) = sync["rooms"]["join"].as_object() else {
+            return commands;
+        };
+        for (room, data) in rooms {
+            let Some(events) = data["timeline"]["events"].as_array() else {
+                continue;
+            };
+            for event in events {
+                let sender = event["sender"].as_str().unwrap_or_default();
+                let body =
+                    event["content"]["body"].as_str().unwrap_or_default();
+                if event["type"] == "m.room.message"
+                    && sender != self.user_id
+                    && body.starts_with(COMMAND_PREFIX)
+                {
+                    commands.push(ChatCommand {
+                        channel: room.clone(),
+                        sender: sender.to_string(),
+                        text: body.to_string(),
+                    });
+                }
+            }
+        }
+        commands
+    }
+}
+
+#[async_trait::async_trait]
+impl ChatBackend for MatrixBackend {
+    fn name(&self) -> &str {
+        "matrix"
+    }
+
+    async fn send_message(
+        &self,
+        channel: &str,
+        message: &ChatMessage,
+    ) -> Result<(), String> {
+        self.send_text(channel, &message.to_markdown()).await
+    }
+
+    async fn send_direct(
+        &self,
+        user: &str,
+        message: &ChatMessage,
+    ) -> Result<(), String> {
+        let room = self.direct_room(user).await?;
+        self.send_text(&room, &message.to_markdown()).await
+    }
+
+    async fn receive_commands(&self) -> Option<Vec<ChatCommand>> {
+        let mut since = self.since.lock().await;
+        let mut url = self.url(&format!("/sync?timeout={}", SYNC_TIMEOUT_MS));
+        if let Some(token) = since.as_ref() {
+            url += &format!("&since={}", urlencoding::encode(token));
+        }
+
+        let sync = match self.request(self.client.get(url)).await {
+            Ok(sync) => sync,
+            Err(e) => {
+                warn!("{}", e);
+                time::sleep(Duration::from_secs(10)).await;
+                return Some(Vec::new());
+            }
+        };
+
+        // Skip the history returned by the first sync, only answering
+        // commands sent while the bot is running.
+        let first_sync = since.is_none();
+        *since = sync["next_batch"].as_str().map(str::to_string);
+        let commands = self.handle_sync(&sync).await;
+        if first_sync {
+            return Some(Vec::new());
+        }
+        Some(commands)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat::stub::Stub;
+    use axum::http::Method;
+
+    fn backend(stub: &Stub) -> MatrixBackend {
+        MatrixBackend::new(&format!("{}/", stub.url), "secret", "@bot:example")
+    }
+
+    #[tokio::test]
+    async fn sends_room_messages() {
+        let stub = Stub::start(vec![(200, json!({"event_id": "$1"}))]).await;
+
+        backend(&stub)
+            .send_message("!room:example", &ChatMessage::new("Hello"))
+            .await
+            .unwrap();
+
+        let requests = stub.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method, Method::PUT);
+        assert!(requests[0].path.starts_with(
+            "/_matrix/client/v3/rooms/%21room%3Aexample/send/m.room.message/"
+        ));
+        assert_eq!(requests[0].authorization.as_deref(), Some("Bearer secret"));
+        assert_eq!(
+            requests[0].body,
+            json!({"msgtype": "m.notice", "body": "**Hello**"})
+        );
+    }
+
+    #[tokio::test]
+    async fn creates_a_direct_room_once() {
+        let stub = Stub::start(vec![
+            (200, json!({"room_id": "!dm:example"})),
+            (200, json!({"event_id": "$1"})),
+            (200, json!({"event_id": "$2"})),
+        ])
+        .await;
+        let backend = backend(&stub);
+
+        let message = ChatMessage::new("Hello");
+        backend
+            .send_direct("@alice:example", &message)
+            .await
+            .unwrap();
+        backend
+            .send_direct("@alice:example", &message)
+            .await
+            .unwrap();
+
+        let requests = stub.requests();
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[0].method, Method::POST);
+        assert_eq!(requests[0].path, "/_matrix/client/v3/createRoom");
+        assert_eq!(requests[0].body["invite"], json!(["@alice:example"]));
+        assert_eq!(requests[0].body["is_direct"], true);
+        for request in &requests[1..] {
+            assert!(
+                request
+                    .path
+                    .starts_with("/_matrix/client/v3/rooms/%21dm%3Aexample/")
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn reports_error_statuses() {
+        let stub = Stub::start(vec![(
+            403,
+            json!({"errcode": "M_FORBIDDEN", "error": "Not in room"}),
+        )])
+        .await;
+
+        let error = backend(&stub)
+            .send_message("!room:example", &ChatMessage::new("Hello"))
+            .await
+            .unwrap_err();
+        assert!(error.contains("403"));
+        assert!(error.contains("M_FORBIDDEN"));
+    }
+
+    #[tokio::test]
+    async fn receives_commands_after_the_first_sync() {
+        let event = |sender: &str, body: &str| {
+            json!({
+                "type": "m.room.message",
+                "sender": sender,
+                "content": {"body": body},
+            })
+        };
+        let stub = Stub::start(vec![
+            (
+                200,
+                json!({
+                    "next_batch": "s1",
+                    "rooms": {"join": {"!room:example": {"timeline": {
+                        "events": [event("@alice:example", "!obmc-help")],
+                    }}}},
+                }),
+            ),
+            (
+                200,
+                json!({
+                    "next_batch": "s2",
+                    "rooms": {
+                        "invite": {"!new:example": {}},
+                        "join": {"!room:example": {"timeline": {"events": [
+                            event("@bot:example", "!obmc-help"),
+                            event("@alice:example", "hello"),
+                            event("@alice:example", "!obmc-report"),
+                        ]}}},
+                    },
+                }),
+            ),
+        ])
+        .await;
+        let backend = backend(&stub);
+
+        assert!(backend.receive_commands().await.unwrap().is_empty());
+        let commands = backend.receive_commands().await.unwrap();
+        assert_eq!(commands.len(), 1);
+        assert_eq!(commands[0].channel, "!room:example");
+        assert_eq!(commands[0].sender, "@alice:example");
+        assert_eq!(commands[0].text, "!obmc-report");
+
+        let paths: Vec<String> = stub
+            .requests()
+            .into_iter()
+            .map(|request| request.path)
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                "/_matrix/client/v3/sync?timeout=30000",
+                "/_matrix/client/v3/sync?timeout=30000&since=s1",
+                "/_matrix/client/v3/join/%21new%3Aexample",
+            ]
+        );
+    }
+}
@@ -0,0 +1,385 @@
+use crate::audit::{AuditAction, AuditEntry};
+use crate::changes::container::Transition;
+use crate::changes::patterns::Patterns;
+use crate::changes::status::ReviewState;
+use crate::chat::backend::{Backends, ChatMessage};
+use crate::chat::reminders::deserialize_optional_channel;
+use crate::config;
+use crate::context::ServiceContext;
+use serde::Deserialize;
+use tokio::sync::broadcast;
+use tracing::{error, warn};
+
+/// Environment variable naming the YAML file with the notification rules.
+const NOTIFICATIONS_CONFIG_ENV: &str = "NOTIFICATIONS_CONFIG";
+
+fn default_backend() -> String {
+    "discord".to_string()
+}
+
+fn default_template() -> String {
+    "{project}: [{subject}]({url}) by {owner} is now {state}".to_string()
+}
+
+/// Post a message when a change enters a review state
+#[derive(Debug, Clone, Deserialize)]
+pub struct NotificationRule {
+    pub name: String,
+    /// Review state entered, e.g. ReadyToSubmit or FailingCI
+    pub to: String,
+    /// Review states the change must come from; any state when empty
+    #[serde(default)]
+    pub from: Vec<String>,
+    /// Project regexes the rule applies to; all projects when empty
+    #[serde(default)]
+    pub projects: Patterns,
+    /// Chat backend to post to: discord, matrix or webhook
+    #[serde(default = "default_backend")]
+    pub backend: String,
+    /// Channel to post to
+    #[serde(default, deserialize_with = "deserialize_optional_channel")]
+    pub channel_id: Option<String>,
+    /// Also send a direct message to the change owner's linked Discord
+    /// account
+    #[serde(default)]
+    pub notify_author: bool,
+    /// Message text.  {project}, {number}, {subject}, {owner}, {mention},
+    /// {state}, {previous} and {url} are replaced by details of the change.
+    #[serde(default = "default_template")]
+    pub template: String,
+}
+
+impl NotificationRule {
+    fn validate(&self) -> Result<(), String> {
+        for state in std::iter::once(&self.to).chain(self.from.iter()) {
+            if !ReviewState::NAMES.contains(&state.as_str()) {
+                return Err(format!(
+                    "Unknown review state {} for notification {}, expected one of {}",
+                    state,
+                    self.name,
+                    ReviewState::NAMES.join(", ")
+                ));
+            }
+        }
+        if self.channel_id.is_none() && !self.notify_author {
+            return Err(format!(
+                "Notification {} needs a channel_id or notify_author",
+                self.name
+            ));
+        }
+        if self.notify_author && self.backend != "discord" {
+            return Err(format!(
+                "Notification {} can only notify_author on discord",
+                self.name
+            ));
+        }
+        Ok(())
+    }
+
+    /// Check if a transition triggers the rule.  Moving between two states
+    /// of the same name, e.g. feedback from one reviewer to another, does
+    /// not count as entering the state.
+    pub fn matches(&self, transition: &Transition) -> bool {
+        let change = &transition.change;
+        if change.review_state.name() != self.to
+            || transition.previous.name() == self.to
+        {
+            return false;
+        }
+        if !self.from.is_empty()
+            && !self
+                .from
+                .iter()
+                .any(|state| state == transition.previous.name())
+        {
+            return false;
+        }
+        self.projects.is_empty()
+            || self.projects.is_match(&change.change.project)
+    }
+
+    /// Build the message for a transition, given the Discord mention of the
+    /// change owner
+    pub fn message(
+        &self,
+        transition: &Transition,
+        mention: &str,
+    ) -> ChatMessage {
+        let owner = &transition.change.change.owner.username;
+        // Discord mentions only work on Discord, and only ping the owner
+        // outside the embed
+        let discord = self.backend == "discord";
+        let ping =
+            discord && mention != owner && self.template.contains("{mention}");
+        ChatMessage {
+            content: ping.then(|| mention.to_string()),
+            description: Some(
+                self.render(transition, if discord { mention } else { owner }),
+            ),
+            ..Default::default()
+        }
+    }
+
+    /// Fill in the template for a transition
+    pub fn render(&self, transition: &Transition, mention: &str) -> String {
+        let change = &transition.change.change;
+        self.template
+            .replace("{project}", &change.project)
+            .replace("{number}", &change.id_number.to_string())
+            .replace("{subject}", &change.subject)
+            .replace("{owner}", &change.owner.username)
+            .replace("{mention}", mention)
+            .replace(
+                "{state}",
+                &format!("{:?}", transition.change.review_state),
+            )
+            .replace("{previous}", &format!("{:?}", transition.previous))
+            .replace(
+                "{url}",
+                &format!(
+                    "https://gerrit.openbmc.org/c/{}/+/{}",
+                    change.project, change.id_number
+                ),
+            )
+    }
+}
+
+/// The configured notification rules
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct NotificationsConfig {
+    pub notifications: Vec<NotificationRule>,
+}
+
+impl NotificationsConfig {
+    /// Load the notification rules from the file named by
+    /// NOTIFICATIONS_CONFIG or the configuration file, or none if neither
+    /// has any.
+    pub fn load() -> Result<NotificationsConfig, String> {
+        let Ok(path) = std::env::var(NOTIFICATIONS_CONFIG_ENV) else {
+            return Ok(NotificationsConfig {
+                notifications: config::get()
+                    .notifications
+                    .clone()
+                    .unwrap_or_default(),
+            });
+        };
+        let text = std::fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+        let config = serde_yaml::from_str::<NotificationsConfig>(&text)
+            .map_err(|e| format!("Failed to parse {}: {}", path, e))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        for rule in &self.notifications {
+            rule.validate()?;
+        }
+        Ok(())
+    }
+}
+
+// Post the notifications of every rule a transition triggers.
+async fn notify(
+    context: &ServiceContext,
+    backends: &Backends,
+    rules: &[NotificationRule],
+    transition: &Transition,
+) {
+    let owner = &transition.change.change.owner.username;
+    let (mention, author_ids) = {
+        let ctx = context.lock().unwrap();
+        (ctx.links.mention(owner), ctx.links.discord_ids(owner))
+    };
+
+    for rule in rules.iter().filter(|rule| rule.matches(transition)) {
+        let Some(backend) = backends.get(&rule.backend) else {
+            warn!(
+                "Notification {} uses backend {}, which is not enabled",
+                rule.name, rule.backend
+            );
+            continue;
+        };
+
+        let message = rule.message(transition, &mention);
+
+        let audit = |target: &str, result: &Result<(), String>| {
+            AuditEntry::new(
+                AuditAction::Notification,
+                &rule.name,
+                &format!("{}:{}", rule.backend, target),
+                vec![transition.change.change.id_number],
+            )
+            .message(&message.to_markdown())
+            .result(result)
+            .record();
+        };
+
+        if let Some(channel) = &rule.channel_id {
+            let result = backend.send_message(channel, &message).await;
+            audit(channel, &result);
+            if let Err(e) = result {
+                error!("Notification {}: {}", rule.name, e);
+            }
+        }
+
+        if rule.notify_author {
+            for id in &author_ids {
+                let result =
+                    backend.send_direct(&id.to_string(), &message).await;
+                audit(&id.to_string(), &result);
+                if let Err(e) = result {
+                    error!("Notification {}: {}", rule.name, e);
+                }
+            }
+        }
+    }
+}
+
+/// Task posting notifications for review state transitions
+pub async fn notifier_task(
+    context: ServiceContext,
+    backends: Backends,
+    rules: Vec<NotificationRule>,
+) {
+    let mut transitions = context.lock().unwrap().transitions.subscribe();
+
+    loop {
+        match transitions.recv().await {
+            Ok(transition) => {
+                notify(&context, &backends, &rules, &transition).await;
+            }
+            Err(broadcast::error::RecvError::Lagged(count)) => {
+                warn!("Notifier dropped {} review state transitions", count);
+            }
+            Err(broadcast::error::RecvError::Closed) => return,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::changes::container::Change;
+    use crate::gerrit::data::test_change;
+
+    fn rule(yaml: &str) -> NotificationRule {
+        let rule: NotificationRule = serde_yaml::from_str(yaml).unwrap();
+        rule.validate().unwrap();
+        rule
+    }
+
+    fn transition(previous: ReviewState, state: ReviewState) -> Transition {
+        let change = test_change(1, serde_json::json!({}));
+        Transition {
+            change: Change {
+                review_state_updated: change.updated,
+                change,
+                review_state: state,
+            },
+            previous,
+        }
+    }
+
+    #[test]
+    fn matches_the_state_entered() {
+        let rule = rule(
+            "
+name: ready
+to: ReadyToSubmit
+channel_id: 1
+",
+        );
+        assert!(rule.matches(&transition(
+            ReviewState::MaintainerReview,
+            ReviewState::ReadyToSubmit
+        )));
+        assert!(!rule.matches(&transition(
+            ReviewState::ReadyToSubmit,
+            ReviewState::MergeConflict
+        )));
+    }
+
+    #[test]
+    fn matches_the_previous_state_and_project() {
+        let rule = rule(
+            "
+name: ci
+to: FailingCI
+from: [MaintainerReview, ReadyToSubmit]
+projects: [openbmc/bmc.*]
+channel_id: 1
+",
+        );
+        assert!(rule.matches(&transition(
+            ReviewState::ReadyToSubmit,
+            ReviewState::FailingCI
+        )));
+        assert!(!rule.matches(&transition(
+            ReviewState::CommunityReview,
+            ReviewState::FailingCI
+        )));
+
+        let rule = NotificationRule {
+            projects: Patterns::try_from(vec!["openbmc/docs".to_string()])
+                .unwrap(),
+            ..rule
+        };
+        assert!(!rule.matches(&transition(
+            ReviewState::ReadyToSubmit,
+            ReviewState::FailingCI
+        )));
+    }
+
+    #[test]
+    fn ignores_changes_within_a_state() {
+        let rule = rule(
+            "
+name: feedback
+to: PendingFeedback
+channel_id: 1
+",
+        );
+        assert!(rule.matches(&transition(
+            ReviewState::CommunityReview,
+            ReviewState::PendingFeedback("bob".to_string())
+        )));
+        assert!(!rule.matches(&transition(
+            ReviewState::PendingFeedback("bob".to_string()),
+            ReviewState::PendingFeedback("carol".to_string())
+        )));
+    }
+
+    #[test]
+    fn mentions_ping_the_owner_on_discord() {
+        let transition =
+            transition(ReviewState::Unknown, ReviewState::FailingCI);
+        let discord = rule(
+            "
+name: ci
+to: FailingCI
+channel_id: 1
+template: '{mention}: CI fails on {number}'
+",
+        );
+        let message = discord.message(&transition, "<@42>");
+        assert_eq!(message.content.as_deref(), Some("<@42>"));
+        assert_eq!(
+            message.description.as_deref(),
+            Some("<@42>: CI fails on 1")
+        );
+
+        // Unlinked owners and other backends get the plain username
+        assert_eq!(discord.message(&transition, "alice").content, None);
+        let matrix = NotificationRule {
+            backend: "matrix".to_string(),
+            ..discord
+        };
+        let message = matrix.message(&transition, "<@42>");
+        assert_eq!(message.content, None);
+        assert_eq!(
+            message.description.as_deref(),
+            Some("alice: CI fails on 1")
+        );
+    }
+}
@@ -0,0 +1,683 @@
+use crate::audit::{AuditAction, AuditEntry};
+use crate::changes::exemptions::Automation;
+use crate::changes::filter::should_include_change;
+use crate::changes::patterns::Patterns;
+use crate::changes::report::{
+    ChangeFilter, TimeInterval, changes_by_owner_time,
+};
+use crate::changes::status::{NextStepOwner, ReviewState};
+use crate::chat::backend::{ChatBackend, ChatMessage};
+use crate::chat::selection::{CommunityReviewChanges, select_changes};
+use crate::config;
+use crate::context::ServiceContext;
+use crate::gerrit::connection::GerritConnection;
+use chrono::{DateTime, Timelike, Utc};
+use chrono_tz::Tz;
+use croner::Cron;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::sync::Arc;
+use tracing::{error, warn};
+
+/// Environment variable naming the YAML file with the reminder jobs.
+const REMINDERS_CONFIG_ENV: &str = "DISCORD_REMINDERS_CONFIG";
+
+/// Schedule used when only the Discord review channel is set.
+const LEGACY_SCHEDULE: &str = "0 */3 * * *";
+
+/// Upper bound on cron occurrences skipped while looking for one outside
+/// of quiet hours.
+const MAX_SKIPPED_OCCURRENCES: usize = 10_000;
+
+/// Longest URL Discord accepts in an embed.
+const MAX_REPORT_URL_LENGTH: usize = 2048;
+
+fn default_total_changes() -> usize {
+    10
+}
+
+fn default_recent_changes() -> usize {
+    8
+}
+
+fn default_advertise_window_hours() -> i64 {
+    7 * 24
+}
+
+fn default_repeat_hours() -> i64 {
+    24
+}
+
+fn default_timezone() -> Tz {
+    Tz::UTC
+}
+
+fn default_backend() -> String {
+    "discord".to_string()
+}
+
+/// Channel ids are numbers on Discord but strings elsewhere, so accept both.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ChannelValue {
+    Number(u64),
+    Text(String),
+}
+
+impl From<ChannelValue> for String {
+    fn from(value: ChannelValue) -> String {
+        match value {
+            ChannelValue::Number(id) => id.to_string(),
+            ChannelValue::Text(id) => id,
+        }
+    }
+}
+
+fn deserialize_channel<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    ChannelValue::deserialize(deserializer).map(Into::into)
+}
+
+pub fn deserialize_optional_channel<'de, D>(
+    deserializer: D,
+) -> Result<Option<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Option::<ChannelValue>::deserialize(deserializer)
+        .map(|value| value.map(Into::into))
+}
+
+/// Local hours, in the job's timezone, during which no reminders are sent.
+/// The range may wrap around midnight, e.g. 22 to 7.
+#[derive(Debug, Clone, Deserialize)]
+pub struct QuietHours {
+    pub start: u32,
+    pub end: u32,
+}
+
+impl QuietHours {
+    fn contains(&self, hour: u32) -> bool {
+        if self.start <= self.end {
+            hour >= self.start && hour < self.end
+        } else {
+            hour >= self.start || hour < self.end
+        }
+    }
+}
+
+/// Send the changes of the matching projects to their own channel
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChannelRoute {
+    #[serde(deserialize_with = "deserialize_channel")]
+    pub channel_id: String,
+    /// Project regexes routed to the channel
+    pub projects: Patterns,
+}
+
+/// A community review reminder posted on a cron schedule.  Changes are sent
+/// to the first route matching their project, or to `channel_id` otherwise.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReminderJob {
+    pub name: String,
+    /// Standard five field cron expression, evaluated in `timezone`
+    pub schedule: String,
+    /// Chat backend to post to: discord, matrix or webhook
+    #[serde(default = "default_backend")]
+    pub backend: String,
+    /// Channel for changes not matched by any route
+    #[serde(default, deserialize_with = "deserialize_optional_channel")]
+    pub channel_id: Option<String>,
+    #[serde(default)]
+    pub routes: Vec<ChannelRoute>,
+    /// Project regexes to draw changes from; all projects when empty
+    #[serde(default)]
+    pub projects: Patterns,
+    /// Total number of changes to advertise
+    #[serde(default = "default_total_changes")]
+    pub total_changes: usize,
+    /// Number of changes to pick from those waiting under 72 hours
+    #[serde(default = "default_recent_changes")]
+    pub recent_changes: usize,
+    /// Every change is advertised at least once in this many hours, as
+    /// long as the schedule runs more often than that
+    #[serde(default = "default_advertise_window_hours")]
+    pub advertise_window_hours: i64,
+    /// Changes advertised within this many hours are not repeated unless
+    /// nothing else is left
+    #[serde(default = "default_repeat_hours")]
+    pub repeat_hours: i64,
+    #[serde(default)]
+    pub quiet_hours: Option<QuietHours>,
+    #[serde(default = "default_timezone")]
+    pub timezone: Tz,
+}
+
+impl ReminderJob {
+    fn cron(&self) -> Result<Cron, String> {
+        Cron::from_str(&self.schedule).map_err(|e| {
+            format!(
+                "Invalid schedule '{}' for reminder {}: {}",
+                self.schedule, self.name, e
+            )
+        })
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        self.cron()?;
+        if self.channel_id.is_none() && self.routes.is_empty() {
+            return Err(format!(
+                "Reminder {} needs a channel_id or routes",
+                self.name
+            ));
+        }
+        if let Some(quiet) = &self.quiet_hours
+            && (quiet.start > 23 || quiet.end > 23)
+        {
+            return Err(format!(
+                "Quiet hours for reminder {} must be between 0 and 23",
+                self.name
+            ));
+        }
+        if self.recent_changes > self.total_changes {
+            return Err(format!(
+                "recent_changes for reminder {} exceeds total_changes",
+                self.name
+            ));
+        }
+        Ok(())
+    }
+
+    /// Get the channel a change in a project is reminded in, if the job
+    /// draws changes from that project.
+    pub fn channel_for(&self, project: &str) -> Option<String> {
+        if !self.projects.is_empty() && !self.projects.is_match(project) {
+            return None;
+        }
+
+        self.routes
+            .iter()
+            .find(|route| route.projects.is_match(project))
+            .map_or_else(
+                || self.channel_id.clone(),
+                |route| Some(route.channel_id.clone()),
+            )
+    }
+
+    /// Check if a channel only gets the changes of some projects, rather
+    /// than everything not routed elsewhere
+    pub fn is_scoped(&self, channel: &str) -> bool {
+        !self.projects.is_empty()
+            || self.routes.iter().any(|route| route.channel_id == channel)
+    }
+
+    /// Check if a time falls within the job's quiet hours
+    pub fn is_quiet(&self, time: DateTime<Utc>) -> bool {
+        self.quiet_hours.as_ref().is_some_and(|quiet| {
+            quiet.contains(time.with_timezone(&self.timezone).hour())
+        })
+    }
+
+    /// Find the next time after `after` the job should run, skipping any
+    /// occurrences within quiet hours.
+    pub fn next_run(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let cron = self.cron().ok()?;
+        cron.iter_after(after.with_timezone(&self.timezone))
+            .take(MAX_SKIPPED_OCCURRENCES)
+            .map(|time| time.with_timezone(&Utc))
+            .find(|time| !self.is_quiet(*time))
+    }
+}
+
+/// The configured reminder jobs
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct RemindersConfig {
+    pub jobs: Vec<ReminderJob>,
+}
+
+impl RemindersConfig {
+    /// Load the reminder jobs from the file named by
+    /// DISCORD_REMINDERS_CONFIG, or the configuration file.  Without either,
+    /// a single job posting to the Discord review channel every three hours
+    /// is used if that is set.
+    pub fn load() -> Result<RemindersConfig, String> {
+        if let Ok(path) = std::env::var(REMINDERS_CONFIG_ENV) {
+            return Self::from_file(&path);
+        }
+        match &config::get().reminders {
+            Some(jobs) => Ok(RemindersConfig { jobs: jobs.clone() }),
+            None => Ok(Self::legacy()),
+        }
+    }
+
+    /// Load the reminder jobs from a YAML file
+    pub fn from_file(path: &str) -> Result<RemindersConfig, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+        let config = serde_yaml::from_str::<RemindersConfig>(&text)
+            .map_err(|e| format!("Failed to parse {}: {}", path, e))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        for job in &self.jobs {
+            job.validate()?;
+        }
+        Ok(())
+    }
+
+    fn legacy() -> RemindersConfig {
+        let Some(channel_id) = &config::get().discord.review_channel_id else {
+            return RemindersConfig::default();
+        };
+
+        RemindersConfig {
+            jobs: vec![ReminderJob {
+                name: "community-review".to_string(),
+                schedule: LEGACY_SCHEDULE.to_string(),
+                backend: default_backend(),
+                channel_id: Some(channel_id.clone()),
+                routes: Vec::new(),
+                projects: Patterns::default(),
+                total_changes: default_total_changes(),
+                recent_changes: default_recent_changes(),
+                advertise_window_hours: default_advertise_window_hours(),
+                repeat_hours: default_repeat_hours(),
+                quiet_hours: None,
+                timezone: default_timezone(),
+            }],
+        }
+    }
+}
+
+/// Get the changes that need community review in the job's projects, grouped
+/// by the channel they are routed to, with their age as of `now`.
+pub fn get_community_review_changes(
+    context: &ServiceContext,
+    job: &ReminderJob,
+    now: DateTime<Utc>,
+) -> BTreeMap<String, CommunityReviewChanges> {
+    // Use existing changes_by_owner_time function to get changes.  Changes
+    // exempt from reports may still be advertised.
+    let changes_by_time = changes_by_owner_time(
+        context,
+        &ChangeFilter {
+            include_exempt: true,
+            ..Default::default()
+        },
+        now,
+    );
+
+    // Get the lock on the context to access changes
+    let ctx = context.lock().unwrap();
+
+    let mut channels: BTreeMap<String, CommunityReviewChanges> =
+        BTreeMap::new();
+
+    // Process all time intervals in a single iteration
+    for time_interval in [
+        TimeInterval::Under24Hours,
+        TimeInterval::Under72Hours,
+        TimeInterval::Under2Weeks,
+        TimeInterval::Under8Weeks,
+        TimeInterval::Over8Weeks,
+    ] {
+        let change_ids = changes_by_time
+            .get_changes(time_interval, NextStepOwner::Community);
+
+        for id in change_ids {
+            let Some(change) = ctx.changes.get(id) else {
+                continue;
+            };
+
+            // Double-check that the change is actually in CommunityReview
+            // state and apply the community filter
+            if !matches!(change.review_state, ReviewState::CommunityReview)
+                || !should_include_change(&change.change)
+                || ctx
+                    .exemptions
+                    .is_exempt(&change.change, Automation::Reminders)
+            {
+                continue;
+            }
+
+            let Some(channel) = job.channel_for(&change.change.project) else {
+                continue;
+            };
+
+            // Skip changes someone has already claimed for review
+            if ctx.claims.get(change.change.id_number).is_some() {
+                continue;
+            }
+
+            // Categorize changes based on time interval
+            let changes = channels.entry(channel).or_default();
+            match time_interval {
+                TimeInterval::Under24Hours | TimeInterval::Under72Hours => {
+                    changes.recent.push(change);
+                }
+                _ => {
+                    changes.older.push(change);
+                }
+            }
+        }
+    }
+
+    channels
+}
+
+// Look up whether owners not recently checked have any merged changes.
+async fn update_first_time_contributors<G>(
+    context: &ServiceContext,
+    gerrit: &G,
+    owners: &[String],
+) where
+    G: GerritConnection + Sync,
+{
+    let now = Utc::now();
+    let unchecked = context
+        .lock()
+        .unwrap()
+        .reminder_history
+        .unchecked_owners(owners, now);
+    if unchecked.is_empty() {
+        return;
+    }
+
+    for owner in unchecked {
+        // Leave the owner unchecked if Gerrit rejected the query, e.g. for
+        // a deleted account, so it is not taken to mean no merged changes.
+        // Quoting keeps names with spaces in one term.
+        let Ok(merged) = gerrit
+            .query_changes(
+                &format!(
+                    "owner:\"{}\" status:merged limit:1",
+                    owner.replace('"', "")
+                ),
+                &[],
+            )
+            .await
+        else {
+            continue;
+        };
+        context.lock().unwrap().reminder_history.set_contributor(
+            &owner,
+            !merged.is_empty(),
+            now,
+        );
+    }
+}
+
+// Link to the report on a channel's projects.  Channels taking every
+// project, and those with too many projects for a link, get the overall
+// report instead.
+fn report_url(hostname: &str, scoped: bool, projects: &[String]) -> String {
+    let overall = format!("https://{}/bot/report", hostname);
+    if !scoped {
+        return overall;
+    }
+
+    let url = format!(
+        "{}/{}",
+        overall,
+        projects
+            .iter()
+            .map(|project| urlencoding::encode(project))
+            .collect::<Vec<_>>()
+            .join("%20")
+    );
+    if url.len() > MAX_REPORT_URL_LENGTH {
+        overall
+    } else {
+        url
+    }
+}
+
+// Send community review reminders to each channel of the job
+async fn send_community_review_reminder(
+    context: &ServiceContext,
+    backend: &dyn ChatBackend,
+    job: &ReminderJob,
+) {
+    let channels = get_community_review_changes(context, job, Utc::now());
+
+    for (channel, changes) in channels {
+        let total_count = changes.len();
+        let projects = changes.projects();
+        update_first_time_contributors(
+            context,
+            &context.get_gerrit(),
+            &changes.owners(),
+        )
+        .await;
+
+        let now = Utc::now();
+        let changes = {
+            let ctx = context.lock().unwrap();
+            let history = &ctx.reminder_history;
+            select_changes(
+                changes,
+                job,
+                history,
+                &history.first_time_contributors(),
+                now,
+            )
+        };
+
+        if changes.is_empty() {
+            continue;
+        }
+
+        let mut message = ChatMessage::new("Review Reminder");
+        message.description =
+            Some("Want to help with reviews? Here are a few...".to_string());
+        for change in &changes {
+            message = message.change(change);
+        }
+        message.claimable = changes
+            .iter()
+            .map(|change| change.change.id_number)
+            .collect();
+
+        // Add footer with count of additional changes
+        let additional_count = total_count.saturating_sub(changes.len());
+        if additional_count > 0 {
+            message.footer =
+                Some(format!("And there are {} more...", additional_count));
+        }
+
+        // Add webserver link to the channel's projects if the hostname is
+        // set
+        if let Some(hostname) = &config::get().webserver.hostname {
+            message.url =
+                Some(report_url(hostname, job.is_scoped(&channel), &projects));
+        }
+
+        let result = backend.send_message(&channel, &message).await;
+        AuditEntry::new(
+            AuditAction::Reminder,
+            &job.name,
+            &format!("{}:{}", backend.name(), channel),
+            message.claimable.clone(),
+        )
+        .message(&message.to_markdown())
+        .result(&result)
+        .record();
+        if let Err(e) = result {
+            error!("Reminder {}: {}", job.name, e);
+            continue;
+        }
+
+        context
+            .lock()
+            .unwrap()
+            .reminder_history
+            .record(&changes, now);
+    }
+}
+
+/// Periodic task for sending community review reminders on a job's schedule
+pub async fn community_review_reminder_task(
+    context: ServiceContext,
+    backend: Arc<dyn ChatBackend>,
+    job: ReminderJob,
+) {
+    loop {
+        let now = Utc::now();
+        let Some(next_run) = job.next_run(now) else {
+            warn!("Reminder {} has no upcoming runs, stopping.", job.name);
+            return;
+        };
+
+        // Sleep until the next scheduled run
+        let duration = next_run.signed_duration_since(now);
+        tokio::time::sleep(tokio::time::Duration::from_secs(
+            duration.num_seconds() as u64 + 1,
+        ))
+        .await;
+
+        // Send reminder
+        send_community_review_reminder(&context, backend.as_ref(), &job).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gerrit::stub::StubGerrit;
+    use chrono::TimeZone;
+
+    fn job(yaml: &str) -> ReminderJob {
+        let job: ReminderJob = serde_yaml::from_str(yaml).unwrap();
+        job.validate().unwrap();
+        job
+    }
+
+    fn utc(day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 1, day, hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn next_run_follows_the_schedule() {
+        let job = job("
+name: test
+schedule: '0 */3 * * *'
+channel_id: 1
+");
+        assert_eq!(job.next_run(utc(6, 1, 30)), Some(utc(6, 3, 0)));
+        assert_eq!(job.next_run(utc(6, 3, 0)), Some(utc(6, 6, 0)));
+        assert_eq!(job.next_run(utc(6, 22, 0)), Some(utc(7, 0, 0)));
+    }
+
+    #[test]
+    fn next_run_uses_the_timezone() {
+        // 2025-01-06 is a Monday; Chicago is UTC-6 in January
+        let job = job("
+name: test
+schedule: '30 9 * * MON-FRI'
+channel_id: 1
+timezone: America/Chicago
+");
+        assert_eq!(job.next_run(utc(6, 12, 0)), Some(utc(6, 15, 30)));
+        assert_eq!(job.next_run(utc(10, 16, 0)), Some(utc(13, 15, 30)));
+    }
+
+    #[test]
+    fn quiet_hours_wrap_around_midnight() {
+        let quiet = QuietHours { start: 22, end: 7 };
+        assert!(quiet.contains(22));
+        assert!(quiet.contains(0));
+        assert!(quiet.contains(6));
+        assert!(!quiet.contains(7));
+        assert!(!quiet.contains(21));
+
+        let quiet = QuietHours { start: 12, end: 14 };
+        assert!(quiet.contains(13));
+        assert!(!quiet.contains(14));
+        assert!(!quiet.contains(0));
+    }
+
+    #[test]
+    fn next_run_skips_quiet_hours() {
+        let job = job("
+name: test
+schedule: '0 */3 * * *'
+channel_id: 1
+quiet_hours:
+  start: 22
+  end: 7
+timezone: Europe/Berlin
+");
+        // 21:00 UTC is 22:00 in Berlin, so the next run is 09:00 there
+        assert!(job.is_quiet(utc(6, 21, 0)));
+        assert_eq!(job.next_run(utc(6, 20, 0)), Some(utc(7, 8, 0)));
+    }
+
+    #[test]
+    fn invalid_quiet_hours_are_rejected() {
+        let job: ReminderJob = serde_yaml::from_str(
+            "
+name: test
+schedule: '0 * * * *'
+channel_id: 1
+quiet_hours:
+  start: 22
+  end: 24
+",
+        )
+        .unwrap();
+        assert!(job.validate().is_err());
+    }
+
+    #[test]
+    fn report_url_falls_back_to_the_overall_report() {
+        let projects = vec![
+            "openbmc/bmcweb".to_string(),
+            "openbmc/dbus-sensors".to_string(),
+        ];
+        assert_eq!(
+            report_url("bot.example.com", true, &projects),
+            "https://bot.example.com/bot/report/\
+             openbmc%2Fbmcweb%20openbmc%2Fdbus-sensors"
+        );
+        assert_eq!(
+            report_url("bot.example.com", false, &projects),
+            "https://bot.example.com/bot/report"
+        );
+
+        let many: Vec<String> =
+            (0..200).map(|i| format!("openbmc/project-{}", i)).collect();
+        assert_eq!(
+            report_url("bot.example.com", true, &many),
+            "https://bot.example.com/bot/report"
+        );
+    }
+
+    #[tokio::test]
+    async fn rejected_contributor_queries_leave_owners_unchecked() {
+        let context = ServiceContext::detached();
+        let gerrit = StubGerrit::new(Err(
+            "400 Bad Request: Account 'John Doe' not found".to_string(),
+        ));
+        let owners = vec!["John Doe".to_string()];
+
+        update_first_time_contributors(&context, &gerrit, &owners).await;
+
+        assert_eq!(
+            gerrit.queries(),
+            vec!["owner:\"John Doe\" status:merged limit:1"]
+        );
+        assert_eq!(
+            context
+                .lock()
+                .unwrap()
+                .reminder_history
+                .unchecked_owners(&owners, Utc::now()),
+            owners
+        );
+    }
+}
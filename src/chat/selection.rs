@@ -0,0 +1,447 @@
+use crate::changes::container::Change;
+use crate::chat::reminders::ReminderJob;
+use crate::state;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+const HISTORY_FILE: &str = "reminder_history.json";
+
+/// How long advertisements are remembered.
+const HISTORY_DAYS: i64 = 30;
+
+/// How long to trust that an owner has no merged changes before asking
+/// Gerrit again.
+const FIRST_TIME_RECHECK_HOURS: i64 = 24;
+
+/// How much more likely a first-time contributor's change is to be picked.
+const FIRST_TIME_WEIGHT: f64 = 3.0;
+
+/// Changes awaiting community review in one channel, split into those under
+/// 72 hours old and older ones.
+#[derive(Debug, Default)]
+pub struct CommunityReviewChanges {
+    pub recent: Vec<Change>,
+    pub older: Vec<Change>,
+}
+
+impl CommunityReviewChanges {
+    pub fn len(&self) -> usize {
+        self.recent.len() + self.older.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get the sorted, unique projects of the changes
+    pub fn projects(&self) -> Vec<String> {
+        let mut projects: Vec<String> = self
+            .iter()
+            .map(|change| change.change.project.clone())
+            .collect();
+        projects.sort();
+        projects.dedup();
+        projects
+    }
+
+    /// Get the sorted, unique owners of the changes
+    pub fn owners(&self) -> Vec<String> {
+        let mut owners: Vec<String> = self
+            .iter()
+            .map(|change| change.change.owner.username.clone())
+            .collect();
+        owners.sort();
+        owners.dedup();
+        owners
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &Change> {
+        self.recent.iter().chain(self.older.iter())
+    }
+}
+
+/// Whether an owner had a merged change when last checked
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ContributorCheck {
+    merged: bool,
+    checked: DateTime<Utc>,
+}
+
+/// When changes were last advertised in a reminder, and which owners are
+/// first-time contributors, persisted in the state directory.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReminderHistory {
+    advertised: HashMap<u64, DateTime<Utc>>,
+    contributors: HashMap<String, ContributorCheck>,
+}
+
+impl ReminderHistory {
+    pub fn load() -> ReminderHistory {
+        state::load(HISTORY_FILE)
+    }
+
+    pub fn save(&self) {
+        state::save(HISTORY_FILE, self);
+    }
+
+    /// Get when a change was last advertised
+    pub fn last_advertised(&self, id_number: u64) -> Option<DateTime<Utc>> {
+        self.advertised.get(&id_number).copied()
+    }
+
+    /// Record that changes were advertised
+    pub fn record(&mut self, changes: &[Change], now: DateTime<Utc>) {
+        self.mark(changes, now);
+        self.save();
+    }
+
+    /// Note that changes were advertised without saving the history, e.g.
+    /// when simulating reminders
+    pub fn mark(&mut self, changes: &[Change], now: DateTime<Utc>) {
+        self.advertised
+            .retain(|_, time| now - *time < Duration::days(HISTORY_DAYS));
+        for change in changes {
+            self.advertised.insert(change.change.id_number, now);
+        }
+    }
+
+    /// Get the owners whose first-time status is unknown or stale
+    pub fn unchecked_owners(
+        &self,
+        owners: &[String],
+        now: DateTime<Utc>,
+    ) -> Vec<String> {
+        owners
+            .iter()
+            .filter(|owner| match self.contributors.get(*owner) {
+                Some(check) => {
+                    !check.merged
+                        && now - check.checked
+                            >= Duration::hours(FIRST_TIME_RECHECK_HOURS)
+                }
+                None => true,
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Record whether an owner has a merged change
+    pub fn set_contributor(
+        &mut self,
+        owner: &str,
+        merged: bool,
+        now: DateTime<Utc>,
+    ) {
+        self.contributors.insert(
+            owner.to_string(),
+            ContributorCheck {
+                merged,
+                checked: now,
+            },
+        );
+        self.save();
+    }
+
+    /// Get the owners known to have no merged changes
+    pub fn first_time_contributors(&self) -> HashSet<String> {
+        self.contributors
+            .iter()
+            .filter(|(_, check)| !check.merged)
+            .map(|(owner, _)| owner.clone())
+            .collect()
+    }
+
+    /// When a change last got attention: its last advertisement, or when it
+    /// entered community review if that is later.
+    fn last_seen(&self, change: &Change) -> DateTime<Utc> {
+        match self.last_advertised(change.change.id_number) {
+            Some(advertised) => advertised.max(change.review_state_updated),
+            None => change.review_state_updated,
+        }
+    }
+}
+
+/// Select the changes to advertise for a job.
+///
+/// Changes not advertised within the job's window are picked first, most
+/// neglected first.  Remaining slots are filled by a weighted random pick,
+/// favoring changes that have waited longer and those of first-time
+/// contributors, with up to `recent_changes` drawn from changes under 72
+/// hours old.  Changes advertised within `repeat_hours` are only used when
+/// nothing else is left.
+pub fn select_changes(
+    changes: CommunityReviewChanges,
+    job: &ReminderJob,
+    history: &ReminderHistory,
+    first_time: &HashSet<String>,
+    now: DateTime<Utc>,
+) -> Vec<Change> {
+    let window = Duration::hours(job.advertise_window_hours);
+    let repeat = Duration::hours(job.repeat_hours);
+
+    let recently_advertised = |change: &Change| {
+        history
+            .last_advertised(change.change.id_number)
+            .is_some_and(|time| now - time < repeat)
+    };
+    let weight = |change: &Change| {
+        let hours = (now - change.review_state_updated).num_hours().max(1);
+        let mut weight = hours as f64;
+        if first_time.contains(&change.change.owner.username) {
+            weight *= FIRST_TIME_WEIGHT;
+        }
+        weight
+    };
+
+    let recent_ids: HashSet<u64> =
+        changes.recent.iter().map(|c| c.change.id_number).collect();
+    let is_recent =
+        |change: &Change| recent_ids.contains(&change.change.id_number);
+
+    let mut candidates: Vec<Change> =
+        changes.recent.into_iter().chain(changes.older).collect();
+    let mut selected = Vec::new();
+
+    // Changes overdue for advertisement, most neglected first
+    let mut overdue: Vec<Change> = candidates
+        .iter()
+        .filter(|change| now - history.last_seen(change) >= window)
+        .cloned()
+        .collect();
+    overdue.sort_by_key(|change| history.last_seen(change));
+    overdue.truncate(job.total_changes);
+    take(&mut candidates, &overdue);
+    selected.extend(overdue);
+
+    // Weighted pick of recent changes, then of everything else
+    let fresh: Vec<Change> = candidates
+        .iter()
+        .filter(|change| !recently_advertised(change))
+        .cloned()
+        .collect();
+    let recent_selected = selected.iter().filter(|c| is_recent(c)).count();
+    let recent_count = job
+        .recent_changes
+        .saturating_sub(recent_selected)
+        .min(job.total_changes.saturating_sub(selected.len()));
+    let recent: Vec<Change> =
+        fresh.iter().filter(|c| is_recent(c)).cloned().collect();
+    let picked = weighted_sample(recent, recent_count, &weight);
+    take(&mut candidates, &picked);
+    selected.extend(picked);
+
+    let fresh: Vec<Change> = candidates
+        .iter()
+        .filter(|change| !recently_advertised(change))
+        .cloned()
+        .collect();
+    let picked = weighted_sample(
+        fresh,
+        job.total_changes.saturating_sub(selected.len()),
+        &weight,
+    );
+    take(&mut candidates, &picked);
+    selected.extend(picked);
+
+    // Fall back to recently advertised changes, least recent first
+    candidates.sort_by_key(|change| history.last_seen(change));
+    candidates.truncate(job.total_changes.saturating_sub(selected.len()));
+    selected.extend(candidates);
+
+    selected
+}
+
+/// Remove the picked changes from the candidates
+fn take(candidates: &mut Vec<Change>, picked: &[Change]) {
+    let ids: HashSet<u64> = picked.iter().map(|c| c.change.id_number).collect();
+    candidates.retain(|change| !ids.contains(&change.change.id_number));
+}
+
+/// Pick `count` changes at random without replacement, each with a
+/// probability proportional to its weight.
+fn weighted_sample<F>(
+    changes: Vec<Change>,
+    count: usize,
+    weight: &F,
+) -> Vec<Change>
+where
+    F: Fn(&Change) -> f64,
+{
+    // Efraimidis-Spirakis: keep the largest u^(1/w) keys
+    let mut keyed: Vec<(f64, Change)> = changes
+        .into_iter()
+        .map(|change| {
+            let key = rand::random::<f64>().powf(1.0 / weight(&change));
+            (key, change)
+        })
+        .collect();
+    keyed.sort_by(|a, b| b.0.total_cmp(&a.0));
+    keyed
+        .into_iter()
+        .take(count)
+        .map(|(_, change)| change)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::changes::status::ReviewState;
+    use crate::gerrit::data::test_change;
+
+    fn now() -> DateTime<Utc> {
+        "2025-01-10T12:00:00Z".parse().unwrap()
+    }
+
+    fn job(total: usize, recent: usize) -> ReminderJob {
+        serde_yaml::from_str(&format!(
+            "
+name: test
+schedule: '0 * * * *'
+channel_id: 1
+total_changes: {}
+recent_changes: {}
+advertise_window_hours: 168
+repeat_hours: 24
+",
+            total, recent
+        ))
+        .unwrap()
+    }
+
+    // A change by `owner` that entered community review `hours` ago
+    fn change(id_number: u64, owner: &str, hours: i64) -> Change {
+        Change {
+            change: test_change(
+                id_number,
+                serde_json::json!({"owner": {"username": owner}}),
+            ),
+            review_state: ReviewState::CommunityReview,
+            review_state_updated: now() - Duration::hours(hours),
+        }
+    }
+
+    fn ids(changes: &[Change]) -> Vec<u64> {
+        changes
+            .iter()
+            .map(|change| change.change.id_number)
+            .collect()
+    }
+
+    // How often each change is picked as the only one of a reminder
+    fn pick_counts(
+        changes: &[Change],
+        first_time: &HashSet<String>,
+    ) -> HashMap<u64, usize> {
+        let mut counts = HashMap::new();
+        for _ in 0..4000 {
+            let selected = select_changes(
+                CommunityReviewChanges {
+                    recent: Vec::new(),
+                    older: changes.to_vec(),
+                },
+                &job(1, 0),
+                &ReminderHistory::default(),
+                first_time,
+                now(),
+            );
+            *counts.entry(selected[0].change.id_number).or_default() += 1;
+        }
+        counts
+    }
+
+    #[test]
+    fn overdue_changes_come_first() {
+        let changes = CommunityReviewChanges {
+            recent: vec![change(1, "alice", 10)],
+            older: vec![
+                change(2, "bob", 200),
+                change(3, "carol", 300),
+                change(4, "dave", 100),
+            ],
+        };
+        let selected = select_changes(
+            changes,
+            &job(2, 0),
+            &ReminderHistory::default(),
+            &HashSet::new(),
+            now(),
+        );
+        assert_eq!(ids(&selected), vec![3, 2]);
+    }
+
+    #[test]
+    fn recently_advertised_changes_are_a_last_resort() {
+        let mut history = ReminderHistory::default();
+        history.mark(&[change(1, "alice", 100)], now() - Duration::hours(1));
+
+        let changes = CommunityReviewChanges {
+            recent: Vec::new(),
+            older: vec![change(1, "alice", 100), change(2, "bob", 50)],
+        };
+        let selected = select_changes(
+            changes,
+            &job(1, 0),
+            &history,
+            &HashSet::new(),
+            now(),
+        );
+        assert_eq!(ids(&selected), vec![2]);
+
+        let changes = CommunityReviewChanges {
+            recent: Vec::new(),
+            older: vec![change(1, "alice", 100), change(2, "bob", 50)],
+        };
+        let selected = select_changes(
+            changes,
+            &job(2, 0),
+            &history,
+            &HashSet::new(),
+            now(),
+        );
+        assert_eq!(ids(&selected), vec![2, 1]);
+    }
+
+    #[test]
+    fn recent_changes_fill_their_share() {
+        let changes = CommunityReviewChanges {
+            recent: vec![change(1, "alice", 10), change(2, "bob", 20)],
+            older: vec![change(3, "carol", 100), change(4, "dave", 110)],
+        };
+        let selected = select_changes(
+            changes,
+            &job(3, 2),
+            &ReminderHistory::default(),
+            &HashSet::new(),
+            now(),
+        );
+        assert_eq!(selected.len(), 3);
+        assert!(ids(&selected).contains(&1));
+        assert!(ids(&selected).contains(&2));
+    }
+
+    #[test]
+    fn longer_waits_weigh_more() {
+        // Weights 90 and 10: the older change is picked about 9 times in 10
+        let counts = pick_counts(
+            &[change(1, "alice", 90), change(2, "bob", 10)],
+            &HashSet::new(),
+        );
+        let share = counts[&1] as f64 / 4000.0;
+        assert!((0.85..0.95).contains(&share), "share {}", share);
+    }
+
+    #[test]
+    fn first_time_contributors_weigh_more() {
+        // Equal waits, so the first-time contributor is picked three times
+        // as often
+        let first_time = HashSet::from(["bob".to_string()]);
+        let counts = pick_counts(
+            &[change(1, "alice", 50), change(2, "bob", 50)],
+            &first_time,
+        );
+        let share = counts[&2] as f64 / 4000.0;
+        assert!((0.7..0.8).contains(&share), "share {}", share);
+    }
+}
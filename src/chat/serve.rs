@@ -0,0 +1,190 @@
+use crate::audit::{AuditAction, AuditEntry};
+use crate::changes::container::Transition;
+use crate::changes::status::ReviewState;
+use crate::chat::backend::{Backends, ChatBackend, ChatMessage};
+use crate::chat::commands;
+use crate::chat::matrix::MatrixBackend;
+use crate::chat::notifier::{NotificationsConfig, notifier_task};
+use crate::chat::reminders::{RemindersConfig, community_review_reminder_task};
+use crate::chat::webhook::WebhookBackend;
+use crate::context::ServiceContext;
+use crate::discord::backend::DiscordBackend;
+use chrono::Utc;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use tracing::{error, info, warn};
+
+/// Create the chat backends configured in the environment
+pub fn backends(enable_discord: bool) -> Backends {
+    let mut backends: Backends = Backends::new();
+    let mut add = |backend: Arc<dyn ChatBackend>| {
+        info!("Chat backend {} enabled", backend.name());
+        backends.insert(backend.name().to_string(), backend);
+    };
+
+    if enable_discord && let Some(backend) = DiscordBackend::from_config() {
+        add(Arc::new(backend));
+    }
+    if let Some(backend) = MatrixBackend::from_config() {
+        add(Arc::new(backend));
+    }
+    if let Some(backend) = WebhookBackend::from_config() {
+        add(Arc::new(backend));
+    }
+    backends
+}
+
+// Send a DM to every user subscribed to the project of a change that just
+// entered a state they care about.  Subscriptions are made through Discord
+// commands, so they are delivered through Discord.
+async fn notify_subscribers(
+    context: &ServiceContext,
+    backend: &dyn ChatBackend,
+    transition: &Transition,
+) {
+    let change = &transition.change;
+    let title = match change.review_state {
+        ReviewState::CommunityReview => "Change Awaiting Community Review",
+        ReviewState::ReadyToSubmit => "Change Ready to Submit",
+        _ => return,
+    };
+
+    let subscribers = context
+        .lock()
+        .unwrap()
+        .subscriptions
+        .subscribers(&change.change.project);
+
+    for discord_id in subscribers {
+        let key =
+            format!("{}:{:?}", change.change.id_number, change.review_state);
+        let now = Utc::now();
+        if !context
+            .lock()
+            .unwrap()
+            .subscriptions
+            .may_notify(discord_id, &key, now)
+        {
+            continue;
+        }
+
+        let message = ChatMessage::new(title).change(change);
+        let result =
+            backend.send_direct(&discord_id.to_string(), &message).await;
+        AuditEntry::new(
+            AuditAction::Subscription,
+            &change.change.project,
+            &format!("{}:{}", backend.name(), discord_id),
+            vec![change.change.id_number],
+        )
+        .message(&message.to_markdown())
+        .result(&result)
+        .record();
+        // A failed send, e.g. to a user not accepting DMs, does not count
+        // towards their limit
+        match result {
+            Ok(()) => context
+                .lock()
+                .unwrap()
+                .subscriptions
+                .record_notification(discord_id, &key, now),
+            Err(e) => error!("{}", e),
+        }
+    }
+}
+
+// Task listening for review state transitions to notify subscribers.
+async fn subscription_task(
+    context: ServiceContext,
+    backend: Arc<dyn ChatBackend>,
+) {
+    let mut transitions = context.lock().unwrap().transitions.subscribe();
+
+    loop {
+        match transitions.recv().await {
+            Ok(transition) => {
+                notify_subscribers(&context, backend.as_ref(), &transition)
+                    .await;
+            }
+            Err(broadcast::error::RecvError::Lagged(count)) => {
+                warn!("Dropped {} review state transitions", count);
+            }
+            Err(broadcast::error::RecvError::Closed) => return,
+        }
+    }
+}
+
+// Task answering text commands received by a backend.
+async fn command_task(context: ServiceContext, backend: Arc<dyn ChatBackend>) {
+    while let Some(received) = backend.receive_commands().await {
+        for command in received {
+            let Some(reply) = commands::handle(&context, &command.text) else {
+                continue;
+            };
+            if let Err(e) = backend.reply(&command, &reply).await {
+                error!("Failed to reply to {}: {}", command.sender, e);
+            }
+        }
+    }
+}
+
+pub async fn serve(context: ServiceContext, backends: Backends) {
+    let mut handles = Vec::new();
+
+    // Start a periodic task for each community review reminder
+    let jobs = match RemindersConfig::load() {
+        Ok(config) => config.jobs,
+        Err(e) => {
+            error!("Reminders disabled: {}", e);
+            Vec::new()
+        }
+    };
+    if jobs.is_empty() {
+        warn!("No reminders configured, community review reminders disabled.");
+    }
+    for job in jobs {
+        let Some(backend) = backends.get(&job.backend) else {
+            error!(
+                "Reminder {} uses backend {}, which is not enabled",
+                job.name, job.backend
+            );
+            continue;
+        };
+        handles.push(tokio::spawn(community_review_reminder_task(
+            context.clone(),
+            backend.clone(),
+            job,
+        )));
+    }
+
+    // Start the task for review state notifications
+    match NotificationsConfig::load() {
+        Ok(config) if !config.notifications.is_empty() => {
+            handles.push(tokio::spawn(notifier_task(
+                context.clone(),
+                backends.clone(),
+                config.notifications,
+            )));
+        }
+        Ok(_) => {}
+        Err(e) => error!("Notifications disabled: {}", e),
+    }
+
+    // Start the task for subscription notifications
+    if let Some(backend) = backends.get("discord") {
+        handles.push(tokio::spawn(subscription_task(
+            context.clone(),
+            backend.clone(),
+        )));
+    }
+
+    // Answer commands on backends without native commands
+    for backend in backends.values() {
+        handles
+            .push(tokio::spawn(command_task(context.clone(), backend.clone())));
+    }
+
+    for handle in handles {
+        handle.await.unwrap();
+    }
+}
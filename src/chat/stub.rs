@@ -0,0 +1,86 @@
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::{HeaderMap, Method, StatusCode, Uri};
+use axum::response::{IntoResponse, Response};
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+/// A request received by the stand-in
+#[derive(Debug, Clone)]
+pub struct StubRequest {
+    pub method: Method,
+    /// Path and query
+    pub path: String,
+    pub authorization: Option<String>,
+    /// JSON body, or null if there was none
+    pub body: Value,
+}
+
+#[derive(Default)]
+struct StubState {
+    requests: Vec<StubRequest>,
+    responses: VecDeque<(StatusCode, Value)>,
+}
+
+/// A local HTTP server standing in for a chat service.  It records every
+/// request and answers with the queued responses in order, then with 200
+/// and an empty object.
+pub struct Stub {
+    pub url: String,
+    state: Arc<Mutex<StubState>>,
+}
+
+impl Stub {
+    pub async fn start(responses: Vec<(u16, Value)>) -> Stub {
+        let state = Arc::new(Mutex::new(StubState {
+            requests: Vec::new(),
+            responses: responses
+                .into_iter()
+                .map(|(status, body)| {
+                    (StatusCode::from_u16(status).unwrap(), body)
+                })
+                .collect(),
+        }));
+        let listener =
+            tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        let app = axum::Router::new()
+            .fallback(respond)
+            .with_state(state.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        Stub { url, state }
+    }
+
+    /// Get the requests received so far
+    pub fn requests(&self) -> Vec<StubRequest> {
+        self.state.lock().unwrap().requests.clone()
+    }
+}
+
+async fn respond(
+    State(state): State<Arc<Mutex<StubState>>>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let mut state = state.lock().unwrap();
+    state.requests.push(StubRequest {
+        method,
+        path: uri.to_string(),
+        authorization: headers
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string),
+        body: serde_json::from_slice(&body).unwrap_or(Value::Null),
+    });
+
+    let (status, body) = state
+        .responses
+        .pop_front()
+        .unwrap_or((StatusCode::OK, Value::Object(Default::default())));
+    (status, axum::Json(body)).into_response()
+}
@@ -0,0 +1,135 @@
+use crate::chat::backend::{ChatBackend, ChatMessage};
+use crate::config;
+use serde_json::json;
+
+/// Posts messages as JSON to an outbound webhook, for bridges to chat
+/// systems without a backend of their own, such as IRC.
+///
+/// Channel messages are sent as `{"channel", "text", "message"}` and
+/// direct messages as `{"user", "text", "message"}`, where `text` is the
+/// message rendered as Markdown.
+pub struct WebhookBackend {
+    url: String,
+    client: reqwest::Client,
+}
+
+impl WebhookBackend {
+    pub fn new(url: &str) -> WebhookBackend {
+        WebhookBackend {
+            url: url.to_string(),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Create a backend posting to the configured URL
+    pub fn from_config() -> Option<WebhookBackend> {
+        Some(Self::new(config::get().webhook.url.as_deref()?))
+    }
+
+    async fn post(&self, body: serde_json::Value) -> Result<(), String> {
+        let response = self
+            .client
+            .post(&self.url)
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| format!("Webhook request failed: {}", e))?;
+
+        if !response.status().is_success() {
+            return Err(format!(
+                "Webhook request failed: {}",
+                response.status()
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl ChatBackend for WebhookBackend {
+    fn name(&self) -> &str {
+        "webhook"
+    }
+
+    async fn send_message(
+        &self,
+        channel: &str,
+        message: &ChatMessage,
+    ) -> Result<(), String> {
+        self.post(json!({
+            "channel": channel,
+            "text": message.to_markdown(),
+            "message": message,
+        }))
+        .await
+    }
+
+    async fn send_direct(
+        &self,
+        user: &str,
+        message: &ChatMessage,
+    ) -> Result<(), String> {
+        self.post(json!({
+            "user": user,
+            "text": message.to_markdown(),
+            "message": message,
+        }))
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat::stub::Stub;
+    use axum::http::Method;
+
+    fn message() -> ChatMessage {
+        let mut message = ChatMessage::new("Review Reminder");
+        message.description = Some("Here are a few...".to_string());
+        message
+    }
+
+    #[tokio::test]
+    async fn posts_channel_messages() {
+        let stub = Stub::start(Vec::new()).await;
+        let backend = WebhookBackend::new(&format!("{}/hook", stub.url));
+
+        backend.send_message("reviews", &message()).await.unwrap();
+
+        let requests = stub.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method, Method::POST);
+        assert_eq!(requests[0].path, "/hook");
+        assert_eq!(requests[0].authorization, None);
+        assert_eq!(requests[0].body["channel"], "reviews");
+        assert_eq!(
+            requests[0].body["text"],
+            "**Review Reminder**\nHere are a few..."
+        );
+        assert_eq!(requests[0].body["message"]["title"], "Review Reminder");
+    }
+
+    #[tokio::test]
+    async fn posts_direct_messages() {
+        let stub = Stub::start(Vec::new()).await;
+        let backend = WebhookBackend::new(&stub.url);
+
+        backend.send_direct("alice", &message()).await.unwrap();
+
+        let requests = stub.requests();
+        assert_eq!(requests[0].body["user"], "alice");
+        assert!(requests[0].body.get("channel").is_none());
+    }
+
+    #[tokio::test]
+    async fn reports_error_statuses() {
+        let stub =
+            Stub::start(vec![(503, serde_json::json!({"error": "down"}))])
+                .await;
+        let backend = WebhookBackend::new(&stub.url);
+
+        let error = backend.send_message("reviews", &message()).await;
+        assert!(error.unwrap_err().contains("503"));
+    }
+}
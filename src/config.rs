@@ -0,0 +1,364 @@
+use crate::changes::assignment::AssignmentConfig;
+use crate::changes::exemptions::{Exemption, Exemptions};
+use crate::changes::filter;
+use crate::changes::policy::{AbandonPolicy, PoliciesConfig};
+use crate::changes::retrigger::RetriggerConfig;
+use crate::chat::notifier::{NotificationRule, NotificationsConfig};
+use crate::chat::reminders::{
+    ReminderJob, RemindersConfig, deserialize_optional_channel,
+};
+use serde::Deserialize;
+use std::sync::OnceLock;
+use tracing::error;
+
+/// Environment variable naming the configuration file, if not given on the
+/// command line.
+const CONFIG_ENV: &str = "GERRIT_FASTER_CONFIG";
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+fn default_port() -> u16 {
+    3000
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// Gerrit account the bot acts as
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GerritConfig {
+    /// Overridden by GERRIT_USERNAME
+    pub username: Option<String>,
+    /// HTTP password, overridden by GERRIT_PASSWORD or GERRIT_PASSWORD_FILE
+    pub password: Option<String>,
+    /// File holding the HTTP password
+    pub password_file: Option<String>,
+}
+
+impl GerritConfig {
+    /// Get the username and password, which every command talking to Gerrit
+    /// needs
+    pub fn credentials(&self) -> Result<(String, String), String> {
+        let username = self.username.clone().ok_or(
+            "Gerrit username not set: use gerrit.username or GERRIT_USERNAME",
+        )?;
+        let password = self.password.clone().ok_or(
+            "Gerrit password not set: use gerrit.password, \
+             gerrit.password_file, GERRIT_PASSWORD or GERRIT_PASSWORD_FILE",
+        )?;
+        Ok((username, password))
+    }
+}
+
+/// The web UI
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebserverConfig {
+    /// Port to listen on, overridden by --port
+    #[serde(default = "default_port")]
+    pub port: u16,
+    /// Public hostname used in links, overridden by WEBSERVER_HOSTNAME
+    pub hostname: Option<String>,
+}
+
+impl Default for WebserverConfig {
+    fn default() -> Self {
+        WebserverConfig {
+            port: default_port(),
+            hostname: None,
+        }
+    }
+}
+
+/// The Discord bot
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DiscordConfig {
+    /// Whether to run the bot; --disable-discord turns it off
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Bot token, overridden by DISCORD_TOKEN or DISCORD_TOKEN_FILE
+    pub token: Option<String>,
+    /// File holding the bot token
+    pub token_file: Option<String>,
+    /// Channel for the default reminder job, overridden by
+    /// DISCORD_REVIEW_CHANNEL_ID
+    #[serde(default, deserialize_with = "deserialize_optional_channel")]
+    pub review_channel_id: Option<String>,
+    /// Replacement for https://discord.com, overridden by DISCORD_API_URL
+    pub api_url: Option<String>,
+}
+
+impl Default for DiscordConfig {
+    fn default() -> Self {
+        DiscordConfig {
+            enabled: default_enabled(),
+            token: None,
+            token_file: None,
+            review_channel_id: None,
+            api_url: None,
+        }
+    }
+}
+
+/// The Matrix chat backend, enabled when all of its settings are given
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MatrixConfig {
+    /// e.g. https://matrix.org, overridden by MATRIX_HOMESERVER_URL
+    pub homeserver_url: Option<String>,
+    /// Overridden by MATRIX_ACCESS_TOKEN or MATRIX_ACCESS_TOKEN_FILE
+    pub access_token: Option<String>,
+    /// File holding the access token
+    pub access_token_file: Option<String>,
+    /// The bot's user, e.g. @bot:matrix.org, overridden by MATRIX_USER_ID
+    pub user_id: Option<String>,
+}
+
+/// The outbound webhook chat backend, enabled when a URL is given
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookConfig {
+    /// Overridden by CHAT_WEBHOOK_URL or CHAT_WEBHOOK_URL_FILE
+    pub url: Option<String>,
+    /// File holding the URL, which often embeds a secret
+    pub url_file: Option<String>,
+}
+
+/// Which changes count as community changes
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FiltersConfig {
+    /// YAML file of rejected repositories and files, replacing the built-in
+    /// config/rejected_patterns.yaml
+    pub rejected_patterns: Option<String>,
+}
+
+/// Settings read from the configuration file, with environment variables
+/// applied on top.  Sections left out keep their defaults, or the files
+/// named by their own environment variables.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default)]
+    pub gerrit: GerritConfig,
+    #[serde(default)]
+    pub webserver: WebserverConfig,
+    #[serde(default)]
+    pub discord: DiscordConfig,
+    #[serde(default)]
+    pub matrix: MatrixConfig,
+    #[serde(default)]
+    pub webhook: WebhookConfig,
+    #[serde(default)]
+    pub filters: FiltersConfig,
+    /// Abandonment policies, overridden by ABANDON_POLICIES_CONFIG
+    pub policies: Option<Vec<AbandonPolicy>>,
+    /// Reminder jobs, overridden by DISCORD_REMINDERS_CONFIG
+    pub reminders: Option<Vec<ReminderJob>>,
+    /// Notification rules, overridden by NOTIFICATIONS_CONFIG
+    pub notifications: Option<Vec<NotificationRule>>,
+    /// Exemptions from automation, overridden by EXEMPTIONS_CONFIG
+    pub exemptions: Option<Vec<Exemption>>,
+    /// Reviewer assignment, overridden by REVIEWER_ASSIGNMENT_CONFIG
+    pub reviewer_assignment: Option<AssignmentConfig>,
+    /// CI retriggers, overridden by CI_RETRIGGER_CONFIG
+    pub ci_retrigger: Option<RetriggerConfig>,
+}
+
+// Read a secret from a file, without the trailing newline editors add.
+fn read_secret(path: &str) -> Result<String, String> {
+    std::fs::read_to_string(path)
+        .map(|secret| secret.trim_end_matches(['\r', '\n']).to_string())
+        .map_err(|e| format!("Failed to read {}: {}", path, e))
+}
+
+// Resolve a setting from, in order: its environment variable, the file
+// named by the variable with a _FILE suffix, the configuration file's value
+// and the file it names.
+fn resolve(
+    env: &str,
+    value: Option<String>,
+    file: Option<&str>,
+) -> Result<Option<String>, String> {
+    if let Ok(value) = std::env::var(env) {
+        return Ok(Some(value));
+    }
+    if let Ok(path) = std::env::var(format!("{}_FILE", env)) {
+        return read_secret(&path).map(Some);
+    }
+    if value.is_some() {
+        return Ok(value);
+    }
+    file.map(read_secret).transpose()
+}
+
+impl Config {
+    /// Load the configuration file, a YAML file unless its name ends in
+    /// .toml.  Without a path, the file named by GERRIT_FASTER_CONFIG is
+    /// used, or only the environment if that is not set either.
+    pub fn load(path: Option<&str>) -> Result<Config, String> {
+        let path = path
+            .map(str::to_string)
+            .or_else(|| std::env::var(CONFIG_ENV).ok());
+        let config = match &path {
+            Some(path) => {
+                let text = std::fs::read_to_string(path)
+                    .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+                if path.ends_with(".toml") {
+                    toml::from_str::<Config>(&text).map_err(|e| e.to_string())
+                } else {
+                    serde_yaml::from_str::<Config>(&text)
+                        .map_err(|e| e.to_string())
+                }
+                .map_err(|e| format!("Failed to parse {}: {}", path, e))?
+            }
+            None => Config::default(),
+        };
+
+        let config = config.with_env()?;
+        config.validate().map_err(|e| match &path {
+            Some(path) => format!("{}: {}", path, e),
+            None => e,
+        })?;
+        Ok(config)
+    }
+
+    // Apply the environment variables overriding settings.
+    fn with_env(mut self) -> Result<Config, String> {
+        let gerrit = &mut self.gerrit;
+        gerrit.username =
+            resolve("GERRIT_USERNAME", gerrit.username.take(), None)?;
+        gerrit.password = resolve(
+            "GERRIT_PASSWORD",
+            gerrit.password.take(),
+            gerrit.password_file.as_deref(),
+        )?;
+
+        let discord = &mut self.discord;
+        discord.token = resolve(
+            "DISCORD_TOKEN",
+            discord.token.take(),
+            discord.token_file.as_deref(),
+        )?;
+        discord.review_channel_id = resolve(
+            "DISCORD_REVIEW_CHANNEL_ID",
+            discord.review_channel_id.take(),
+            None,
+        )?;
+        discord.api_url =
+            resolve("DISCORD_API_URL", discord.api_url.take(), None)?;
+
+        let matrix = &mut self.matrix;
+        matrix.homeserver_url = resolve(
+            "MATRIX_HOMESERVER_URL",
+            matrix.homeserver_url.take(),
+            None,
+        )?;
+        matrix.access_token = resolve(
+            "MATRIX_ACCESS_TOKEN",
+            matrix.access_token.take(),
+            matrix.access_token_file.as_deref(),
+        )?;
+        matrix.user_id =
+            resolve("MATRIX_USER_ID", matrix.user_id.take(), None)?;
+
+        self.webhook.url = resolve(
+            "CHAT_WEBHOOK_URL",
+            self.webhook.url.take(),
+            self.webhook.url_file.as_deref(),
+        )?;
+
+        self.webserver.hostname = resolve(
+            "WEBSERVER_HOSTNAME",
+            self.webserver.hostname.take(),
+            None,
+        )?;
+        Ok(self)
+    }
+
+    // Check every section, so mistakes are reported at startup rather than
+    // when a feature first runs.
+    fn validate(&self) -> Result<(), String> {
+        if let Some(id) = &self.discord.review_channel_id
+            && id.parse::<u64>().is_err()
+        {
+            return Err(format!("Invalid Discord review channel: {}", id));
+        }
+        let matrix = [
+            &self.matrix.homeserver_url,
+            &self.matrix.access_token,
+            &self.matrix.user_id,
+        ];
+        if matrix.iter().any(|setting| setting.is_some())
+            && matrix.iter().any(|setting| setting.is_none())
+        {
+            return Err("matrix needs homeserver_url, access_token and \
+                        user_id, or none of them"
+                .to_string());
+        }
+        if let Some(path) = &self.filters.rejected_patterns {
+            filter::load_patterns(Some(path))?;
+        }
+        if let Some(policies) = &self.policies {
+            PoliciesConfig {
+                policies: policies.clone(),
+            }
+            .validate()
+            .map_err(|e| format!("policies: {}", e))?;
+        }
+        if let Some(jobs) = &self.reminders {
+            RemindersConfig { jobs: jobs.clone() }
+                .validate()
+                .map_err(|e| format!("reminders: {}", e))?;
+        }
+        if let Some(notifications) = &self.notifications {
+            NotificationsConfig {
+                notifications: notifications.clone(),
+            }
+            .validate()
+            .map_err(|e| format!("notifications: {}", e))?;
+        }
+        if let Some(exemptions) = &self.exemptions {
+            Exemptions {
+                exemptions: exemptions.clone(),
+            }
+            .validate()
+            .map_err(|e| format!("exemptions: {}", e))?;
+        }
+        if let Some(assignment) = &self.reviewer_assignment {
+            assignment
+                .validate()
+                .map_err(|e| format!("reviewer_assignment: {}", e))?;
+        }
+        if let Some(retrigger) = &self.ci_retrigger {
+            retrigger
+                .validate()
+                .map_err(|e| format!("ci_retrigger: {}", e))?;
+        }
+        Ok(())
+    }
+}
+
+/// Load the configuration once at startup.  Fails if the file or the
+/// environment is invalid, or if the configuration was already loaded.
+pub fn init(path: Option<&str>) -> Result<&'static Config, String> {
+    let config = Config::load(path)?;
+    CONFIG
+        .set(config)
+        .map_err(|_| "Configuration already loaded".to_string())?;
+    Ok(get())
+}
+
+/// Get the configuration.  Without `init`, it is loaded from the
+/// environment on first use, falling back to the defaults if invalid.
+pub fn get() -> &'static Config {
+    CONFIG.get_or_init(|| {
+        Config::load(None).unwrap_or_else(|e| {
+            error!("Using the default configuration: {}", e);
+            Config::default()
+        })
+    })
+}
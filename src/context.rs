@@ -1,21 +1,101 @@
-use crate::changes::container::Container as Changes;
+use crate::changes::assignment::Assignments;
+use crate::changes::container::{Container as Changes, Transition};
+use crate::changes::exemptions::Exemptions;
+use crate::changes::owners::Owners;
+use crate::changes::policy::{AbandonWarnings, Abandonments};
+use crate::changes::retrigger::Retriggers;
+use crate::changes::trends::Trends;
+use crate::chat::selection::ReminderHistory;
+use crate::discord::claims::Claims;
+use crate::discord::links::AccountLinks;
+use crate::discord::subscriptions::Subscriptions;
 use crate::gerrit::connection::Connection as Gerrit;
 use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+use tracing::error;
+
+/// Number of review state transitions buffered for slow listeners.
+const TRANSITION_CHANNEL_SIZE: usize = 1024;
 
 #[derive(Debug, Clone)]
 pub struct ServiceContextData {
     pub gerrit: Gerrit,
     pub changes: Changes,
+    pub owners: Owners,
+    pub trends: Trends,
+    pub links: AccountLinks,
+    pub subscriptions: Subscriptions,
+    pub reminder_history: ReminderHistory,
+    pub claims: Claims,
+    pub abandonments: Abandonments,
+    pub abandon_warnings: AbandonWarnings,
+    pub exemptions: Exemptions,
+    pub assignments: Assignments,
+    pub retriggers: Retriggers,
+    pub transitions: broadcast::Sender<Transition>,
 }
 
 #[derive(Debug, Clone)]
 pub struct ServiceContext(pub Arc<Mutex<ServiceContextData>>);
 
+impl Default for ServiceContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ServiceContext {
     pub fn new() -> ServiceContext {
         ServiceContext(Arc::new(Mutex::new(ServiceContextData {
             gerrit: crate::gerrit::connection::new(),
             changes: Changes::new(),
+            owners: Owners::default(),
+            trends: Trends::load(),
+            links: AccountLinks::load(),
+            subscriptions: Subscriptions::load(),
+            reminder_history: ReminderHistory::load(),
+            claims: Claims::load(),
+            abandonments: Abandonments::load(),
+            abandon_warnings: AbandonWarnings::load(),
+            exemptions: Exemptions::load().unwrap_or_else(|e| {
+                error!("Using default exemptions: {}", e);
+                Exemptions::default()
+            }),
+            assignments: Assignments::load(),
+            retriggers: Retriggers::load(),
+            transitions: broadcast::channel(TRANSITION_CHANNEL_SIZE).0,
+        })))
+    }
+
+    /// Create a context with no connection to Gerrit and state kept only in
+    /// memory, for serving changes loaded from a snapshot without touching
+    /// the live service's state
+    pub fn offline() -> ServiceContext {
+        crate::state::keep_in_memory();
+        Self::detached()
+    }
+
+    /// Create a context without Gerrit credentials or saved state, for
+    /// simulations that must not touch either
+    pub fn detached() -> ServiceContext {
+        ServiceContext(Arc::new(Mutex::new(ServiceContextData {
+            gerrit: crate::gerrit::connection::offline(),
+            changes: Changes::new(),
+            owners: Owners::default(),
+            trends: Trends::default(),
+            links: AccountLinks::default(),
+            subscriptions: Subscriptions::default(),
+            reminder_history: ReminderHistory::default(),
+            claims: Claims::default(),
+            abandonments: Abandonments::default(),
+            abandon_warnings: AbandonWarnings::default(),
+            exemptions: Exemptions::load().unwrap_or_else(|e| {
+                error!("Using default exemptions: {}", e);
+                Exemptions::default()
+            }),
+            assignments: Assignments::default(),
+            retriggers: Retriggers::default(),
+            transitions: broadcast::channel(TRANSITION_CHANNEL_SIZE).0,
         })))
     }
 
@@ -0,0 +1,247 @@
+use crate::chat::backend::{ChatBackend, ChatMessage};
+use crate::config;
+use poise::serenity_prelude as serenity;
+use std::sync::Arc;
+
+// Discord allows at most 25 fields per embed
+const MAX_EMBED_FIELDS: usize = 25;
+
+// Discord allows at most 5 rows of 5 buttons per message
+const MAX_BUTTONS_PER_ROW: usize = 5;
+const MAX_CLAIM_BUTTONS: usize = 25;
+
+// Custom id prefix of the "I'll review" buttons
+pub const CLAIM_PREFIX: &str = "claim:";
+
+/// Posts messages through the Discord REST API
+pub struct DiscordBackend {
+    http: Arc<serenity::Http>,
+}
+
+impl DiscordBackend {
+    /// Create a backend with a bot token.  The API URL, if given, replaces
+    /// https://discord.com, e.g. to point at a local stand-in.
+    pub fn new(token: &str, api_url: Option<&str>) -> DiscordBackend {
+        let mut builder = serenity::HttpBuilder::new(token);
+        if let Some(url) = api_url {
+            builder = builder.proxy(url).ratelimiter_disabled(true);
+        }
+        DiscordBackend {
+            http: Arc::new(builder.build()),
+        }
+    }
+
+    /// Create a backend using the configured Discord token and API URL
+    pub fn from_config() -> Option<DiscordBackend> {
+        let discord = &config::get().discord;
+        Some(Self::new(
+            discord.token.as_deref()?,
+            discord.api_url.as_deref(),
+        ))
+    }
+
+    fn embed(message: &ChatMessage) -> serenity::CreateEmbed {
+        // Blue color
+        let mut embed = serenity::CreateEmbed::new().color((38, 139, 210));
+
+        if !message.title.is_empty() {
+            embed = embed.title(&message.title);
+        }
+        if let Some(description) = &message.description {
+            embed = embed.description(description);
+        }
+        for field in message.fields.iter().take(MAX_EMBED_FIELDS) {
+            embed = embed.field(
+                &field.name,
+                &field.value,
+                false, // Inline: false means each field will be on its own line
+            );
+        }
+        if let Some(footer) = &message.footer {
+            embed = embed.footer(serenity::CreateEmbedFooter::new(footer));
+        }
+        if let Some(url) = &message.url {
+            embed = embed.url(url);
+        }
+        embed
+    }
+
+    fn create_message(message: &ChatMessage) -> serenity::CreateMessage {
+        let mut create = serenity::CreateMessage::new()
+            .add_embed(Self::embed(message))
+            .components(claim_buttons(&message.claimable, |_| false));
+        if let Some(content) = &message.content {
+            create = create.content(content);
+        }
+        create
+    }
+}
+
+#[async_trait::async_trait]
+impl ChatBackend for DiscordBackend {
+    fn name(&self) -> &str {
+        "discord"
+    }
+
+    async fn send_message(
+        &self,
+        channel: &str,
+        message: &ChatMessage,
+    ) -> Result<(), String> {
+        let channel_id = channel
+            .parse::<u64>()
+            .map_err(|_| format!("Invalid Discord channel: {}", channel))?;
+
+        serenity::ChannelId::new(channel_id)
+            .send_message(self.http.as_ref(), Self::create_message(message))
+            .await
+            .map(|_| ())
+            .map_err(|e| format!("Failed to send to Discord channel: {}", e))
+    }
+
+    async fn send_direct(
+        &self,
+        user: &str,
+        message: &ChatMessage,
+    ) -> Result<(), String> {
+        let user_id = user
+            .parse::<u64>()
+            .map_err(|_| format!("Invalid Discord user: {}", user))?;
+
+        serenity::UserId::new(user_id)
+            .direct_message(self.http.as_ref(), Self::create_message(message))
+            .await
+            .map(|_| ())
+            .map_err(|e| format!("Failed to send DM to Discord user: {}", e))
+    }
+}
+
+/// Build rows of "I'll review" buttons for changes, disabling those already
+/// claimed.
+pub fn claim_buttons<F>(
+    ids: &[u64],
+    claimed: F,
+) -> Vec<serenity::CreateActionRow>
+where
+    F: Fn(u64) -> bool,
+{
+    ids.iter()
+        .take(MAX_CLAIM_BUTTONS)
+        .map(|id| {
+            serenity::CreateButton::new(format!("{}{}", CLAIM_PREFIX, id))
+                .label(format!("I'll review {}", id))
+                .style(serenity::ButtonStyle::Primary)
+                .disabled(claimed(*id))
+        })
+        .collect::<Vec<_>>()
+        .chunks(MAX_BUTTONS_PER_ROW)
+        .map(|row| serenity::CreateActionRow::Buttons(row.to_vec()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat::stub::Stub;
+    use axum::http::Method;
+    use serde_json::{Value, json};
+
+    fn user(id: &str) -> Value {
+        json!({"id": id, "username": "user", "discriminator": "0"})
+    }
+
+    // The message Discord returns for one it created
+    fn created(channel: &str) -> Value {
+        json!({
+            "id": "900",
+            "channel_id": channel,
+            "author": user("800"),
+            "content": "",
+            "timestamp": "2025-01-01T00:00:00+00:00",
+            "tts": false,
+            "mention_everyone": false,
+            "mentions": [],
+            "mention_roles": [],
+            "attachments": [],
+            "embeds": [],
+            "pinned": false,
+            "type": 0,
+        })
+    }
+
+    fn message() -> ChatMessage {
+        let mut message = ChatMessage::new("Review Reminder");
+        message.url = Some("https://bot.example.com/bot/report".to_string());
+        message.footer = Some("And there are 3 more...".to_string());
+        message.claimable = vec![101, 102];
+        message.content = Some("<@42>".to_string());
+        message
+    }
+
+    #[tokio::test]
+    async fn sends_an_embed_with_claim_buttons() {
+        let stub = Stub::start(vec![(200, created("123"))]).await;
+        let backend = DiscordBackend::new("token", Some(&stub.url));
+
+        backend.send_message("123", &message()).await.unwrap();
+
+        let requests = stub.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method, Method::POST);
+        assert_eq!(requests[0].path, "/api/v10/channels/123/messages");
+        assert_eq!(requests[0].authorization.as_deref(), Some("Bot token"));
+        assert_eq!(requests[0].body["content"], "<@42>");
+
+        let embed = &requests[0].body["embeds"][0];
+        assert_eq!(embed["title"], "Review Reminder");
+        assert_eq!(embed["url"], "https://bot.example.com/bot/report");
+        assert_eq!(embed["footer"]["text"], "And there are 3 more...");
+        let buttons = &requests[0].body["components"][0]["components"];
+        assert_eq!(buttons[0]["custom_id"], "claim:101");
+        assert_eq!(buttons[1]["label"], "I'll review 102");
+    }
+
+    #[tokio::test]
+    async fn opens_a_dm_channel_for_direct_messages() {
+        let stub = Stub::start(vec![
+            (
+                200,
+                json!({"id": "555", "type": 1, "recipients": [user("42")]}),
+            ),
+            (200, created("555")),
+        ])
+        .await;
+        let backend = DiscordBackend::new("token", Some(&stub.url));
+
+        backend.send_direct("42", &message()).await.unwrap();
+
+        let requests = stub.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].path, "/api/v10/users/@me/channels");
+        assert_eq!(requests[0].body["recipient_id"], "42");
+        assert_eq!(requests[1].path, "/api/v10/channels/555/messages");
+    }
+
+    #[tokio::test]
+    async fn reports_error_statuses() {
+        let stub = Stub::start(vec![(
+            403,
+            json!({"code": 50013, "message": "Missing Permissions"}),
+        )])
+        .await;
+        let backend = DiscordBackend::new("token", Some(&stub.url));
+
+        let error = backend.send_message("123", &message()).await.unwrap_err();
+        assert!(error.contains("Missing Permissions"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_ids_without_a_request() {
+        let stub = Stub::start(Vec::new()).await;
+        let backend = DiscordBackend::new("token", Some(&stub.url));
+
+        assert!(backend.send_message("reviews", &message()).await.is_err());
+        assert!(backend.send_direct("alice", &message()).await.is_err());
+        assert!(stub.requests().is_empty());
+    }
+}
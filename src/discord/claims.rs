@@ -0,0 +1,75 @@
+use crate::state;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+const CLAIMS_FILE: &str = "claims.json";
+
+/// How long a review claim keeps a change out of reminders.
+const CLAIM_HOURS: i64 = 72;
+
+/// A Discord user's promise to review a change
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Claim {
+    pub discord_id: u64,
+    pub name: String,
+    pub claimed: DateTime<Utc>,
+}
+
+impl Claim {
+    fn expired(&self, now: DateTime<Utc>) -> bool {
+        now - self.claimed >= Duration::hours(CLAIM_HOURS)
+    }
+}
+
+/// Review claims by change number, persisted in the state directory.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Claims {
+    claims: HashMap<u64, Claim>,
+}
+
+impl Claims {
+    pub fn load() -> Claims {
+        state::load(CLAIMS_FILE)
+    }
+
+    pub fn save(&self) {
+        state::save(CLAIMS_FILE, self);
+    }
+
+    /// Claim a change for review, returning the existing claim if someone
+    /// else already holds one.
+    pub fn claim(
+        &mut self,
+        id_number: u64,
+        discord_id: u64,
+        name: &str,
+    ) -> Result<(), Claim> {
+        let now = Utc::now();
+        self.claims.retain(|_, claim| !claim.expired(now));
+
+        if let Some(claim) = self.claims.get(&id_number)
+            && claim.discord_id != discord_id
+        {
+            return Err(claim.clone());
+        }
+
+        self.claims.insert(
+            id_number,
+            Claim {
+                discord_id,
+                name: name.to_string(),
+                claimed: now,
+            },
+        );
+        self.save();
+        Ok(())
+    }
+
+    /// Get the unexpired claim on a change
+    pub fn get(&self, id_number: u64) -> Option<&Claim> {
+        self.claims
+            .get(&id_number)
+            .filter(|claim| !claim.expired(Utc::now()))
+    }
+}
@@ -0,0 +1,105 @@
+use crate::state;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+const LINKS_FILE: &str = "links.json";
+
+/// How long a link request stays valid before it must be renewed.
+const PENDING_LINK_HOURS: i64 = 24;
+
+/// A link request waiting for verification
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingLink {
+    pub username: String,
+    pub token: String,
+    pub requested: DateTime<Utc>,
+}
+
+/// Mapping between Discord user ids and Gerrit usernames, persisted in the
+/// state directory.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AccountLinks {
+    linked: HashMap<u64, String>,
+    pending: HashMap<u64, PendingLink>,
+}
+
+impl AccountLinks {
+    pub fn load() -> AccountLinks {
+        state::load(LINKS_FILE)
+    }
+
+    pub fn save(&self) {
+        state::save(LINKS_FILE, self);
+    }
+
+    /// Start linking a Discord user to a Gerrit username, returning the
+    /// token the user must post to prove they own the Gerrit account.
+    pub fn request(&mut self, discord_id: u64, username: &str) -> String {
+        let token = format!("obmc-link-{:016x}", rand::random::<u64>());
+        self.pending.insert(
+            discord_id,
+            PendingLink {
+                username: username.to_string(),
+                token: token.clone(),
+                requested: Utc::now(),
+            },
+        );
+        self.save();
+        token
+    }
+
+    /// Get the outstanding, unexpired link request for a Discord user
+    pub fn get_pending(&self, discord_id: u64) -> Option<&PendingLink> {
+        self.pending.get(&discord_id).filter(|pending| {
+            Utc::now() - pending.requested < Duration::hours(PENDING_LINK_HOURS)
+        })
+    }
+
+    /// Complete the outstanding link request for a Discord user
+    pub fn confirm(&mut self, discord_id: u64) -> Option<String> {
+        let username = self.get_pending(discord_id)?.username.clone();
+        self.pending.remove(&discord_id);
+        self.link(discord_id, &username);
+        Some(username)
+    }
+
+    /// Link a Discord user to a Gerrit username without verification
+    pub fn link(&mut self, discord_id: u64, username: &str) {
+        self.linked.insert(discord_id, username.to_string());
+        self.save();
+    }
+
+    /// Remove any link or link request for a Discord user
+    pub fn unlink(&mut self, discord_id: u64) -> Option<String> {
+        self.pending.remove(&discord_id);
+        let username = self.linked.remove(&discord_id);
+        self.save();
+        username
+    }
+
+    /// Get the Gerrit username linked to a Discord user
+    pub fn gerrit_username(&self, discord_id: u64) -> Option<String> {
+        self.linked.get(&discord_id).cloned()
+    }
+
+    /// Get the Discord users linked to a Gerrit username
+    pub fn discord_ids(&self, username: &str) -> Vec<u64> {
+        let mut ids: Vec<u64> = self
+            .linked
+            .iter()
+            .filter(|(_, linked)| *linked == username)
+            .map(|(id, _)| *id)
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    /// Format a Gerrit username as a Discord mention if it is linked
+    pub fn mention(&self, username: &str) -> String {
+        match self.discord_ids(username).first() {
+            Some(id) => format!("<@{}>", id),
+            None => username.to_string(),
+        }
+    }
+}
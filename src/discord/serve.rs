@@ -1,289 +1,888 @@
 use crate::changes::container::Change;
-use crate::changes::filter::should_include_change;
+use crate::changes::owners::changes_by_maintainer;
 use crate::changes::report::{
-    TimeInterval, changes_by_owner_time, report_by_time,
+    ChangeFilter, GroupBy, ReportFormat, TimeInterval, changes_by_owner_group,
+    changes_by_owner_time, check_days, report_by_time,
 };
+use crate::changes::reviewers::{DEFAULT_REVIEWER_DAYS, report_by_reviewer};
 use crate::changes::status::{NextStepOwner, ReviewState};
+use crate::chat::backend::change_field;
+use crate::chat::commands::{self, REVIEWERS_TO_SELECT};
+use crate::config;
 use crate::context::ServiceContext;
-use chrono::Timelike;
+use crate::discord::backend::{CLAIM_PREFIX, claim_buttons};
+use crate::gerrit::connection::GerritConnection;
 use poise::serenity_prelude as serenity;
-use rand::prelude::*;
-use rand::rng;
-use tracing::{error, warn};
+use tracing::error;
 
 type Error = Box<dyn std::error::Error + Send + Sync>;
 type Context<'a> = poise::Context<'a, ServiceContext, Error>;
 
-// Constants for community review change selection
-const TOTAL_CHANGES_TO_SELECT: usize = 10;
-const RECENT_CHANGES_TO_SELECT: usize = 8;
+// Discord allows at most 25 fields per embed
+const MAX_EMBED_CHANGES: usize = 20;
+
+#[derive(Debug, poise::ChoiceParameter)]
+enum FormatChoice {
+    #[name = "Table"]
+    Text,
+    #[name = "Markdown"]
+    Markdown,
+    #[name = "CSV"]
+    Csv,
+    #[name = "JSON"]
+    Json,
+}
+
+impl From<FormatChoice> for ReportFormat {
+    fn from(choice: FormatChoice) -> ReportFormat {
+        match choice {
+            FormatChoice::Text => ReportFormat::Text,
+            FormatChoice::Markdown => ReportFormat::Markdown,
+            FormatChoice::Csv => ReportFormat::Csv,
+            FormatChoice::Json => ReportFormat::Json,
+        }
+    }
+}
+
+// Discord allows at most 25 autocomplete choices of up to 100 characters
+const MAX_AUTOCOMPLETE_CHOICES: usize = 25;
+const MAX_CHOICE_LENGTH: usize = 100;
+
+// Check if a name, or any '/' separated part of it, starts with a prefix,
+// ignoring case.
+fn matches_prefix(name: &str, partial: &str) -> bool {
+    let partial = partial.to_lowercase();
+    let name = name.to_lowercase();
+    name.starts_with(&partial)
+        || name.split('/').any(|part| part.starts_with(&partial))
+}
+
+// Suggest projects with open changes.
+async fn autocomplete_project(
+    ctx: Context<'_>,
+    partial: &str,
+) -> impl Iterator<Item = String> {
+    let groups = changes_by_owner_group(
+        ctx.data(),
+        &ChangeFilter::default(),
+        GroupBy::Repo,
+    );
+    let mut projects: Vec<String> = groups
+        .get_groups()
+        .into_iter()
+        .filter(|project| matches_prefix(project, partial))
+        .cloned()
+        .collect();
+    projects.sort();
+    projects.into_iter().take(MAX_AUTOCOMPLETE_CHOICES)
+}
+
+// Suggest open changes whose number or subject starts with the input.
+async fn autocomplete_change(
+    ctx: Context<'_>,
+    partial: &str,
+) -> impl Iterator<Item = serenity::AutocompleteChoice> {
+    let lowercase = partial.to_lowercase();
+    let mut changes: Vec<(u64, String)> = ctx
+        .data()
+        .lock()
+        .unwrap()
+        .changes
+        .changes
+        .values()
+        .filter(|change| {
+            change.change.id_number.to_string().starts_with(partial)
+                || change.change.subject.to_lowercase().starts_with(&lowercase)
+        })
+        .map(|change| (change.change.id_number, change.change.subject.clone()))
+        .collect();
+    changes.sort();
+    changes.reverse();
+
+    changes.into_iter().take(MAX_AUTOCOMPLETE_CHOICES).map(
+        |(id_number, subject)| {
+            let name: String = format!("{}: {}", id_number, subject)
+                .chars()
+                .take(MAX_CHOICE_LENGTH)
+                .collect();
+            serenity::AutocompleteChoice::new(name, id_number.to_string())
+        },
+    )
+}
+
+// Suggest Gerrit usernames of change owners and reviewers.
+async fn autocomplete_username(
+    ctx: Context<'_>,
+    partial: &str,
+) -> impl Iterator<Item = String> {
+    let lowercase = partial.to_lowercase();
+    let mut usernames: Vec<String> = {
+        let data = ctx.data().lock().unwrap();
+        data.changes
+            .changes
+            .values()
+            .flat_map(|change| {
+                let reviewers = change
+                    .change
+                    .labels
+                    .get("Code-Review")
+                    .into_iter()
+                    .flat_map(|label| label.iter())
+                    .map(|approval| approval.username.clone());
+                std::iter::once(change.change.owner.username.clone())
+                    .chain(reviewers)
+            })
+            .filter(|username| username.to_lowercase().starts_with(&lowercase))
+            .collect()
+    };
+    usernames.sort();
+    usernames.dedup();
+    usernames.into_iter().take(MAX_AUTOCOMPLETE_CHOICES)
+}
+
+// Suggest the projects the user is subscribed to.
+async fn autocomplete_subscription(
+    ctx: Context<'_>,
+    partial: &str,
+) -> impl Iterator<Item = String> {
+    let lowercase = partial.to_lowercase();
+    ctx.data()
+        .lock()
+        .unwrap()
+        .subscriptions
+        .get(ctx.author().id.get())
+        .into_iter()
+        .map(|subscription| subscription.pattern)
+        .filter(|pattern| pattern.to_lowercase().starts_with(&lowercase))
+        .take(MAX_AUTOCOMPLETE_CHOICES)
+        .collect::<Vec<_>>()
+        .into_iter()
+}
 
 // Give a report of outstanding changes.
 #[poise::command(slash_command, prefix_command, rename = "obmc-report")]
 async fn report(
     ctx: Context<'_>,
-    #[description = "Project"] project: Option<String>,
+    #[description = "Project"]
+    #[autocomplete = "autocomplete_project"]
+    project: Option<String>,
+    #[description = "Branch"] branch: Option<String>,
+    #[description = "Topic"] topic: Option<String>,
+    #[description = "Output format"] format: Option<FormatChoice>,
 ) -> Result<(), Error> {
     let service = ctx.data().clone();
+    let format: ReportFormat = format.map(Into::into).unwrap_or_default();
 
-    let report = report_by_time(&service, project.clone(), None);
+    let filter = ChangeFilter {
+        project: project.clone(),
+        branch: branch.clone(),
+        topic: topic.clone(),
+        ..Default::default()
+    };
+    let report = report_by_time(&service, &filter, format);
+
+    // Discord does not render tables, so wrap every format in a code block
+    // with the matching syntax highlighting.
+    let report = match format {
+        ReportFormat::Markdown => format!("```md\n{}\n```", report),
+        ReportFormat::Text => format!("```\n{}\n```", report),
+        ReportFormat::Csv => format!("```csv\n{}\n```", report),
+        ReportFormat::Json => format!("```json\n{}\n```", report),
+    };
 
-    let response = if let Some(ref project_name) = project {
-        format!("Project {}:\n```\n{}\n```", project_name, report)
+    let mut title: Vec<String> = Vec::new();
+    if let Some(ref project_name) = project {
+        title.push(format!("Project {}", project_name));
+    }
+    if let Some(ref branch_name) = branch {
+        title.push(format!("Branch {}", branch_name));
+    }
+    if let Some(ref topic_name) = topic {
+        title.push(format!("Topic {}", topic_name));
+    }
+
+    let response = if title.is_empty() {
+        format!("Overall Status:\n{}", report)
     } else {
-        format!("Overall Status:\n```\n{}\n```", report)
+        format!("{}:\n{}", title.join(", "), report)
     };
     ctx.say(response).await?;
 
     Ok(())
 }
 
-// Get the the review status of a Gerrit change.
-#[poise::command(slash_command, prefix_command, rename = "obmc-review-status")]
-async fn review_status(
+// Show the most active reviewers and the changes they are blocking.
+#[poise::command(slash_command, prefix_command, rename = "obmc-reviewers")]
+async fn reviewers(
     ctx: Context<'_>,
-    #[description = "Change ID"] change_id: String,
+    #[description = "Count votes cast in the last N days"] days: Option<i64>,
+    #[description = "Number of reviewers to show"] limit: Option<usize>,
 ) -> Result<(), Error> {
-    let change: Option<Change>;
-    {
-        let changes = &ctx.data().lock().unwrap().changes;
-
-        let id = change_id.parse::<u64>();
-        change = match id {
-            Ok(i) => changes.get(i),
-            _ => changes.get_by_change_id(&change_id),
+    let service = ctx.data().clone();
+    let days = match check_days(days.unwrap_or(DEFAULT_REVIEWER_DAYS)) {
+        Ok(days) => days,
+        Err(e) => {
+            ctx.say(e).await?;
+            return Ok(());
         }
-    }
-
-    let response = if change.is_some() {
-        format!(
-            "Change {} is {:?}.",
-            change_id,
-            change.unwrap().review_state
-        )
-    } else {
-        format!("Could not find change: {}", change_id)
     };
-    ctx.say(response).await?;
+
+    let report = report_by_reviewer(
+        &service,
+        days,
+        Some(limit.unwrap_or(REVIEWERS_TO_SELECT)),
+        ReportFormat::Text,
+    );
+
+    ctx.say(format!("Reviewers:\n```\n{}\n```", report)).await?;
     Ok(())
 }
 
-// Get changes that need community review, selecting up to RECENT_CHANGES_TO_SELECT changes that are
-// under 24 hours or under 72 hours old, and then selecting additional changes
-// from the over 72 hours group to make a total of TOTAL_CHANGES_TO_SELECT changes.
-// Returns a tuple of (selected_changes, total_community_review_count)
-async fn get_community_review_changes(
-    context: &ServiceContext,
-) -> (Vec<Change>, usize) {
-    // Use existing changes_by_owner_time function to get changes
-    let changes_by_time = changes_by_owner_time(context, None, None);
-
-    // Get the lock on the context to access changes
-    let ctx = context.lock().unwrap();
-
-    // Collect changes in CommunityReview state, separating into recent and older groups
-    let mut recent_changes = Vec::new();
-    let mut older_changes = Vec::new();
-    let mut total_community_review_count = 0;
-
-    // Process all time intervals in a single iteration
-    for time_interval in [
-        TimeInterval::Under24Hours,
-        TimeInterval::Under72Hours,
-        TimeInterval::Under2Weeks,
-        TimeInterval::Under8Weeks,
-        TimeInterval::Over8Weeks,
-    ] {
-        let change_ids = changes_by_time
-            .get_changes(time_interval, NextStepOwner::Community);
-
-        for id in change_ids {
-            if let Some(change) = ctx.changes.get(id) {
-                // Double-check that the change is actually in CommunityReview state
-                if matches!(change.review_state, ReviewState::CommunityReview) {
-                    // Apply the community filter
-                    if should_include_change(&change.change) {
-                        total_community_review_count += 1;
-                        // Categorize changes based on time interval
-                        match time_interval {
-                            TimeInterval::Under24Hours
-                            | TimeInterval::Under72Hours => {
-                                recent_changes.push(change);
-                            }
-                            _ => {
-                                older_changes.push(change);
-                            }
-                        }
-                    }
-                }
+// List the changes waiting on a maintainer, based on the OWNERS files.
+#[poise::command(
+    slash_command,
+    prefix_command,
+    rename = "obmc-maintainer-queue"
+)]
+async fn maintainer_queue(
+    ctx: Context<'_>,
+    #[description = "Gerrit username or email"]
+    #[autocomplete = "autocomplete_username"]
+    username: String,
+) -> Result<(), Error> {
+    let service = ctx.data().clone();
+    let changes = changes_by_maintainer(&service, &username);
+
+    let mut queue: Vec<Change> = Vec::new();
+    {
+        let container = &service.lock().unwrap().changes;
+        for time_interval in [
+            TimeInterval::Over8Weeks,
+            TimeInterval::Under8Weeks,
+            TimeInterval::Under2Weeks,
+            TimeInterval::Under72Hours,
+            TimeInterval::Under24Hours,
+        ] {
+            for id in
+                changes.get_changes(time_interval, NextStepOwner::Maintainer)
+            {
+                queue.extend(container.get(id));
             }
         }
     }
 
-    // Randomly shuffle both groups
-    let mut rng = rng();
-    recent_changes.shuffle(&mut rng);
-    older_changes.shuffle(&mut rng);
+    if queue.is_empty() {
+        ctx.say(format!("No changes are waiting on {}.", username))
+            .await?;
+        return Ok(());
+    }
 
-    // Select up to RECENT_CHANGES_TO_SELECT recent changes
-    let recent_count =
-        std::cmp::min(RECENT_CHANGES_TO_SELECT, recent_changes.len());
-    let mut selected_changes: Vec<Change> =
-        recent_changes[..recent_count].to_vec();
+    let mut embed = serenity::CreateEmbed::new()
+        .title(format!("Maintainer Queue for {}", username))
+        .description("Changes awaiting maintainer review, oldest first...")
+        .color((133, 153, 0)); // Green color
 
-    // Select additional changes from older group to make a total of TOTAL_CHANGES_TO_SELECT
-    let additional_count = TOTAL_CHANGES_TO_SELECT - selected_changes.len();
-    let older_count = std::cmp::min(additional_count, older_changes.len());
-    selected_changes.extend(older_changes[..older_count].to_vec());
+    for change in queue.iter().take(MAX_EMBED_CHANGES) {
+        let (field_name, field_value) = change_field(change);
+        embed = embed.field(field_name, field_value, false);
+    }
 
-    (selected_changes, total_community_review_count)
-}
+    let additional_count = queue.len().saturating_sub(MAX_EMBED_CHANGES);
+    if additional_count > 0 {
+        embed = embed.footer(serenity::CreateEmbedFooter::new(format!(
+            "And there are {} more...",
+            additional_count
+        )));
+    }
 
-// Send community review reminder to Discord
-async fn send_community_review_reminder(
-    context: &ServiceContext,
-    http: &serenity::Http,
-    channel_id: u64,
-) {
-    let (changes, total_count) = get_community_review_changes(context).await;
+    if let Some(hostname) = &config::get().webserver.hostname {
+        embed = embed
+            .url(format!("https://{}/bot/maintainer/{}", hostname, username));
+    }
 
+    let mention = service.lock().unwrap().links.mention(&username);
+    ctx.send(
+        poise::CreateReply::default()
+            .content(format!("Changes waiting on {}:", mention))
+            .embed(embed),
+    )
+    .await?;
+    Ok(())
+}
+
+// Get the review state of every change in a cross-repository topic.
+#[poise::command(slash_command, prefix_command, rename = "obmc-topic")]
+async fn topic_status(
+    ctx: Context<'_>,
+    #[description = "Topic"] topic: String,
+) -> Result<(), Error> {
+    let changes = commands::topic_changes(ctx.data(), &topic);
     if changes.is_empty() {
-        return;
+        ctx.say(commands::topic_summary(&topic, &changes)).await?;
+        return Ok(());
     }
 
+    let ready = commands::topic_ready(&changes);
     let mut embed = serenity::CreateEmbed::new()
-        .title("Review Reminder")
-        .description("Want to help with reviews? Here are a few...")
-        .color((38, 139, 210)); // Blue color
+        .title(format!("Topic {}", topic))
+        .description(commands::topic_summary(&topic, &changes))
+        .color(if ready { (133, 153, 0) } else { (203, 75, 22) });
 
-    for change in &changes {
-        let change_url = format!(
-            "https://gerrit.openbmc.org/c/{}/+/{}",
-            change.change.project, change.change.id_number
+    for change in changes.iter().take(MAX_EMBED_CHANGES) {
+        embed = embed.field(
+            format!("{} - {:?}", change.change.project, change.review_state),
+            format!(
+                "[{}](https://gerrit.openbmc.org/c/{}/+/{})",
+                change.change.subject,
+                change.change.project,
+                change.change.id_number
+            ),
+            false,
         );
+    }
 
-        // Calculate waiting time
-        let now = chrono::Utc::now();
-        let duration = now.signed_duration_since(change.review_state_updated);
-        let waiting_time = format_duration(duration);
+    if let Some(hostname) = &config::get().webserver.hostname {
+        embed = embed.url(format!(
+            "https://{}/bot/topic/{}",
+            hostname,
+            urlencoding::encode(&topic)
+        ));
+    }
 
-        let field_value = format!(
-            "[{}]({}) (+{}/-{})",
-            change.change.subject,
-            change_url,
-            change.change.insertions,
-            change.change.deletions,
-        );
+    ctx.send(poise::CreateReply::default().embed(embed)).await?;
+    Ok(())
+}
 
-        embed = embed.field(
-            format!("{} - waiting {}", change.change.project, waiting_time),
-            field_value,
-            false, // Inline: false means each field will be on its own line
-        );
+// Order author action items so the most urgent are listed first.
+fn author_action_rank(state: &ReviewState) -> u8 {
+    match state {
+        ReviewState::FailingCI => 0,
+        ReviewState::MergeConflict => 1,
+        ReviewState::PendingFeedback(_) => 2,
+        ReviewState::PendingCommentResolution(_) => 3,
+        ReviewState::MissingCI => 4,
+        _ => 5,
     }
+}
 
-    // Add footer with count of additional changes
-    let additional_count = total_count.saturating_sub(changes.len());
-    if additional_count > 0 {
-        let footer = serenity::CreateEmbedFooter::new(format!(
-            "And there are {} more...",
-            additional_count
-        ));
-        embed = embed.footer(footer);
+// Format a list of changes as embed field lines, within Discord's limit of
+// 1024 characters per field.
+fn change_lines(changes: &[Change]) -> String {
+    const MAX_FIELD_LENGTH: usize = 1024;
+
+    let mut result = String::new();
+    for (i, change) in changes.iter().enumerate() {
+        let line = format!(
+            "[{}](https://gerrit.openbmc.org/c/{}/+/{})\n",
+            change.change.subject,
+            change.change.project,
+            change.change.id_number
+        );
+        let more = format!("And {} more...", changes.len() - i);
+        if result.len() + line.len() + more.len() > MAX_FIELD_LENGTH {
+            result += &more;
+            break;
+        }
+        result += &line;
     }
+    result
+}
+
+// List a user's changes grouped by who needs to act next.
+#[poise::command(slash_command, prefix_command, rename = "obmc-my-changes")]
+async fn my_changes(
+    ctx: Context<'_>,
+    #[description = "Gerrit username (defaults to your linked account)"]
+    #[autocomplete = "autocomplete_username"]
+    username: Option<String>,
+) -> Result<(), Error> {
+    let service = ctx.data().clone();
+    let linked_username = service
+        .lock()
+        .unwrap()
+        .links
+        .gerrit_username(ctx.author().id.get());
+    let Some(username) = username.or(linked_username) else {
+        ctx.say(
+            "Please provide a Gerrit username, or link your account with /obmc-link.",
+        )
+        .await?;
+        return Ok(());
+    };
+
+    let changes_by_time = changes_by_owner_time(
+        &service,
+        &ChangeFilter {
+            owner: Some(username.clone()),
+            ..Default::default()
+        },
+        chrono::Utc::now(),
+    );
 
-    // Add webserver link if WEBSERVER_HOSTNAME is set
-    if let Ok(hostname) = std::env::var("WEBSERVER_HOSTNAME") {
-        embed = embed.url(format!("https://{}/bot/report", hostname));
+    let mut changes_by_owner: Vec<(NextStepOwner, Vec<Change>)> = Vec::new();
+    {
+        let container = &service.lock().unwrap().changes;
+        for owner in [
+            NextStepOwner::Author,
+            NextStepOwner::Community,
+            NextStepOwner::Maintainer,
+        ] {
+            let mut changes: Vec<Change> = Vec::new();
+            for time_interval in [
+                TimeInterval::Over8Weeks,
+                TimeInterval::Under8Weeks,
+                TimeInterval::Under2Weeks,
+                TimeInterval::Under72Hours,
+                TimeInterval::Under24Hours,
+            ] {
+                for id in changes_by_time.get_changes(time_interval, owner) {
+                    changes.extend(container.get(id));
+                }
+            }
+            changes_by_owner.push((owner, changes));
+        }
     }
 
-    let channel_id = serenity::ChannelId::new(channel_id);
-    if let Err(e) = channel_id
-        .send_message(http, serenity::CreateMessage::new().add_embed(embed))
-        .await
+    if changes_by_owner
+        .iter()
+        .all(|(_, changes)| changes.is_empty())
     {
-        error!("Failed to send message to Discord channel: {}", e);
+        ctx.say(format!("Could not find open changes for {}.", username))
+            .await?;
+        return Ok(());
     }
-}
 
-// Format duration as simple time string like "1 hour" or "3 days"
-fn format_duration(duration: chrono::Duration) -> String {
-    let hours = duration.num_hours();
-    let days = duration.num_days();
+    let mut embed = serenity::CreateEmbed::new()
+        .title(format!("Changes for {}", username))
+        .color((38, 139, 210)); // Blue color
 
-    if days > 0 {
-        if days == 1 {
-            "1 day".to_string()
-        } else {
-            format!("{} days", days)
+    for (owner, mut changes) in changes_by_owner {
+        if changes.is_empty() {
+            continue;
         }
-    } else if hours > 0 {
-        if hours == 1 {
-            "1 hour".to_string()
+
+        if owner == NextStepOwner::Author {
+            // List each author action on its own, most urgent first.
+            changes
+                .sort_by_key(|change| author_action_rank(&change.review_state));
+            for change in changes.iter().take(MAX_EMBED_CHANGES) {
+                embed = embed.field(
+                    format!("Action: {:?}", change.review_state),
+                    change_lines(std::slice::from_ref(change)),
+                    false,
+                );
+            }
         } else {
-            format!("{} hours", hours)
+            embed = embed.field(
+                format!("Waiting on {:?} ({})", owner, changes.len()),
+                change_lines(&changes),
+                false,
+            );
         }
-    } else {
-        "less than 1 hour".to_string()
     }
+
+    if let Some(hostname) = &config::get().webserver.hostname {
+        embed =
+            embed.url(format!("https://{}/bot/user/{}", hostname, username));
+    }
+
+    ctx.send(poise::CreateReply::default().embed(embed)).await?;
+    Ok(())
+}
+
+// Start linking your Discord account to a Gerrit username.
+#[poise::command(slash_command, prefix_command, rename = "obmc-link")]
+async fn link(
+    ctx: Context<'_>,
+    #[description = "Gerrit username"]
+    #[autocomplete = "autocomplete_username"]
+    username: String,
+) -> Result<(), Error> {
+    let token = ctx
+        .data()
+        .lock()
+        .unwrap()
+        .links
+        .request(ctx.author().id.get(), &username);
+
+    ctx.send(
+        poise::CreateReply::default()
+            .content(format!(
+                concat!(
+                    "To link your account to Gerrit user {}, post a comment ",
+                    "containing `{}` on any Gerrit change as that user and then ",
+                    "run /obmc-link-verify.  Alternatively, ask a server admin ",
+                    "to run /obmc-link-approve.  The request expires in 24 hours."
+                ),
+                username, token
+            ))
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
 }
 
-// Periodic task for sending community review reminders
-async fn community_review_reminder_task(
-    context: ServiceContext,
-    http: &serenity::Http,
-) {
-    // Get the channel ID from environment variable or exit if not set
-    let channel_id = match std::env::var("DISCORD_REVIEW_CHANNEL_ID") {
-        Ok(id) => match id.parse::<u64>() {
-            Ok(parsed_id) => parsed_id,
-            Err(_) => {
-                error!("Invalid DISCORD_REVIEW_CHANNEL_ID: {}", id);
-                return;
+// Verify a link request by finding its token in a Gerrit comment.
+#[poise::command(slash_command, prefix_command, rename = "obmc-link-verify")]
+async fn link_verify(ctx: Context<'_>) -> Result<(), Error> {
+    let discord_id = ctx.author().id.get();
+    let pending = ctx
+        .data()
+        .lock()
+        .unwrap()
+        .links
+        .get_pending(discord_id)
+        .cloned();
+
+    let Some(pending) = pending else {
+        ctx.send(
+            poise::CreateReply::default()
+                .content("No pending link request; run /obmc-link first.")
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    };
+
+    ctx.defer_ephemeral().await?;
+
+    let verified = ctx
+        .data()
+        .get_gerrit()
+        .query_changes(
+            &format!("commentby:{} -age:1d", pending.username),
+            &["MESSAGES"],
+        )
+        .await
+        .unwrap_or_default()
+        .iter()
+        .flat_map(|change| change.messages.iter())
+        .any(|message| {
+            message
+                .author
+                .as_ref()
+                .is_some_and(|author| author.username == pending.username)
+                && message.message.contains(&pending.token)
+        });
+
+    let response = if verified {
+        ctx.data().lock().unwrap().links.confirm(discord_id);
+        format!("Linked to Gerrit user {}.", pending.username)
+    } else {
+        format!(
+            "Could not find a comment by {} containing `{}` yet.",
+            pending.username, pending.token
+        )
+    };
+    ctx.send(
+        poise::CreateReply::default()
+            .content(response)
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
+
+// Approve a link request, or link a user directly, as a server admin.
+#[poise::command(
+    slash_command,
+    prefix_command,
+    rename = "obmc-link-approve",
+    required_permissions = "MANAGE_GUILD"
+)]
+async fn link_approve(
+    ctx: Context<'_>,
+    #[description = "Discord user"] user: serenity::User,
+    #[description = "Gerrit username (defaults to the pending request)"]
+    #[autocomplete = "autocomplete_username"]
+    username: Option<String>,
+) -> Result<(), Error> {
+    let linked = {
+        let links = &mut ctx.data().lock().unwrap().links;
+        match username {
+            Some(username) => {
+                links.link(user.id.get(), &username);
+                Some(username)
             }
-        },
-        Err(_) => {
-            // Channel ID not set, exit the task
-            warn!(
-                "DISCORD_REVIEW_CHANNEL_ID not set, community review reminders disabled."
-            );
-            return;
+            None => links.confirm(user.id.get()),
         }
     };
 
-    loop {
-        // Calculate delay until next hour using wall clock time
-        let now = chrono::Utc::now();
-        let next_hour = now
-            .with_minute(0)
-            .unwrap()
-            .with_second(0)
-            .unwrap()
-            .with_nanosecond(0)
-            .unwrap()
-            + chrono::Duration::hours(3);
-
-        let duration = next_hour.signed_duration_since(now);
-        let seconds_until_next_hour = duration.num_seconds() as u64 + 1;
-
-        // Sleep until next hour
-        tokio::time::sleep(tokio::time::Duration::from_secs(
-            seconds_until_next_hour,
-        ))
-        .await;
+    let response = match linked {
+        Some(username) => {
+            format!("Linked {} to Gerrit user {}.", user.name, username)
+        }
+        None => format!("No pending link request for {}.", user.name),
+    };
+    ctx.send(
+        poise::CreateReply::default()
+            .content(response)
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
 
-        // Send reminder
-        send_community_review_reminder(&context, http, channel_id).await;
+// Remove the link between your Discord account and Gerrit.
+#[poise::command(slash_command, prefix_command, rename = "obmc-unlink")]
+async fn unlink(ctx: Context<'_>) -> Result<(), Error> {
+    let unlinked = ctx
+        .data()
+        .lock()
+        .unwrap()
+        .links
+        .unlink(ctx.author().id.get());
+
+    let response = match unlinked {
+        Some(username) => format!("Unlinked from Gerrit user {}.", username),
+        None => "Your account is not linked.".to_string(),
+    };
+    ctx.send(
+        poise::CreateReply::default()
+            .content(response)
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
+
+// Subscribe to DMs when changes in a project need review or are ready.
+#[poise::command(slash_command, prefix_command, rename = "obmc-subscribe")]
+async fn subscribe(
+    ctx: Context<'_>,
+    #[description = "Project name, or regex if 'regex' is set"]
+    #[autocomplete = "autocomplete_project"]
+    project: String,
+    #[description = "Treat the project as a regex"] regex: Option<bool>,
+) -> Result<(), Error> {
+    let result = ctx.data().lock().unwrap().subscriptions.subscribe(
+        ctx.author().id.get(),
+        &project,
+        regex.unwrap_or(false),
+    );
+
+    let response = match result {
+        Ok(true) => format!("Subscribed to {}.", project),
+        Ok(false) => format!("Already subscribed to {}.", project),
+        Err(e) => e,
+    };
+    ctx.send(
+        poise::CreateReply::default()
+            .content(response)
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
+
+// Remove a project subscription.
+#[poise::command(slash_command, prefix_command, rename = "obmc-unsubscribe")]
+async fn unsubscribe(
+    ctx: Context<'_>,
+    #[description = "Project name or regex"]
+    #[autocomplete = "autocomplete_subscription"]
+    project: String,
+) -> Result<(), Error> {
+    let removed = ctx
+        .data()
+        .lock()
+        .unwrap()
+        .subscriptions
+        .unsubscribe(ctx.author().id.get(), &project);
+
+    let response = if removed {
+        format!("Unsubscribed from {}.", project)
+    } else {
+        format!("Not subscribed to {}.", project)
+    };
+    ctx.send(
+        poise::CreateReply::default()
+            .content(response)
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
+
+// List your project subscriptions.
+#[poise::command(slash_command, prefix_command, rename = "obmc-subscriptions")]
+async fn subscriptions(ctx: Context<'_>) -> Result<(), Error> {
+    let subscriptions = ctx
+        .data()
+        .lock()
+        .unwrap()
+        .subscriptions
+        .get(ctx.author().id.get());
+
+    let response = if subscriptions.is_empty() {
+        "You have no subscriptions.".to_string()
+    } else {
+        subscriptions
+            .iter()
+            .map(|s| {
+                if s.regex.is_some() {
+                    format!("- `{}` (regex)", s.pattern)
+                } else {
+                    format!("- {}", s.pattern)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    ctx.send(
+        poise::CreateReply::default()
+            .content(response)
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
+
+// Get the the review status of a Gerrit change.
+#[poise::command(slash_command, prefix_command, rename = "obmc-review-status")]
+async fn review_status(
+    ctx: Context<'_>,
+    #[description = "Change number or Change-Id"]
+    #[autocomplete = "autocomplete_change"]
+    change_id: String,
+) -> Result<(), Error> {
+    ctx.say(commands::review_status(ctx.data(), &change_id))
+        .await?;
+    Ok(())
+}
+
+// Record a review claim from a reminder button and show the claimer in the
+// reminder embed.
+async fn handle_claim(
+    ctx: &serenity::Context,
+    interaction: &serenity::ComponentInteraction,
+    context: &ServiceContext,
+    id_number: u64,
+) -> Result<(), Error> {
+    let result = context.lock().unwrap().claims.claim(
+        id_number,
+        interaction.user.id.get(),
+        &interaction.user.name,
+    );
+
+    if let Err(claim) = result {
+        interaction
+            .create_response(
+                ctx,
+                serenity::CreateInteractionResponse::Message(
+                    serenity::CreateInteractionResponseMessage::new()
+                        .content(format!(
+                            "{} is already reviewing this change.",
+                            claim.name
+                        ))
+                        .ephemeral(true),
+                ),
+            )
+            .await?;
+        return Ok(());
+    }
+
+    // Mark the claimed change in the embed
+    let change_path = format!("/+/{})", id_number);
+    let mention = format!("Claimed by <@{}>", interaction.user.id);
+    let embeds = interaction
+        .message
+        .embeds
+        .iter()
+        .cloned()
+        .map(|mut embed| {
+            for field in embed.fields.iter_mut() {
+                if field.value.contains(&change_path)
+                    && !field.value.contains(&mention)
+                {
+                    field.value = format!("{}\n{}", field.value, mention);
+                }
+            }
+            serenity::CreateEmbed::from(embed)
+        })
+        .collect();
+
+    // Rebuild the buttons so the claimed one is disabled
+    let ids: Vec<u64> = interaction
+        .message
+        .components
+        .iter()
+        .flat_map(|row| row.components.iter())
+        .filter_map(|component| match component {
+            serenity::ActionRowComponent::Button(button) => {
+                match &button.data {
+                    serenity::ButtonKind::NonLink { custom_id, .. } => {
+                        custom_id.strip_prefix(CLAIM_PREFIX)?.parse().ok()
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        })
+        .collect();
+
+    interaction
+        .create_response(
+            ctx,
+            serenity::CreateInteractionResponse::UpdateMessage(
+                serenity::CreateInteractionResponseMessage::new()
+                    .embeds(embeds)
+                    .components(claim_buttons(&ids, |id| {
+                        context.lock().unwrap().claims.get(id).is_some()
+                    })),
+            ),
+        )
+        .await?;
+    Ok(())
+}
+
+// Handle gateway events that are not commands.
+async fn event_handler(
+    ctx: &serenity::Context,
+    event: &serenity::FullEvent,
+    context: &ServiceContext,
+) -> Result<(), Error> {
+    if let serenity::FullEvent::InteractionCreate {
+        interaction: serenity::Interaction::Component(interaction),
+    } = event
+        && let Some(id_number) = interaction
+            .data
+            .custom_id
+            .strip_prefix(CLAIM_PREFIX)
+            .and_then(|id| id.parse::<u64>().ok())
+    {
+        handle_claim(ctx, interaction, context, id_number).await?;
     }
+    Ok(())
 }
 
 pub async fn serve(context: ServiceContext) {
-    let token = std::env::var("DISCORD_TOKEN").expect("missing DISCORD_TOKEN");
+    let Some(token) = config::get().discord.token.clone() else {
+        error!("Discord bot disabled: no Discord token configured");
+        return;
+    };
     let intents = serenity::GatewayIntents::non_privileged();
 
     let framework = poise::Framework::builder()
         .options(poise::FrameworkOptions {
-            commands: vec![report(), review_status()],
+            commands: vec![
+                report(),
+                review_status(),
+                reviewers(),
+                maintainer_queue(),
+                topic_status(),
+                my_changes(),
+                link(),
+                link_verify(),
+                link_approve(),
+                unlink(),
+                subscribe(),
+                unsubscribe(),
+                subscriptions(),
+            ],
+            event_handler: |ctx, event, _framework, context| {
+                Box::pin(event_handler(ctx, event, context))
+            },
             ..Default::default()
         })
         .setup(|ctx, _ready, framework| {
@@ -299,19 +898,6 @@ pub async fn serve(context: ServiceContext) {
                     guild.id.edit_nickname(ctx, Some("openbmc-bot")).await?;
                 }
 
-                // Clone context and http for the periodic task
-                let context_clone = context.clone();
-                let http = ctx.http.clone();
-
-                // Start the periodic task for community review reminders
-                tokio::spawn(async move {
-                    community_review_reminder_task(
-                        context_clone,
-                        http.as_ref(),
-                    )
-                    .await;
-                });
-
                 Ok(context)
             })
         })
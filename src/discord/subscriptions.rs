@@ -0,0 +1,224 @@
+use crate::changes::patterns::Patterns;
+use crate::state;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+const SUBSCRIPTIONS_FILE: &str = "subscriptions.json";
+
+/// Maximum number of notifications sent to one user per hour.
+const MAX_NOTIFICATIONS_PER_HOUR: usize = 10;
+
+/// How long a sent notification is remembered to avoid repeating it.
+const NOTIFICATION_MEMORY_DAYS: i64 = 30;
+
+/// A project name, or project regex, a user is subscribed to
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "SubscriptionData", into = "SubscriptionData")]
+pub struct Subscription {
+    pub pattern: String,
+    /// The compiled pattern of a regex subscription
+    pub regex: Option<Patterns>,
+}
+
+// A subscription as saved in the state directory
+#[derive(Serialize, Deserialize)]
+struct SubscriptionData {
+    pattern: String,
+    #[serde(default)]
+    regex: bool,
+}
+
+impl TryFrom<SubscriptionData> for Subscription {
+    type Error = String;
+
+    fn try_from(data: SubscriptionData) -> Result<Self, Self::Error> {
+        Subscription::new(&data.pattern, data.regex)
+    }
+}
+
+impl From<Subscription> for SubscriptionData {
+    fn from(subscription: Subscription) -> Self {
+        SubscriptionData {
+            regex: subscription.regex.is_some(),
+            pattern: subscription.pattern,
+        }
+    }
+}
+
+impl Subscription {
+    pub fn new(pattern: &str, regex: bool) -> Result<Subscription, String> {
+        let regex = regex
+            .then(|| Patterns::try_from(vec![pattern.to_string()]))
+            .transpose()?;
+        Ok(Subscription {
+            pattern: pattern.to_string(),
+            regex,
+        })
+    }
+
+    pub fn matches(&self, project: &str) -> bool {
+        match &self.regex {
+            Some(regex) => regex.is_match(project),
+            None => self.pattern == project,
+        }
+    }
+}
+
+/// Per-user project subscriptions and the notifications already sent,
+/// persisted in the state directory.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Subscriptions {
+    subscriptions: HashMap<u64, Vec<Subscription>>,
+    /// When each (user, change, state) notification was sent
+    notified: HashMap<String, DateTime<Utc>>,
+    /// Recent notification times per user, for rate limiting
+    recent: HashMap<u64, Vec<DateTime<Utc>>>,
+}
+
+impl Subscriptions {
+    pub fn load() -> Subscriptions {
+        state::load(SUBSCRIPTIONS_FILE)
+    }
+
+    pub fn save(&self) {
+        state::save(SUBSCRIPTIONS_FILE, self);
+    }
+
+    /// Subscribe a user, returning false if they were already subscribed.
+    pub fn subscribe(
+        &mut self,
+        discord_id: u64,
+        pattern: &str,
+        regex: bool,
+    ) -> Result<bool, String> {
+        let subscription = Subscription::new(pattern, regex)?;
+        let subscriptions = self.subscriptions.entry(discord_id).or_default();
+        if subscriptions.contains(&subscription) {
+            return Ok(false);
+        }
+        subscriptions.push(subscription);
+        self.save();
+        Ok(true)
+    }
+
+    /// Unsubscribe a user, returning false if they were not subscribed.
+    pub fn unsubscribe(&mut self, discord_id: u64, pattern: &str) -> bool {
+        let Some(subscriptions) = self.subscriptions.get_mut(&discord_id)
+        else {
+            return false;
+        };
+
+        let count = subscriptions.len();
+        subscriptions.retain(|s| s.pattern != pattern);
+        let removed = subscriptions.len() != count;
+        if subscriptions.is_empty() {
+            self.subscriptions.remove(&discord_id);
+        }
+        if removed {
+            self.save();
+        }
+        removed
+    }
+
+    /// Get the subscriptions of a user
+    pub fn get(&self, discord_id: u64) -> Vec<Subscription> {
+        self.subscriptions
+            .get(&discord_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Get the users subscribed to a project
+    pub fn subscribers(&self, project: &str) -> Vec<u64> {
+        let mut users: Vec<u64> = self
+            .subscriptions
+            .iter()
+            .filter(|(_, subs)| subs.iter().any(|s| s.matches(project)))
+            .map(|(id, _)| *id)
+            .collect();
+        users.sort();
+        users
+    }
+
+    /// Check if a notification may be sent.  Each (user, change, state) is
+    /// only notified once and users are limited to
+    /// MAX_NOTIFICATIONS_PER_HOUR.
+    pub fn may_notify(
+        &mut self,
+        discord_id: u64,
+        key: &str,
+        now: DateTime<Utc>,
+    ) -> bool {
+        self.notified.retain(|_, sent| {
+            now - *sent < Duration::days(NOTIFICATION_MEMORY_DAYS)
+        });
+        if self
+            .notified
+            .contains_key(&format!("{}:{}", discord_id, key))
+        {
+            return false;
+        }
+
+        let recent = self.recent.entry(discord_id).or_default();
+        recent.retain(|sent| now - *sent < Duration::hours(1));
+        recent.len() < MAX_NOTIFICATIONS_PER_HOUR
+    }
+
+    /// Record a notification once it was sent
+    pub fn record_notification(
+        &mut self,
+        discord_id: u64,
+        key: &str,
+        now: DateTime<Utc>,
+    ) {
+        self.recent.entry(discord_id).or_default().push(now);
+        self.notified.insert(format!("{}:{}", discord_id, key), now);
+        self.save();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn regex_subscriptions_survive_saving() {
+        let subscriptions: Vec<Subscription> = serde_json::from_str(
+            r#"[{"pattern": "openbmc/phosphor-.*", "regex": true},
+                {"pattern": "openbmc/bmcweb"}]"#,
+        )
+        .unwrap();
+        assert!(subscriptions[0].matches("openbmc/phosphor-networkd"));
+        assert!(!subscriptions[0].matches("openbmc/bmcweb"));
+        assert!(subscriptions[1].matches("openbmc/bmcweb"));
+        assert_eq!(
+            serde_json::to_value(&subscriptions).unwrap(),
+            serde_json::json!([
+                {"pattern": "openbmc/phosphor-.*", "regex": true},
+                {"pattern": "openbmc/bmcweb", "regex": false},
+            ])
+        );
+    }
+
+    #[test]
+    fn only_sent_notifications_count_towards_the_limit() {
+        let mut subscriptions = Subscriptions::default();
+        let now = Utc::now();
+        for id in 0..2 * MAX_NOTIFICATIONS_PER_HOUR {
+            let key = format!("{}:CommunityReview", id);
+            assert!(subscriptions.may_notify(1, &key, now));
+        }
+
+        subscriptions
+            .recent
+            .insert(1, vec![now; MAX_NOTIFICATIONS_PER_HOUR]);
+        assert!(!subscriptions.may_notify(1, "1:CommunityReview", now));
+        assert!(subscriptions.may_notify(2, "1:CommunityReview", now));
+        assert!(subscriptions.may_notify(
+            1,
+            "1:CommunityReview",
+            now + Duration::hours(1)
+        ));
+    }
+}
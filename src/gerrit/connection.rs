@@ -1,42 +1,65 @@
+use crate::config;
 use crate::gerrit::data as gerrit_data;
+use base64::Engine;
 use serde_json;
+use std::collections::HashMap;
 use std::fmt;
 use tokio::time::{self, Duration};
-use tracing::{error, info};
+use tracing::{debug, error, info, warn};
 
 /* Gerrit JSON responses have a magic at the beginning that needs to be
  * stripped. */
 fn prune_magic(text: String) -> Option<String> {
     const MAGIC_PREFIX: &str = ")]}'";
-    if text.starts_with(MAGIC_PREFIX) {
-        return Some(text[MAGIC_PREFIX.len()..].to_string());
-    }
-    None
+    text.strip_prefix(MAGIC_PREFIX).map(str::to_string)
 }
 
 #[async_trait::async_trait]
 pub trait GerritConnection {
     fn get_username(&self) -> String;
     fn get_password(&self) -> String;
+    /// Send a request, retrying until Gerrit gives a JSON response.  A
+    /// status accepted by `acceptable_status` ends the retries with an
+    /// error giving the status and response body.
     async fn execute_request<F>(
         &self,
         request: reqwest::RequestBuilder,
         acceptable_status: F,
-    ) -> String
+    ) -> Result<String, String>
     where
         F: Fn(reqwest::StatusCode) -> bool + Send;
     async fn all_open_changes(&self) -> Vec<gerrit_data::ChangeInfo>;
     async fn recent_changes(&self) -> Vec<gerrit_data::ChangeInfo>;
+    async fn query_changes(
+        &self,
+        query: &str,
+        options: &[&str],
+    ) -> Result<Vec<gerrit_data::ChangeInfo>, String>;
     async fn abandon_change(
         &self,
         change_id: &str,
         message: String,
     ) -> Option<gerrit_data::ChangeInfo>;
+    async fn post_review(
+        &self,
+        change_id: &str,
+        message: String,
+        labels: HashMap<String, i64>,
+    ) -> Result<(), String>;
+    async fn add_reviewer(&self, change_id: &str, reviewer: &str) -> bool;
+    async fn file_content(
+        &self,
+        project: &str,
+        branch: &str,
+        path: &str,
+    ) -> Option<String>;
 }
 
 pub struct Connection {
     username: String,
     password: String,
+    /// Never send requests, failing every one
+    offline: bool,
 }
 
 impl Clone for Connection {
@@ -44,6 +67,7 @@ impl Clone for Connection {
         Connection {
             username: self.username.clone(),
             password: self.password.clone(),
+            offline: self.offline,
         }
     }
 }
@@ -53,10 +77,25 @@ impl fmt::Debug for Connection {
         f.debug_struct("Connection")
             .field("username", &self.username)
             .field("password", &"xxxxxxxx")
+            .field("offline", &self.offline)
             .finish()
     }
 }
 
+impl Connection {
+    /// Fetch every open change, as the JSON array returned by `/changes/`
+    pub async fn open_changes_json(&self) -> String {
+        self.execute_request(
+            reqwest::Client::new().get(
+                "https://gerrit.openbmc.org/a/changes/?q=status:open+-is:wip&o=LABELS&o=DETAILED_LABELS&o=DETAILED_ACCOUNTS&o=CURRENT_REVISION&o=CURRENT_FILES&no-limit",
+            ),
+            |_| false,
+        )
+        .await
+        .unwrap_or_default()
+    }
+}
+
 #[async_trait::async_trait]
 impl GerritConnection for Connection {
     fn get_username(&self) -> String {
@@ -71,10 +110,15 @@ impl GerritConnection for Connection {
         &self,
         request: reqwest::RequestBuilder,
         acceptable_status: F,
-    ) -> String
+    ) -> Result<String, String>
     where
         F: Fn(reqwest::StatusCode) -> bool + Send,
     {
+        if self.offline {
+            debug!("Offline, not sending request");
+            return Err("Offline, request not sent".to_string());
+        }
+
         // Clone the request builder for retries
         let request_factory = || {
             request
@@ -101,13 +145,12 @@ impl GerritConnection for Connection {
                             "Request failed with exception status {}: {}",
                             status, error_text
                         );
-                        // For exception statuses, return empty string
-                        return String::new();
+                        return Err(format!("{}: {}", status, error_text));
                     }
 
                     let text = response.text().await.unwrap();
                     if let Some(pruned) = prune_magic(text) {
-                        return pruned;
+                        return Ok(pruned);
                     }
                     // If we don't get the magic prefix, wait 10 seconds and retry
                     time::sleep(Duration::from_secs(10)).await;
@@ -121,20 +164,15 @@ impl GerritConnection for Connection {
     }
 
     async fn all_open_changes(&self) -> Vec<gerrit_data::ChangeInfo> {
-        let result = self
-            .execute_request(
-                reqwest::Client::new().get(
-                    "https://gerrit.openbmc.org/a/changes/?q=status:open+-is:wip&o=LABELS&o=DETAILED_ACCOUNTS&o=CURRENT_REVISION&o=CURRENT_FILES&no-limit",
-                ),
-                |_| false,
-            )
-            .await;
+        let result = self.open_changes_json().await;
 
         serde_json::from_str::<Vec<gerrit_data::ChangeInfoRaw>>(&result)
-            .expect(&format!(
-                "Failed to parse JSON response for all_open_changes. Response content: {}",
-                result
-            ))
+            .unwrap_or_else(|_| {
+                panic!(
+                    "Failed to parse JSON response for all_open_changes. Response content: {}",
+                    result
+                )
+            })
             .into_iter()
             .map(Into::into)
             .collect()
@@ -144,22 +182,63 @@ impl GerritConnection for Connection {
         let result = self
             .execute_request(
                 reqwest::Client::new().get(
-                    "https://gerrit.openbmc.org/a/changes/?q=-age:4h&o=LABELS&o=DETAILED_ACCOUNTS&o=CURRENT_REVISION&o=CURRENT_FILES&no-limit",
+                    "https://gerrit.openbmc.org/a/changes/?q=-age:4h&o=LABELS&o=DETAILED_LABELS&o=DETAILED_ACCOUNTS&o=CURRENT_REVISION&o=CURRENT_FILES&no-limit",
                 ),
                 |_| false,
             )
-            .await;
+            .await
+            .unwrap_or_default();
 
         serde_json::from_str::<Vec<gerrit_data::ChangeInfoRaw>>(&result)
-            .expect(&format!(
-                "Failed to parse JSON response for recent_changes. Response content: {}",
-                result
-            ))
+            .unwrap_or_else(|_| {
+                panic!(
+                    "Failed to parse JSON response for recent_changes. Response content: {}",
+                    result
+                )
+            })
             .into_iter()
             .map(Into::into)
             .collect()
     }
 
+    async fn query_changes(
+        &self,
+        query: &str,
+        options: &[&str],
+    ) -> Result<Vec<gerrit_data::ChangeInfo>, String> {
+        let mut url = format!(
+            "https://gerrit.openbmc.org/a/changes/?q={}",
+            urlencoding::encode(query).replace("%20", "+")
+        );
+        for option in options {
+            url += &format!("&o={}", option);
+        }
+
+        // Gerrit rejects invalid queries, such as an unknown user, with a
+        // client error which retrying cannot fix
+        let result = self
+            .execute_request(reqwest::Client::new().get(&url), |status| {
+                status.is_client_error()
+            })
+            .await
+            .map_err(|e| {
+                let e = format!("Query {} failed: {}", query, e);
+                error!("{}", e);
+                e
+            })?;
+
+        serde_json::from_str::<Vec<gerrit_data::ChangeInfoRaw>>(&result)
+            .map(|changes| changes.into_iter().map(Into::into).collect())
+            .map_err(|e| {
+                let e = format!(
+                    "Failed to parse JSON response for query {}: {}",
+                    query, e
+                );
+                error!("{}", e);
+                e
+            })
+    }
+
     async fn abandon_change(
         &self,
         change_id: &str,
@@ -185,28 +264,164 @@ impl GerritConnection for Connection {
             )
             .await;
 
-        // If we got an empty string, it means we hit an exception status (409)
-        if result.is_empty() {
-            error!("Failed to abandon change {} due to conflict", change_id);
-            return None;
-        }
+        let result = match result {
+            Ok(result) => result,
+            Err(e) => {
+                error!("Failed to abandon change {}: {}", change_id, e);
+                return None;
+            }
+        };
 
         Some(
             serde_json::from_str::<gerrit_data::ChangeInfoRaw>(&result)
-                .expect(&format!(
-                    "Failed to parse JSON response for abandon_change. Response content: {}",
-                    result
-                ))
+                .unwrap_or_else(|_| {
+                    panic!(
+                        "Failed to parse JSON response for abandon_change. Response content: {}",
+                        result
+                    )
+                })
                 .into(),
         )
     }
+
+    async fn post_review(
+        &self,
+        change_id: &str,
+        message: String,
+        labels: HashMap<String, i64>,
+    ) -> Result<(), String> {
+        let url = format!(
+            "https://gerrit.openbmc.org/a/changes/{}/revisions/current/review",
+            change_id
+        );
+
+        let request_body = serde_json::json!({
+            "message": message,
+            "labels": labels,
+        });
+
+        // Closed changes can no longer be reviewed and return HTTP 409.
+        // Invalid labels or votes are rejected with HTTP 400, votes the bot
+        // may not cast with HTTP 403 and deleted changes with HTTP 404.
+        self.execute_request(
+            reqwest::Client::new()
+                .post(&url)
+                .header("Content-Type", "application/json")
+                .json(&request_body),
+            |status| {
+                status == reqwest::StatusCode::BAD_REQUEST
+                    || status == reqwest::StatusCode::FORBIDDEN
+                    || status == reqwest::StatusCode::NOT_FOUND
+                    || status == reqwest::StatusCode::CONFLICT
+            },
+        )
+        .await
+        .map(|_| ())
+        .map_err(|e| {
+            error!("Failed to review change {}: {}", change_id, e);
+            e
+        })
+    }
+
+    async fn add_reviewer(&self, change_id: &str, reviewer: &str) -> bool {
+        let url = format!(
+            "https://gerrit.openbmc.org/a/changes/{}/reviewers",
+            change_id
+        );
+
+        let request_body = serde_json::json!({ "reviewer": reviewer });
+
+        // Unknown reviewers are rejected with HTTP 400 or 422, closed changes
+        // with HTTP 409
+        let result = self
+            .execute_request(
+                reqwest::Client::new()
+                    .post(&url)
+                    .header("Content-Type", "application/json")
+                    .json(&request_body),
+                |status| {
+                    status == reqwest::StatusCode::BAD_REQUEST
+                        || status == reqwest::StatusCode::UNPROCESSABLE_ENTITY
+                        || status == reqwest::StatusCode::CONFLICT
+                },
+            )
+            .await;
+
+        if let Err(e) = result {
+            error!(
+                "Failed to add reviewer {} to {}: {}",
+                reviewer, change_id, e
+            );
+            return false;
+        }
+        true
+    }
+
+    async fn file_content(
+        &self,
+        project: &str,
+        branch: &str,
+        path: &str,
+    ) -> Option<String> {
+        let url = format!(
+            "https://gerrit.openbmc.org/a/projects/{}/branches/{}/files/{}/content",
+            urlencoding::encode(project),
+            urlencoding::encode(branch),
+            urlencoding::encode(path)
+        );
+
+        // The content endpoint returns base64 text rather than JSON, so it
+        // has no magic prefix and cannot go through execute_request.
+        let response = match reqwest::Client::new()
+            .get(&url)
+            .basic_auth(self.get_username(), Some(self.get_password()))
+            .send()
+            .await
+        {
+            Ok(response) => response,
+            Err(e) => {
+                warn!("Failed to fetch {}: {}", url, e);
+                return None;
+            }
+        };
+
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return None;
+        }
+        if !response.status().is_success() {
+            warn!("Failed to fetch {}: {}", url, response.status());
+            return None;
+        }
+
+        let text = response.text().await.ok()?;
+        let content = base64::engine::general_purpose::STANDARD
+            .decode(text.trim())
+            .map_err(|e| warn!("Invalid base64 content from {}: {}", url, e))
+            .ok()?;
+        String::from_utf8(content).ok()
+    }
+}
+
+/// A connection which never talks to Gerrit, for working from saved data
+pub fn offline() -> Connection {
+    Connection {
+        username: String::new(),
+        password: String::new(),
+        offline: true,
+    }
 }
 
+/// A connection using the credentials from the configuration, which must
+/// have been checked at startup
 pub fn new() -> Connection {
+    let (username, password) =
+        config::get().gerrit.credentials().unwrap_or_else(|e| {
+            error!("{}", e);
+            Default::default()
+        });
     Connection {
-        username: std::env::var("GERRIT_USERNAME")
-            .expect("GERRIT_USERNAME must be set"),
-        password: std::env::var("GERRIT_PASSWORD")
-            .expect("GERRIT_PASSWORD must be set"),
+        username,
+        password,
+        offline: false,
     }
 }
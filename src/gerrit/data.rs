@@ -7,18 +7,38 @@ use std::ops::Deref;
 pub struct AccountInfo {
     #[serde(default = "default_username")]
     pub username: String,
+    #[serde(default)]
+    pub email: String,
 }
 
 fn default_username() -> String {
     "unknown".to_string()
 }
 
+/// Parse a Gerrit timestamp ("2013-02-01 09:59:32.126000000", always UTC).
+pub fn parse_timestamp(timestamp: &str) -> Option<DateTime<Utc>> {
+    NaiveDateTime::parse_from_str(timestamp, "%Y-%m-%d %H:%M:%S%.f")
+        .ok()
+        .map(|t| DateTime::from_naive_utc_and_offset(t, Utc))
+}
+
 #[derive(Deserialize, Clone, Debug)]
 pub struct ApprovalInfo {
     #[serde(default = "default_username")]
     pub username: String,
     #[serde(default)]
+    pub email: String,
+    #[serde(default)]
     pub value: i64,
+    #[serde(default)]
+    pub date: Option<String>,
+}
+
+impl ApprovalInfo {
+    /// When the vote was cast, if Gerrit reported it.
+    pub fn date(&self) -> Option<DateTime<Utc>> {
+        self.date.as_deref().and_then(parse_timestamp)
+    }
 }
 
 #[derive(Deserialize, Debug)]
@@ -69,6 +89,15 @@ pub enum ChangeStatus {
     Abandoned,
 }
 
+#[derive(Deserialize, Debug, Clone)]
+pub struct ChangeMessageInfo {
+    #[serde(default)]
+    pub author: Option<AccountInfo>,
+    #[serde(default)]
+    pub message: String,
+    pub date: String,
+}
+
 #[derive(Deserialize, Debug, Clone)]
 pub struct FileInfo {}
 
@@ -86,6 +115,8 @@ pub struct ChangeInfoRaw {
     pub branch: String,
     #[serde(default)]
     pub topic: String,
+    #[serde(default)]
+    pub hashtags: Vec<String>,
 
     pub subject: String,
     pub owner: AccountInfo,
@@ -112,6 +143,8 @@ pub struct ChangeInfoRaw {
     pub current_revision: String,
     #[serde(default)]
     pub revisions: HashMap<String, RevisionInfo>,
+    #[serde(default)]
+    pub messages: Vec<ChangeMessageInfo>,
 }
 
 #[derive(Debug, Clone)]
@@ -122,6 +155,7 @@ pub struct ChangeInfo {
     pub project: String,
     pub branch: String,
     pub topic: String,
+    pub hashtags: Vec<String>,
 
     pub subject: String,
     pub owner: AccountInfo,
@@ -141,6 +175,7 @@ pub struct ChangeInfo {
 
     pub current_revision: String,
     pub revisions: HashMap<String, RevisionInfo>,
+    pub messages: Vec<ChangeMessageInfo>,
 }
 
 impl From<ChangeInfoRaw> for ChangeInfo {
@@ -152,24 +187,11 @@ impl From<ChangeInfoRaw> for ChangeInfo {
             project: raw.project,
             branch: raw.branch,
             topic: raw.topic,
+            hashtags: raw.hashtags,
             subject: raw.subject,
             owner: raw.owner,
-            created: DateTime::from_naive_utc_and_offset(
-                NaiveDateTime::parse_from_str(
-                    &raw.created,
-                    "%Y-%m-%d %H:%M:%S%.f",
-                )
-                .unwrap(),
-                Utc,
-            ),
-            updated: DateTime::from_naive_utc_and_offset(
-                NaiveDateTime::parse_from_str(
-                    &raw.updated,
-                    "%Y-%m-%d %H:%M:%S%.f",
-                )
-                .unwrap(),
-                Utc,
-            ),
+            created: parse_timestamp(&raw.created).unwrap(),
+            updated: parse_timestamp(&raw.updated).unwrap(),
             status: raw.status,
             work_in_progress: raw.work_in_progress,
             mergeable: raw.mergeable,
@@ -184,6 +206,44 @@ impl From<ChangeInfoRaw> for ChangeInfo {
             submit_records: raw.submit_records.clone(),
             current_revision: raw.current_revision,
             revisions: raw.revisions.clone(),
+            messages: raw.messages,
         }
     }
 }
+
+/// Build an open change as Gerrit's JSON would describe it, with `fields`
+/// replacing the defaults, e.g.
+/// `json!({"updated": "2025-01-01 00:00:00.000000000"})`
+#[cfg(test)]
+pub fn test_change_json(
+    id_number: u64,
+    fields: serde_json::Value,
+) -> serde_json::Value {
+    let mut change = serde_json::json!({
+        "id": format!("openbmc%2Fbmcweb~master~I{}", id_number),
+        "change_id": format!("I{}", id_number),
+        "_number": id_number,
+        "project": "openbmc/bmcweb",
+        "branch": "master",
+        "subject": format!("Change {}", id_number),
+        "owner": {"username": "alice"},
+        "created": "2025-01-01 00:00:00.000000000",
+        "updated": "2025-01-01 00:00:00.000000000",
+        "status": "NEW",
+        "insertions": 1,
+        "deletions": 1,
+    });
+    for (key, value) in fields.as_object().unwrap() {
+        change[key] = value.clone();
+    }
+    change
+}
+
+/// Build an open change as Gerrit would return it, with `fields` replacing
+/// the defaults as in `test_change_json`
+#[cfg(test)]
+pub fn test_change(id_number: u64, fields: serde_json::Value) -> ChangeInfo {
+    serde_json::from_value::<ChangeInfoRaw>(test_change_json(id_number, fields))
+        .unwrap()
+        .into()
+}
@@ -0,0 +1,94 @@
+use crate::gerrit::connection::GerritConnection;
+use crate::gerrit::data::ChangeInfo;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// A Gerrit connection answering every query with the same result and
+/// recording the queries.  Other requests are not expected.
+pub struct StubGerrit {
+    result: Result<Vec<ChangeInfo>, String>,
+    queries: Mutex<Vec<String>>,
+}
+
+impl StubGerrit {
+    pub fn new(result: Result<Vec<ChangeInfo>, String>) -> StubGerrit {
+        StubGerrit {
+            result,
+            queries: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Get the queries made so far
+    pub fn queries(&self) -> Vec<String> {
+        self.queries.lock().unwrap().clone()
+    }
+}
+
+#[async_trait::async_trait]
+impl GerritConnection for StubGerrit {
+    fn get_username(&self) -> String {
+        "bot".to_string()
+    }
+
+    fn get_password(&self) -> String {
+        String::new()
+    }
+
+    async fn execute_request<F>(
+        &self,
+        _request: reqwest::RequestBuilder,
+        _acceptable_status: F,
+    ) -> Result<String, String>
+    where
+        F: Fn(reqwest::StatusCode) -> bool + Send,
+    {
+        unimplemented!("execute_request")
+    }
+
+    async fn all_open_changes(&self) -> Vec<ChangeInfo> {
+        unimplemented!("all_open_changes")
+    }
+
+    async fn recent_changes(&self) -> Vec<ChangeInfo> {
+        unimplemented!("recent_changes")
+    }
+
+    async fn query_changes(
+        &self,
+        query: &str,
+        _options: &[&str],
+    ) -> Result<Vec<ChangeInfo>, String> {
+        self.queries.lock().unwrap().push(query.to_string());
+        self.result.clone()
+    }
+
+    async fn abandon_change(
+        &self,
+        _change_id: &str,
+        _message: String,
+    ) -> Option<ChangeInfo> {
+        unimplemented!("abandon_change")
+    }
+
+    async fn post_review(
+        &self,
+        _change_id: &str,
+        _message: String,
+        _labels: HashMap<String, i64>,
+    ) -> Result<(), String> {
+        unimplemented!("post_review")
+    }
+
+    async fn add_reviewer(&self, _change_id: &str, _reviewer: &str) -> bool {
+        unimplemented!("add_reviewer")
+    }
+
+    async fn file_content(
+        &self,
+        _project: &str,
+        _branch: &str,
+        _path: &str,
+    ) -> Option<String> {
+        unimplemented!("file_content")
+    }
+}
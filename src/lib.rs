@@ -1,19 +1,52 @@
+pub mod audit;
 pub mod changes {
+    pub mod assignment;
+    pub mod backtest;
     pub mod container;
+    pub mod exemptions;
+    pub mod export;
     pub mod filter;
+    pub mod owners;
+    pub mod patterns;
+    pub mod policy;
     pub mod report;
+    pub mod retrigger;
+    pub mod reviewers;
     pub mod serve;
+    pub mod snapshot;
     pub mod status;
+    pub mod trends;
 }
+pub mod chat {
+    pub mod backend;
+    pub mod commands;
+    pub mod matrix;
+    pub mod notifier;
+    pub mod reminders;
+    pub mod selection;
+    pub mod serve;
+    #[cfg(test)]
+    pub mod stub;
+    pub mod webhook;
+}
+pub mod config;
 pub mod context;
 pub mod discord {
+    pub mod backend;
+    pub mod claims;
+    pub mod links;
     pub mod serve;
+    pub mod subscriptions;
 }
 pub mod gerrit {
     pub mod connection;
     pub mod data;
+    #[cfg(test)]
+    pub mod stub;
 }
+pub mod state;
 pub mod webserver {
+    pub mod charts;
     pub mod serve;
     pub mod templates;
 }
@@ -1,28 +1,330 @@
-use clap::Parser;
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser, Subcommand};
 use dotenv::dotenv;
+use gerrit_faster::changes::assignment;
+use gerrit_faster::changes::assignment::AssignmentConfig;
+use gerrit_faster::changes::backtest;
+use gerrit_faster::changes::exemptions::Exemptions;
+use gerrit_faster::changes::export;
+use gerrit_faster::changes::policy::PoliciesConfig;
+use gerrit_faster::changes::report::{
+    ChangeFilter, GroupBy, ReportFormat, report_by_group, report_by_time,
+};
+use gerrit_faster::changes::retrigger;
+use gerrit_faster::changes::retrigger::RetriggerConfig;
 use gerrit_faster::changes::serve as changes;
+use gerrit_faster::changes::snapshot::{self, Snapshot};
+use gerrit_faster::changes::status::{NextStepOwner, review_state};
+use gerrit_faster::chat::notifier::NotificationsConfig;
+use gerrit_faster::chat::reminders::RemindersConfig;
+use gerrit_faster::chat::serve as chat;
+use gerrit_faster::config;
 use gerrit_faster::context::ServiceContext;
 use gerrit_faster::discord::serve as discord;
+use gerrit_faster::gerrit::data::ChangeStatus;
 use gerrit_faster::webserver::serve as webserver;
-use tracing::{Level, info};
+use tracing::{Level, error, info};
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
 struct Args {
-    /// Port to run the webserver on
-    #[clap(short, long, default_value_t = 3000)]
-    port: u16,
     /// Debug mode
-    #[clap(short, long, default_value_t = false)]
+    #[clap(short, long, global = true, default_value_t = false)]
     debug: bool,
+    /// YAML or TOML configuration file, instead of GERRIT_FASTER_CONFIG
+    #[clap(short, long, global = true)]
+    config: Option<String>,
+    #[clap(flatten)]
+    serve: ServeArgs,
+    #[clap(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+struct ServeArgs {
+    /// Port to run the webserver on, instead of webserver.port
+    #[clap(short, long)]
+    port: Option<u16>,
     /// Disable the Discord bot
     #[clap(long, default_value_t = false)]
     disable_discord: bool,
+    /// Serve the changes saved by `capture`, or any `/changes/` response,
+    /// without polling Gerrit or running the automation
+    #[clap(long)]
+    offline: Option<String>,
+}
+
+impl ServeArgs {
+    fn is_set(&self) -> bool {
+        self.port.is_some() || self.disable_discord || self.offline.is_some()
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Run the service: poll Gerrit, serve the web UI and run the bots.
+    /// This is the default without a subcommand.
+    Serve(ServeArgs),
+    /// Fetch the open changes once and print a report
+    Report {
+        /// Group by repo, branch or topic instead of by age
+        #[clap(long)]
+        group_by: Option<GroupBy>,
+        /// Only include changes to this project
+        #[clap(long)]
+        project: Option<String>,
+        /// Only include changes owned by this user
+        #[clap(long)]
+        owner: Option<String>,
+        /// Output format: text, markdown, csv or json
+        #[clap(long, default_value = "text")]
+        format: ReportFormat,
+    },
+    /// Fetch the open changes once and print one row per change
+    Export {
+        /// Output format: json or csv
+        #[clap(long, default_value = "json")]
+        format: ReportFormat,
+    },
+    /// Print the review state of a change and what it is waiting for
+    Status {
+        /// Change number or Change-Id
+        change: String,
+    },
+    /// Validate the configuration files named by the environment
+    CheckConfig,
+    /// Save the open changes from Gerrit to a file for `serve --offline`
+    /// and `backtest`
+    Capture {
+        /// File to write the snapshot to
+        file: String,
+    },
+    /// Replay snapshots through the abandonment policies and reminders,
+    /// printing what would have happened each day
+    Backtest {
+        /// Snapshots saved by `capture`, taken at different times
+        #[clap(required = true)]
+        files: Vec<String>,
+        /// Days to continue after the last snapshot
+        #[clap(long, default_value_t = 30)]
+        days: i64,
+        /// Abandonment policies to use instead of ABANDON_POLICIES_CONFIG
+        #[clap(long)]
+        policies: Option<String>,
+        /// Reminder jobs to use instead of DISCORD_REMINDERS_CONFIG
+        #[clap(long)]
+        reminders: Option<String>,
+    },
+}
+
+// Create a context for talking to Gerrit, once its credentials are known to
+// be configured.
+fn connect() -> Result<ServiceContext, String> {
+    config::get().gerrit.credentials()?;
+    Ok(ServiceContext::new())
+}
+
+// Connect and fetch the open changes once.
+async fn fetch() -> Result<ServiceContext, String> {
+    let context = connect()?;
+    changes::fetch_open_changes(&context).await;
+    Ok(context)
+}
+
+// Check that the Discord bot can start, if it is enabled.
+fn check_discord(enabled: bool) -> Result<(), String> {
+    if enabled && config::get().discord.token.is_none() {
+        return Err("Discord token not set: use discord.token, \
+                    discord.token_file, DISCORD_TOKEN or DISCORD_TOKEN_FILE, \
+                    or disable Discord"
+            .to_string());
+    }
+    Ok(())
+}
+
+// Serve the web UI and Discord commands from a snapshot, leaving Gerrit and
+// the chat automation alone.
+async fn serve_offline(
+    path: &str,
+    port: u16,
+    enable_discord: bool,
+) -> Result<(), String> {
+    let snapshot = Snapshot::from_file(path)?;
+    let context = ServiceContext::offline();
+    snapshot.load_into(&context);
+    info!(
+        "Offline mode: serving {} changes from {}, Gerrit polling disabled",
+        snapshot.changes.len(),
+        path
+    );
+
+    let mut handles =
+        vec![tokio::spawn(webserver::serve(context.clone(), port))];
+    if enable_discord {
+        handles.push(tokio::spawn(discord::serve(context.clone())));
+    }
+
+    for handle in handles {
+        handle.await.unwrap();
+    }
+    Ok(())
+}
+
+// Run the service until it is stopped.
+async fn serve(args: &ServeArgs) -> Result<(), String> {
+    let config = config::get();
+    let enable_discord = config.discord.enabled && !args.disable_discord;
+    let port = args.port.unwrap_or(config.webserver.port);
+    check_discord(enable_discord)?;
+    if let Some(path) = &args.offline {
+        return serve_offline(path, port, enable_discord).await;
+    }
+
+    // Fail now rather than run with some automation silently disabled
+    let mut valid = true;
+    for (name, result) in check_config(enable_discord) {
+        if let Err(e) = result {
+            error!("Invalid {}: {}", name, e);
+            valid = false;
+        }
+    }
+    if !valid {
+        return Err("Invalid configuration, see check-config".to_string());
+    }
+    let context = connect()?;
+    info!("Service ServiceContext: {:?}", context);
+
+    let backends = chat::backends(enable_discord);
+
+    let mut handles = vec![
+        tokio::spawn(webserver::serve(context.clone(), port)),
+        tokio::spawn(changes::serve(context.clone())),
+        tokio::spawn(assignment::serve(context.clone())),
+        tokio::spawn(retrigger::serve(context.clone(), backends.clone())),
+        tokio::spawn(chat::serve(context.clone(), backends)),
+    ];
+
+    if enable_discord {
+        handles.push(tokio::spawn(discord::serve(context.clone())));
+    }
+
+    for handle in handles {
+        handle.await.unwrap();
+    }
+    Ok(())
+}
+
+// Describe a change's review state, fetched fresh from Gerrit.
+async fn run_status(change_id: &str) -> Result<String, String> {
+    let context = connect()?;
+    let change =
+        changes::fetch_change(&context.get_gerrit(), change_id).await?;
+
+    let mut text = format!(
+        "Change {}: {}\n{}\nOwner: {}\n",
+        change.id_number, change.project, change.subject, change.owner.username
+    );
+    if change.status != ChangeStatus::New {
+        text += &format!("The change is {:?}.\n", change.status);
+        if change.status == ChangeStatus::Abandoned
+            && let Some(abandonment) =
+                context.lock().unwrap().abandonments.get(change.id_number)
+        {
+            text += &format!("{}.\n", abandonment.description());
+        }
+        return Ok(text);
+    }
+    if change.work_in_progress {
+        text += "The change is a work in progress.\n";
+        return Ok(text);
+    }
+
+    let state = review_state(&change);
+    text += &format!(
+        "State: {:?}\nNext step: {:?}\n{}\n",
+        state,
+        NextStepOwner::from(state.clone()),
+        state.explanation()
+    );
+    for exemption in context.lock().unwrap().exemptions.matching(&change) {
+        text += &format!(
+            "Exempt from {} ({})\n",
+            exemption
+                .exempt_from
+                .iter()
+                .map(|automation| automation.name())
+                .collect::<Vec<_>>()
+                .join(", "),
+            exemption.name
+        );
+    }
+    Ok(text)
+}
+
+// Save the open changes from Gerrit to a file.
+async fn run_capture(file: &str) -> Result<String, String> {
+    let context = connect()?;
+    let count = snapshot::capture(&context, file).await?;
+    Ok(format!("Saved {} changes to {}\n", count, file))
+}
+
+// Load every configuration file, returning the errors found.
+fn check_config(
+    enable_discord: bool,
+) -> Vec<(&'static str, Result<(), String>)> {
+    let config = config::get();
+    vec![
+        (
+            "gerrit credentials",
+            config.gerrit.credentials().map(|_| ()),
+        ),
+        ("discord token", check_discord(enable_discord)),
+        ("abandonment policies", PoliciesConfig::load().map(|_| ())),
+        ("exemptions", Exemptions::load().map(|_| ())),
+        ("reminders", RemindersConfig::load().map(|_| ())),
+        ("notifications", NotificationsConfig::load().map(|_| ())),
+        ("reviewer assignment", AssignmentConfig::load().map(|_| ())),
+        ("CI retrigger", RetriggerConfig::load().map(|_| ())),
+    ]
+}
+
+// Run the backtest subcommand, returning the text to print.
+fn run_backtest(
+    files: &[String],
+    days: i64,
+    policies: Option<&str>,
+    reminders: Option<&str>,
+) -> Result<String, String> {
+    let snapshots = files
+        .iter()
+        .map(|file| Snapshot::from_file(file))
+        .collect::<Result<Vec<_>, _>>()?;
+    let policies = match policies {
+        Some(path) => PoliciesConfig::from_file(path)?,
+        None => PoliciesConfig::load()?,
+    };
+    let reminders = match reminders {
+        Some(path) => RemindersConfig::from_file(path)?,
+        None => RemindersConfig::load()?,
+    };
+    let reports = backtest::run(snapshots, &policies, &reminders, days);
+    Ok(backtest::report_text(&reports))
 }
 
 #[tokio::main(flavor = "current_thread")]
 async fn main() {
     let args = Args::parse();
+    // The serve flags are accepted without a subcommand, but clap also lets
+    // them through before any other subcommand, where they would be ignored
+    if args.command.is_some() && args.serve.is_set() {
+        Args::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "--port, --disable-discord and --offline must follow `serve`, \
+                 or be used without a subcommand",
+            )
+            .exit();
+    }
 
     tracing_subscriber::fmt()
         .with_max_level(if args.debug {
@@ -30,23 +332,81 @@ async fn main() {
         } else {
             Level::INFO
         })
+        .with_writer(std::io::stderr)
         .init();
 
     dotenv().ok();
 
-    let context = ServiceContext::new();
-    info!("Service ServiceContext: {:?}", context);
-
-    let mut handles = vec![
-        tokio::spawn(webserver::serve(context.clone(), args.port)),
-        tokio::spawn(changes::serve(context.clone())),
-    ];
-
-    if !args.disable_discord {
-        handles.push(tokio::spawn(discord::serve(context.clone())));
+    if let Err(e) = config::init(args.config.as_deref()) {
+        eprintln!("Invalid configuration: {}", e);
+        std::process::exit(1);
     }
 
-    for handle in handles {
-        handle.await.unwrap();
+    let command = args.command.unwrap_or(Command::Serve(args.serve));
+    let result = match command {
+        Command::Serve(serve_args) => {
+            serve(&serve_args).await.map(|_| String::new())
+        }
+        Command::Report {
+            group_by,
+            project,
+            owner,
+            format,
+        } => fetch().await.map(|context| {
+            let filter = ChangeFilter {
+                project,
+                owner,
+                ..Default::default()
+            };
+            let report = match group_by {
+                Some(group_by) => report_by_group(
+                    &context,
+                    &filter,
+                    group_by,
+                    format,
+                    None::<fn(&str) -> String>,
+                ),
+                None => report_by_time(&context, &filter, format),
+            };
+            report + "\n"
+        }),
+        Command::Export { format } => fetch()
+            .await
+            .map(|context| export::export(&context, format) + "\n"),
+        Command::Status { change } => run_status(&change).await,
+        Command::Capture { file } => run_capture(&file).await,
+        Command::CheckConfig => {
+            let results = check_config(config::get().discord.enabled);
+            for (name, result) in &results {
+                match result {
+                    Ok(()) => println!("ok      {}", name),
+                    Err(e) => println!("FAILED  {}: {}", name, e),
+                }
+            }
+            if results.iter().any(|(_, result)| result.is_err()) {
+                Err("Invalid configuration".to_string())
+            } else {
+                Ok(String::new())
+            }
+        }
+        Command::Backtest {
+            files,
+            days,
+            policies,
+            reminders,
+        } => run_backtest(
+            &files,
+            days,
+            policies.as_deref(),
+            reminders.as_deref(),
+        ),
+    };
+
+    match result {
+        Ok(text) => print!("{}", text),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
     }
 }
@@ -0,0 +1,78 @@
+use serde::{Serialize, de::DeserializeOwned};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tracing::{debug, error};
+
+static IN_MEMORY: AtomicBool = AtomicBool::new(false);
+
+/// Stop writing to the state directory for the rest of the run, so state
+/// changes are only kept in memory.
+pub fn keep_in_memory() {
+    IN_MEMORY.store(true, Ordering::Relaxed);
+}
+
+/// Whether state is only kept in memory, see `keep_in_memory`.
+pub fn in_memory() -> bool {
+    IN_MEMORY.load(Ordering::Relaxed)
+}
+
+/// Directory holding the bot's persistent state files.
+pub fn state_dir() -> PathBuf {
+    PathBuf::from(
+        std::env::var("STATE_DIR").unwrap_or_else(|_| "state".to_string()),
+    )
+}
+
+/// Load a JSON state file, falling back to the default value when the file
+/// does not exist or cannot be parsed.
+pub fn load<T>(name: &str) -> T
+where
+    T: DeserializeOwned + Default,
+{
+    let path = state_dir().join(name);
+    let content = match std::fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(_) => {
+            debug!("No saved state at {}", path.display());
+            return T::default();
+        }
+    };
+
+    serde_json::from_str(&content).unwrap_or_else(|e| {
+        error!("Failed to parse state file {}: {}", path.display(), e);
+        T::default()
+    })
+}
+
+/// Save a JSON state file.  The file is written to a temporary location and
+/// renamed so a crash never leaves a truncated file behind.
+pub fn save<T>(name: &str, value: &T)
+where
+    T: Serialize,
+{
+    if in_memory() {
+        return;
+    }
+    let dir = state_dir();
+    if let Err(e) = std::fs::create_dir_all(&dir) {
+        error!("Failed to create state directory {}: {}", dir.display(), e);
+        return;
+    }
+
+    let path = dir.join(name);
+    let temp_path = dir.join(format!(".{}.tmp", name));
+
+    let content = match serde_json::to_string(value) {
+        Ok(content) => content,
+        Err(e) => {
+            error!("Failed to serialize state {}: {}", name, e);
+            return;
+        }
+    };
+
+    if let Err(e) = std::fs::write(&temp_path, content)
+        .and_then(|_| std::fs::rename(&temp_path, &path))
+    {
+        error!("Failed to save state file {}: {}", path.display(), e);
+    }
+}
@@ -0,0 +1,136 @@
+use chrono::{DateTime, Utc};
+
+const WIDTH: f64 = 800.0;
+const HEIGHT: f64 = 300.0;
+const MARGIN_LEFT: f64 = 50.0;
+const MARGIN_RIGHT: f64 = 20.0;
+const MARGIN_TOP: f64 = 30.0;
+const MARGIN_BOTTOM: f64 = 40.0;
+const Y_TICKS: u64 = 5;
+const X_TICKS: usize = 6;
+
+/// A single line on a chart
+pub struct Series {
+    pub label: String,
+    pub color: &'static str,
+    pub points: Vec<(DateTime<Utc>, u64)>,
+}
+
+/// Round the maximum value up so the Y axis ticks land on whole numbers.
+fn y_axis_max(max: u64) -> u64 {
+    let max = std::cmp::max(max, 1);
+    max.div_ceil(Y_TICKS) * Y_TICKS
+}
+
+/// Render a set of series as an inline SVG line chart.
+pub fn line_chart(title: &str, series: &[Series]) -> String {
+    let timestamps: Vec<DateTime<Utc>> = series
+        .iter()
+        .flat_map(|s| s.points.iter().map(|(t, _)| *t))
+        .collect();
+
+    let (Some(start), Some(end)) =
+        (timestamps.iter().min(), timestamps.iter().max())
+    else {
+        return format!(
+            "<p class=\"chart-empty\">{}: no data recorded yet.</p>\n",
+            title
+        );
+    };
+
+    let max_value = y_axis_max(
+        series
+            .iter()
+            .flat_map(|s| s.points.iter().map(|(_, v)| *v))
+            .max()
+            .unwrap_or(0),
+    );
+
+    let plot_width = WIDTH - MARGIN_LEFT - MARGIN_RIGHT;
+    let plot_height = HEIGHT - MARGIN_TOP - MARGIN_BOTTOM;
+    let span = std::cmp::max((*end - *start).num_seconds(), 1) as f64;
+
+    let x = |t: &DateTime<Utc>| {
+        MARGIN_LEFT + (*t - *start).num_seconds() as f64 / span * plot_width
+    };
+    let y = |v: u64| {
+        MARGIN_TOP + plot_height - v as f64 / max_value as f64 * plot_height
+    };
+
+    let mut result = format!(
+        "<svg class=\"trend-chart\" viewBox=\"0 0 {} {}\" xmlns=\"http://www.w3.org/2000/svg\">\n",
+        WIDTH, HEIGHT
+    );
+    result += &format!(
+        "<text x=\"{}\" y=\"18\" class=\"chart-title\" fill=\"currentColor\">{}</text>\n",
+        MARGIN_LEFT, title
+    );
+
+    // Horizontal grid lines and Y axis labels
+    for tick in 0..=Y_TICKS {
+        let value = max_value / Y_TICKS * tick;
+        result += &format!(
+            "<line x1=\"{}\" y1=\"{:.1}\" x2=\"{}\" y2=\"{:.1}\" stroke=\"currentColor\" stroke-opacity=\"0.2\" />\n",
+            MARGIN_LEFT,
+            y(value),
+            WIDTH - MARGIN_RIGHT,
+            y(value)
+        );
+        result += &format!(
+            "<text x=\"{}\" y=\"{:.1}\" text-anchor=\"end\" font-size=\"12\" fill=\"currentColor\">{}</text>\n",
+            MARGIN_LEFT - 6.0,
+            y(value) + 4.0,
+            value
+        );
+    }
+
+    // X axis date labels
+    let mut sorted_timestamps = timestamps.clone();
+    sorted_timestamps.sort();
+    sorted_timestamps.dedup();
+    let step = std::cmp::max(sorted_timestamps.len().div_ceil(X_TICKS), 1);
+    for t in sorted_timestamps.iter().step_by(step) {
+        result += &format!(
+            "<text x=\"{:.1}\" y=\"{}\" text-anchor=\"middle\" font-size=\"12\" fill=\"currentColor\">{}</text>\n",
+            x(t),
+            HEIGHT - MARGIN_BOTTOM + 18.0,
+            t.format("%Y-%m-%d")
+        );
+    }
+
+    // Data lines
+    for s in series {
+        let points: Vec<String> = s
+            .points
+            .iter()
+            .map(|(t, v)| format!("{:.1},{:.1}", x(t), y(*v)))
+            .collect();
+        result += &format!(
+            "<polyline fill=\"none\" stroke=\"{}\" stroke-width=\"2\" points=\"{}\"><title>{}</title></polyline>\n",
+            s.color,
+            points.join(" "),
+            s.label
+        );
+    }
+
+    // Legend
+    let mut legend_x = MARGIN_LEFT;
+    for s in series {
+        result += &format!(
+            "<rect x=\"{:.1}\" y=\"{}\" width=\"12\" height=\"12\" fill=\"{}\" />\n",
+            legend_x,
+            HEIGHT - 16.0,
+            s.color
+        );
+        result += &format!(
+            "<text x=\"{:.1}\" y=\"{}\" font-size=\"12\" fill=\"currentColor\">{}</text>\n",
+            legend_x + 16.0,
+            HEIGHT - 6.0,
+            s.label
+        );
+        legend_x += 24.0 + 8.0 * s.label.len() as f64;
+    }
+
+    result += "</svg>\n";
+    result
+}
@@ -1,15 +1,24 @@
+use crate::audit::{self, AuditQuery};
 use crate::changes::filter::should_include_change;
-use crate::changes::report::{self as ChangeReport, TimeInterval};
-use crate::changes::{self as Changes, status::NextStepOwner};
+use crate::changes::owners;
+use crate::changes::report::{
+    self as ChangeReport, ChangeFilter, GroupBy, ReportFormat, TimeInterval,
+};
+use crate::changes::reviewers;
+use crate::changes::status::{NextStepOwner, ReviewState};
+use crate::changes::{self as Changes};
 use crate::context::ServiceContext;
+use crate::webserver::charts::{self, Series};
 use crate::webserver::templates::*;
 use askama::Template;
 use axum::{
     Extension, Router,
-    extract::Path,
+    extract::{Path, Query},
+    http::{StatusCode, header},
     response::{Html, IntoResponse, Response},
     routing::get,
 };
+use serde::Deserialize;
 use tower::ServiceBuilder;
 
 pub async fn serve(context: ServiceContext, port: u16) {
@@ -19,10 +28,18 @@ pub async fn serve(context: ServiceContext, port: u16) {
         .route("/bot", get(root))
         .route("/bot/report", get(report_overall))
         .route("/bot/report-by-repo", get(report_repo))
+        .route("/bot/report-by-branch", get(report_branch))
+        .route("/bot/report-by-topic", get(report_topic))
         .route("/bot/report/{*projects}", get(report_projects))
         .route("/bot/review-status/{id}", get(review_status))
+        .route("/bot/topic/{topic}", get(topic_status))
         .route("/bot/style.css", get(css))
         .route("/bot/user/{*usernames}", get(report_users))
+        .route("/bot/maintainer/{username}", get(report_maintainer))
+        .route("/bot/reviewers", get(report_reviewers))
+        .route("/bot/trends", get(trends_overall))
+        .route("/bot/trends/{*projects}", get(trends_projects))
+        .route("/bot/admin/audit", get(audit_log))
         .layer(ServiceBuilder::new().layer(Extension(context)));
 
     // run it
@@ -41,6 +58,14 @@ async fn health() -> &'static str {
     "ok"
 }
 
+// Escape text from Gerrit or chat users for the HTML built here, which the
+// templates insert unescaped.
+fn escape_html(text: &str) -> String {
+    askama::filters::escape(text, askama::filters::Html)
+        .unwrap()
+        .to_string()
+}
+
 fn list_of_changes(
     changes: &ChangeReport::ChangesByOwnerAndTime,
     context: &ServiceContext,
@@ -102,7 +127,7 @@ fn list_of_changes(
 
             result += &format!(
                 "<div class=\"interval-section\">\n<h3 class=\"interval-header\">{}</h3>\n",
-                interval.to_string()
+                interval
             );
             result += "<div class=\"card-container\">\n";
 
@@ -141,6 +166,18 @@ fn list_of_changes(
                         change_data.change.id_number
                     );
 
+                    if let Some(claim) = context
+                        .lock()
+                        .unwrap()
+                        .claims
+                        .get(change_data.change.id_number)
+                    {
+                        result += &format!(
+                            "<div class=\"claim\">Claimed by {}</div>\n",
+                            escape_html(&claim.name)
+                        );
+                    }
+
                     // Add insertions/deletions box
                     result += &format!(
                         "<div class=\"change-stats\"><span class=\"insertions\">+{}</span> / <span class=\"deletions\">-{}</span></div>\n",
@@ -160,39 +197,187 @@ fn list_of_changes(
     result
 }
 
+#[derive(Deserialize)]
+struct ReportQuery {
+    format: Option<String>,
+    branch: Option<String>,
+    topic: Option<String>,
+}
+
+impl ReportQuery {
+    /// Parse the requested format; `None` means the HTML page.
+    fn format(&self) -> Result<Option<ReportFormat>, String> {
+        self.format.as_deref().map(str::parse).transpose()
+    }
+
+    /// Build a filter from the branch and topic parameters.
+    fn filter(&self) -> ChangeFilter {
+        ChangeFilter {
+            branch: self.branch.clone(),
+            topic: self.topic.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+fn formatted_report(format: ReportFormat, report: String) -> Response {
+    ([(header::CONTENT_TYPE, format.content_type())], report).into_response()
+}
+
 async fn report_overall(
+    Query(query): Query<ReportQuery>,
     Extension(context): Extension<ServiceContext>,
-) -> Html<String> {
-    let changes = ChangeReport::changes_by_owner_time(&context, None, None);
-    let report_text = ChangeReport::report_by_owner_time(&changes);
+) -> Response {
+    let format = match query.format() {
+        Ok(format) => format,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+
+    let changes = ChangeReport::changes_by_owner_time(
+        &context,
+        &query.filter(),
+        chrono::Utc::now(),
+    );
+    if let Some(format) = format {
+        return formatted_report(
+            format,
+            ChangeReport::report_by_owner_time(&changes, format),
+        );
+    }
+
+    let report_text =
+        ChangeReport::report_by_owner_time(&changes, ReportFormat::Text);
     let changes_text = list_of_changes(&changes, &context, false);
 
     let template = OverallTemplate {
         report_text,
         changes_text,
     };
-    Html(template.render().unwrap())
+    Html(template.render().unwrap()).into_response()
 }
 
-async fn report_repo(
-    Extension(context): Extension<ServiceContext>,
-) -> Html<String> {
-    let report_text = ChangeReport::report_by_repo(
+fn report_group(
+    query: ReportQuery,
+    context: ServiceContext,
+    group_by: GroupBy,
+) -> Response {
+    let format = match query.format() {
+        Ok(format) => format,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+
+    if let Some(format) = format {
+        return formatted_report(
+            format,
+            ChangeReport::report_by_group(
+                &context,
+                &query.filter(),
+                group_by,
+                format,
+                None::<fn(&str) -> String>,
+            ),
+        );
+    }
+
+    let report_text = ChangeReport::report_by_group(
         &context,
-        None,
-        Some(|repo: &str| {
-            format!("<a href=\"/bot/report/{}\">{}</a>", repo, repo)
+        &query.filter(),
+        group_by,
+        ReportFormat::Text,
+        Some(|group: &str| {
+            let link = match group_by {
+                GroupBy::Repo => {
+                    format!("/bot/report/{}", urlencoding::encode(group))
+                }
+                GroupBy::Branch => {
+                    format!("/bot/report?branch={}", urlencoding::encode(group))
+                }
+                GroupBy::Topic => {
+                    format!("/bot/topic/{}", urlencoding::encode(group))
+                }
+            };
+            format!("<a href=\"{}\">{}</a>", link, escape_html(group))
         }),
     );
 
-    let template = RepoTemplate { report_text };
+    let template = GroupTemplate {
+        group: group_by.to_string(),
+        report_text,
+    };
+    Html(template.render().unwrap()).into_response()
+}
+
+async fn report_repo(
+    Query(query): Query<ReportQuery>,
+    Extension(context): Extension<ServiceContext>,
+) -> Response {
+    report_group(query, context, GroupBy::Repo)
+}
+
+async fn report_branch(
+    Query(query): Query<ReportQuery>,
+    Extension(context): Extension<ServiceContext>,
+) -> Response {
+    report_group(query, context, GroupBy::Branch)
+}
+
+async fn report_topic(
+    Query(query): Query<ReportQuery>,
+    Extension(context): Extension<ServiceContext>,
+) -> Response {
+    report_group(query, context, GroupBy::Topic)
+}
+
+async fn topic_status(
+    Path(topic): Path<String>,
+    Extension(context): Extension<ServiceContext>,
+) -> impl IntoResponse {
+    let mut changes: Vec<Changes::container::Change> = context
+        .lock()
+        .unwrap()
+        .changes
+        .changes
+        .values()
+        .filter(|change| change.change.topic == topic)
+        .cloned()
+        .collect();
+    changes.sort_by(|a, b| {
+        (&a.change.project, a.change.id_number)
+            .cmp(&(&b.change.project, b.change.id_number))
+    });
+
+    let ready = !changes.is_empty()
+        && changes
+            .iter()
+            .all(|change| change.review_state == ReviewState::ReadyToSubmit);
+
+    let template = TopicTemplate {
+        topic,
+        ready,
+        changes: changes
+            .iter()
+            .map(|change| TopicChange {
+                project: change.change.project.clone(),
+                id_number: change.change.id_number,
+                subject: change.change.subject.clone(),
+                review_status: format!("{:?}", change.review_state),
+                ready: change.review_state == ReviewState::ReadyToSubmit,
+            })
+            .collect(),
+    };
     Html(template.render().unwrap())
 }
 
 async fn report_projects(
     Path(projects): Path<String>,
+    Query(query): Query<ReportQuery>,
     Extension(context): Extension<ServiceContext>,
-) -> Html<String> {
+) -> Response {
+    let format = match query.format() {
+        Ok(format) => format,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+
     // Remove the leading slash that comes with the wildcard pattern
     let projects = projects.strip_prefix('/').unwrap_or(&projects).to_string();
 
@@ -205,8 +390,11 @@ async fn report_projects(
     for project_name in &project_names {
         let changes = ChangeReport::changes_by_owner_time(
             &context,
-            Some(project_name.to_string()),
-            None,
+            &ChangeFilter {
+                project: Some(project_name.to_string()),
+                ..query.filter()
+            },
+            chrono::Utc::now(),
         );
 
         // Merge changes into combined_changes
@@ -230,7 +418,17 @@ async fn report_projects(
         }
     }
 
-    let report_text = ChangeReport::report_by_owner_time(&combined_changes);
+    if let Some(format) = format {
+        return formatted_report(
+            format,
+            ChangeReport::report_by_owner_time(&combined_changes, format),
+        );
+    }
+
+    let report_text = ChangeReport::report_by_owner_time(
+        &combined_changes,
+        ReportFormat::Text,
+    );
     let changes_text = list_of_changes(&combined_changes, &context, false);
 
     // If there are multiple projects, display "Projects (count)" instead of listing them all
@@ -245,13 +443,19 @@ async fn report_projects(
         report_text,
         changes_text,
     };
-    Html(template.render().unwrap())
+    Html(template.render().unwrap()).into_response()
 }
 
 async fn report_users(
     Path(usernames): Path<String>,
+    Query(query): Query<ReportQuery>,
     Extension(context): Extension<ServiceContext>,
-) -> Html<String> {
+) -> Response {
+    let format = match query.format() {
+        Ok(format) => format,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+
     // Remove the leading slash that comes with the wildcard pattern
     let usernames = usernames
         .strip_prefix('/')
@@ -267,8 +471,11 @@ async fn report_users(
     for username in &username_list {
         let changes = ChangeReport::changes_by_owner_time(
             &context,
-            None,
-            Some(username.to_string()),
+            &ChangeFilter {
+                owner: Some(username.to_string()),
+                ..query.filter()
+            },
+            chrono::Utc::now(),
         );
 
         // Merge changes into combined_changes
@@ -292,7 +499,17 @@ async fn report_users(
         }
     }
 
-    let report_text = ChangeReport::report_by_owner_time(&combined_changes);
+    if let Some(format) = format {
+        return formatted_report(
+            format,
+            ChangeReport::report_by_owner_time(&combined_changes, format),
+        );
+    }
+
+    let report_text = ChangeReport::report_by_owner_time(
+        &combined_changes,
+        ReportFormat::Text,
+    );
     let changes_text = list_of_changes(&combined_changes, &context, true);
 
     // If there are multiple users, display "Users (count)" instead of listing them all
@@ -307,7 +524,7 @@ async fn report_users(
         report_text,
         changes_text,
     };
-    Html(template.render().unwrap())
+    Html(template.render().unwrap()).into_response()
 }
 
 async fn review_status(
@@ -315,14 +532,32 @@ async fn review_status(
     Extension(context): Extension<ServiceContext>,
 ) -> impl IntoResponse {
     let change: Option<Changes::container::Change>;
+    let exemptions: Vec<(String, String)>;
     {
-        let changes = &context.lock().unwrap().changes;
+        let ctx = context.lock().unwrap();
+        let changes = &ctx.changes;
 
         let id = change_id.parse::<u64>();
         change = match id {
             Ok(i) => changes.get(i),
             _ => changes.get_by_change_id(&change_id),
-        }
+        };
+
+        exemptions = change
+            .iter()
+            .flat_map(|change| ctx.exemptions.matching(&change.change))
+            .map(|exemption| {
+                (
+                    exemption.name.clone(),
+                    exemption
+                        .exempt_from
+                        .iter()
+                        .map(|automation| automation.name())
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                )
+            })
+            .collect();
     }
 
     if let Some(change) = change {
@@ -334,18 +569,263 @@ async fn review_status(
             change_id,
             review_status: format!("{:?}", change.review_state),
             gerrit_url,
+            exemptions,
         };
         Html(template.render().unwrap()).into_response()
     } else {
-        let template = ChangeNotFoundTemplate { change_id };
-        (
-            axum::http::StatusCode::NOT_FOUND,
-            Html(template.render().unwrap()),
-        )
+        let template = ChangeNotFoundTemplate {
+            audit_change: change_id.parse().ok(),
+            change_id,
+        };
+        (StatusCode::NOT_FOUND, Html(template.render().unwrap()))
             .into_response()
     }
 }
 
+async fn report_maintainer(
+    Path(username): Path<String>,
+    Query(query): Query<ReportQuery>,
+    Extension(context): Extension<ServiceContext>,
+) -> Response {
+    let format = match query.format() {
+        Ok(format) => format,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+
+    let changes = owners::changes_by_maintainer(&context, &username);
+    if let Some(format) = format {
+        return formatted_report(
+            format,
+            ChangeReport::report_by_owner_time(&changes, format),
+        );
+    }
+
+    let report_text =
+        ChangeReport::report_by_owner_time(&changes, ReportFormat::Text);
+    let changes_text = list_of_changes(&changes, &context, false);
+
+    let template = MaintainerTemplate {
+        username,
+        report_text,
+        changes_text,
+    };
+    Html(template.render().unwrap()).into_response()
+}
+
+#[derive(Deserialize)]
+struct ReviewersQuery {
+    days: Option<i64>,
+    format: Option<String>,
+}
+
+async fn report_reviewers(
+    Query(query): Query<ReviewersQuery>,
+    Extension(context): Extension<ServiceContext>,
+) -> Response {
+    let days = match ChangeReport::check_days(
+        query.days.unwrap_or(reviewers::DEFAULT_REVIEWER_DAYS),
+    ) {
+        Ok(days) => days,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+
+    if let Some(format) = query.format {
+        return match format.parse::<ReportFormat>() {
+            Ok(format) => formatted_report(
+                format,
+                reviewers::report_by_reviewer(&context, days, None, format),
+            ),
+            Err(e) => (StatusCode::BAD_REQUEST, e).into_response(),
+        };
+    }
+
+    let template = ReviewersTemplate {
+        days,
+        report_text: reviewers::report_by_reviewer(
+            &context,
+            days,
+            None,
+            ReportFormat::Text,
+        ),
+    };
+    Html(template.render().unwrap()).into_response()
+}
+
+fn trend_charts(context: &ServiceContext, projects: &[&str]) -> String {
+    let snapshots = context.lock().unwrap().trends.snapshots.clone();
+    let counts: Vec<_> = snapshots
+        .iter()
+        .map(|snapshot| (snapshot.timestamp, snapshot.counts(projects)))
+        .collect();
+
+    let owner_series = [
+        (NextStepOwner::Community, "#268bd2"),
+        (NextStepOwner::Maintainer, "#859900"),
+        (NextStepOwner::Author, "#cb4b16"),
+    ]
+    .into_iter()
+    .map(|(owner, color)| Series {
+        label: format!("{:?}", owner),
+        color,
+        points: counts
+            .iter()
+            .map(|(timestamp, c)| (*timestamp, c.get_owner_count(owner)))
+            .collect(),
+    })
+    .collect::<Vec<_>>();
+
+    let interval_series = [
+        (TimeInterval::Under24Hours, "#2aa198"),
+        (TimeInterval::Under72Hours, "#268bd2"),
+        (TimeInterval::Under2Weeks, "#6c71c4"),
+        (TimeInterval::Under8Weeks, "#d33682"),
+        (TimeInterval::Over8Weeks, "#dc322f"),
+    ]
+    .into_iter()
+    .map(|(interval, color)| Series {
+        label: interval.to_string(),
+        color,
+        points: counts
+            .iter()
+            .map(|(timestamp, c)| (*timestamp, c.get_interval_count(interval)))
+            .collect(),
+    })
+    .collect::<Vec<_>>();
+
+    charts::line_chart("Open changes by next step owner", &owner_series)
+        + &charts::line_chart(
+            "Open changes by time in current state",
+            &interval_series,
+        )
+}
+
+async fn trends_overall(
+    Extension(context): Extension<ServiceContext>,
+) -> Html<String> {
+    let template = TrendsTemplate {
+        title: "Overall Trends".to_string(),
+        charts_text: trend_charts(&context, &[]),
+    };
+    Html(template.render().unwrap())
+}
+
+async fn trends_projects(
+    Path(projects): Path<String>,
+    Extension(context): Extension<ServiceContext>,
+) -> Html<String> {
+    // Remove the leading slash that comes with the wildcard pattern
+    let projects = projects.strip_prefix('/').unwrap_or(&projects).to_string();
+
+    // Split projects by space to get multiple project names
+    let project_names: Vec<&str> = projects.split_whitespace().collect();
+
+    // If there are multiple projects, display "Projects (count)" instead of listing them all
+    let display_name = if projects.contains(' ') {
+        format!("Projects ({})", project_names.len())
+    } else {
+        projects.clone()
+    };
+
+    let template = TrendsTemplate {
+        title: format!("{} Trends", display_name),
+        charts_text: trend_charts(&context, &project_names),
+    };
+    Html(template.render().unwrap())
+}
+
+/// Number of audit entries shown when no limit is given.
+const DEFAULT_AUDIT_LIMIT: usize = 200;
+
+#[derive(Deserialize)]
+struct AuditLogQuery {
+    change: Option<u64>,
+    action: Option<String>,
+    days: Option<i64>,
+    limit: Option<usize>,
+    format: Option<String>,
+}
+
+async fn audit_log(Query(query): Query<AuditLogQuery>) -> Response {
+    let action = match query.action.as_deref().map(str::parse).transpose() {
+        Ok(action) => action,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    let days = match query.days.map(ChangeReport::check_days).transpose() {
+        Ok(days) => days,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+
+    // The whole log is read, so keep it off the runtime thread
+    let audit_query = AuditQuery {
+        change: query.change,
+        action,
+        since: days
+            .map(|days| chrono::Utc::now() - chrono::Duration::days(days)),
+        limit: Some(query.limit.unwrap_or(DEFAULT_AUDIT_LIMIT)),
+    };
+    let entries =
+        match tokio::task::spawn_blocking(move || audit::query(&audit_query))
+            .await
+        {
+            Ok(entries) => entries,
+            Err(e) => {
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("Failed to read the audit log: {}", e),
+                )
+                    .into_response();
+            }
+        };
+
+    match query.format.as_deref() {
+        None => {}
+        Some("json") => {
+            return (
+                [(header::CONTENT_TYPE, "application/json")],
+                serde_json::to_string(&entries).unwrap(),
+            )
+                .into_response();
+        }
+        Some(format) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                format!("Unsupported audit log format: {}", format),
+            )
+                .into_response();
+        }
+    }
+
+    // Describe the filters and keep them for the JSON link
+    let mut filters = Vec::new();
+    let mut params = Vec::new();
+    if let Some(change) = query.change {
+        filters.push(format!("on change {}", change));
+        params.push(format!("change={}", change));
+    }
+    if let Some(action) = action {
+        filters.push(format!("of type {}", action.name()));
+        params.push(format!("action={}", action.name()));
+    }
+    if let Some(days) = days {
+        filters.push(format!("in the last {} days", days));
+        params.push(format!("days={}", days));
+    }
+    if let Some(limit) = query.limit {
+        params.push(format!("limit={}", limit));
+    }
+    params.push("format=json".to_string());
+
+    let template = AuditTemplate {
+        filter: filters
+            .iter()
+            .map(|filter| format!(" {}", filter))
+            .collect(),
+        json_url: format!("/bot/admin/audit?{}", params.join("&")),
+        entries,
+    };
+    Html(template.render().unwrap()).into_response()
+}
+
 async fn css() -> Response {
     let css_content = include_str!("../../templates/style.css");
     Response::builder()
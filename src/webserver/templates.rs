@@ -8,11 +8,28 @@ pub struct OverallTemplate {
 }
 
 #[derive(Template)]
-#[template(path = "repo.html")]
-pub struct RepoTemplate {
+#[template(path = "group.html")]
+pub struct GroupTemplate {
+    pub group: String,
     pub report_text: String,
 }
 
+pub struct TopicChange {
+    pub project: String,
+    pub id_number: u64,
+    pub subject: String,
+    pub review_status: String,
+    pub ready: bool,
+}
+
+#[derive(Template)]
+#[template(path = "topic.html")]
+pub struct TopicTemplate {
+    pub topic: String,
+    pub ready: bool,
+    pub changes: Vec<TopicChange>,
+}
+
 #[derive(Template)]
 #[template(path = "project.html")]
 pub struct ProjectTemplate {
@@ -35,14 +52,47 @@ pub struct ChangeTemplate {
     pub change_id: String,
     pub review_status: String,
     pub gerrit_url: String,
+    /// Exemption names with the automation they exempt the change from
+    pub exemptions: Vec<(String, String)>,
 }
 
 #[derive(Template)]
 #[template(path = "change_not_found.html")]
 pub struct ChangeNotFoundTemplate {
     pub change_id: String,
+    pub audit_change: Option<u64>,
+}
+
+#[derive(Template)]
+#[template(path = "maintainer.html")]
+pub struct MaintainerTemplate {
+    pub username: String,
+    pub report_text: String,
+    pub changes_text: String,
+}
+
+#[derive(Template)]
+#[template(path = "reviewers.html")]
+pub struct ReviewersTemplate {
+    pub days: i64,
+    pub report_text: String,
+}
+
+#[derive(Template)]
+#[template(path = "trends.html")]
+pub struct TrendsTemplate {
+    pub title: String,
+    pub charts_text: String,
 }
 
 #[derive(Template)]
 #[template(path = "root.html")]
 pub struct RootTemplate;
+
+#[derive(Template)]
+#[template(path = "audit.html")]
+pub struct AuditTemplate {
+    pub filter: String,
+    pub json_url: String,
+    pub entries: Vec<crate::audit::AuditEntry>,
+}